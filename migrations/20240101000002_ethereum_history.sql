@@ -0,0 +1,34 @@
+-- Transfers indexed from node logs and blocks, one row per transfer touching a tracked address
+CREATE TABLE IF NOT EXISTS ethereum_transfers (
+    id SERIAL PRIMARY KEY,
+    address VARCHAR NOT NULL,
+    chain VARCHAR NOT NULL,
+    tx_hash VARCHAR NOT NULL,
+    block_number BIGINT NOT NULL,
+    -- Log index for ERC-20 transfers; -(transaction index + 1) for native transfers
+    log_index BIGINT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    from_address VARCHAR NOT NULL,
+    to_address VARCHAR NOT NULL,
+    token_address VARCHAR,
+    token_symbol VARCHAR NOT NULL,
+    decimals SMALLINT NOT NULL,
+    raw_amount VARCHAR NOT NULL,
+    status VARCHAR NOT NULL,
+    fee_wei VARCHAR,
+    created_at TIMESTAMP DEFAULT NOW(),
+    UNIQUE(address, chain, tx_hash, log_index)
+);
+
+-- Newest and oldest blocks scanned per address, so history is indexed forward and older history
+-- beyond the first lookback window can be backfilled on demand
+CREATE TABLE IF NOT EXISTS ethereum_index_state (
+    address VARCHAR NOT NULL,
+    chain VARCHAR NOT NULL,
+    last_indexed_block BIGINT NOT NULL,
+    first_indexed_block BIGINT NOT NULL,
+    updated_at TIMESTAMP DEFAULT NOW(),
+    PRIMARY KEY (address, chain)
+);
+
+CREATE INDEX IF NOT EXISTS idx_ethereum_transfers_lookup ON ethereum_transfers(address, chain, block_number DESC, log_index DESC);
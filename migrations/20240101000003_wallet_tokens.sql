@@ -0,0 +1,21 @@
+-- ERC-20 contracts discovered from Transfer logs received by each wallet
+CREATE TABLE IF NOT EXISTS wallet_tokens (
+    id SERIAL PRIMARY KEY,
+    address VARCHAR NOT NULL,
+    chain VARCHAR NOT NULL,
+    token_address VARCHAR NOT NULL,
+    first_seen_block BIGINT NOT NULL,
+    created_at TIMESTAMP DEFAULT NOW(),
+    UNIQUE(address, chain, token_address)
+);
+
+-- Last block scanned for token discovery per wallet
+CREATE TABLE IF NOT EXISTS token_discovery_state (
+    address VARCHAR NOT NULL,
+    chain VARCHAR NOT NULL,
+    last_scanned_block BIGINT NOT NULL,
+    updated_at TIMESTAMP DEFAULT NOW(),
+    PRIMARY KEY (address, chain)
+);
+
+CREATE INDEX IF NOT EXISTS idx_wallet_tokens_lookup ON wallet_tokens(address, chain);
@@ -0,0 +1,4 @@
+-- Allow any configured EVM chain in user_wallets, plus 'evm' for an address tracked on every EVM chain.
+-- Chains are validated against the chain registry by the API, so the constraint only guards the format.
+ALTER TABLE user_wallets DROP CONSTRAINT IF EXISTS user_wallets_chain_check;
+ALTER TABLE user_wallets ADD CONSTRAINT user_wallets_chain_check CHECK (chain ~ '^[a-z0-9-]+$');
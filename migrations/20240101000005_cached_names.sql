@@ -0,0 +1,14 @@
+-- Cached ENS/SNS lookups: forward (name -> address) and reverse (address -> primary name).
+-- A NULL result caches a lookup that found nothing.
+CREATE TABLE IF NOT EXISTS cached_names (
+    id SERIAL PRIMARY KEY,
+    lookup VARCHAR NOT NULL,
+    naming_system VARCHAR NOT NULL,
+    direction VARCHAR NOT NULL CHECK (direction IN ('forward', 'reverse')),
+    result VARCHAR,
+    created_at TIMESTAMP DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    UNIQUE(lookup, naming_system, direction)
+);
+
+CREATE INDEX IF NOT EXISTS idx_cached_names_lookup ON cached_names(lookup, naming_system, direction, expires_at);
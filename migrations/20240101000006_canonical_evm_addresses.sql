@@ -0,0 +1,37 @@
+-- EVM addresses are stored lowercase. Merge user_wallets rows that differ only in address case,
+-- keeping the primary (then oldest) row and carrying over a label and primary flag from the others.
+CREATE TEMP TABLE wallet_merge AS
+SELECT id,
+       FIRST_VALUE(id) OVER (
+           PARTITION BY user_id, chain, LOWER(address)
+           ORDER BY is_primary DESC NULLS LAST, created_at ASC, id ASC
+       ) AS keep_id
+FROM user_wallets
+WHERE address ~* '^0x[0-9a-f]{40}$';
+
+UPDATE user_wallets w
+SET label = COALESCE(w.label, merged.label),
+    is_primary = merged.is_primary
+FROM (
+    SELECT m.keep_id,
+           (ARRAY_AGG(d.label ORDER BY d.created_at, d.id) FILTER (WHERE d.label IS NOT NULL))[1] AS label,
+           BOOL_OR(COALESCE(d.is_primary, FALSE)) AS is_primary
+    FROM wallet_merge m
+    JOIN user_wallets d ON d.id = m.id
+    GROUP BY m.keep_id
+) merged
+WHERE w.id = merged.keep_id;
+
+DELETE FROM user_wallets w
+USING wallet_merge m
+WHERE w.id = m.id AND m.id <> m.keep_id;
+
+UPDATE user_wallets
+SET address = LOWER(address)
+WHERE address ~* '^0x[0-9a-f]{40}$' AND address <> LOWER(address);
+
+DROP TABLE wallet_merge;
+
+-- Cached balances are disposable, so mixed-case entries are dropped rather than merged
+DELETE FROM cached_balances
+WHERE address ~* '^0x[0-9a-f]{40}$' AND address <> LOWER(address);
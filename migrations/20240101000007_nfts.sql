@@ -0,0 +1,38 @@
+-- ERC-721/ERC-1155 tokens each wallet has received, found from Transfer/TransferSingle/TransferBatch logs.
+-- Rows are candidates: ownership is verified on-chain whenever the wallet's NFTs are served.
+CREATE TABLE IF NOT EXISTS wallet_nfts (
+    id SERIAL PRIMARY KEY,
+    address VARCHAR NOT NULL,
+    chain VARCHAR NOT NULL,
+    contract_address VARCHAR NOT NULL,
+    token_id VARCHAR NOT NULL, -- decimal uint256
+    standard VARCHAR NOT NULL CHECK (standard IN ('erc721', 'erc1155')),
+    first_seen_block BIGINT NOT NULL,
+    created_at TIMESTAMP DEFAULT NOW(),
+    UNIQUE(address, chain, contract_address, token_id)
+);
+
+-- Last block scanned for NFT discovery per wallet
+CREATE TABLE IF NOT EXISTS nft_discovery_state (
+    address VARCHAR NOT NULL,
+    chain VARCHAR NOT NULL,
+    last_scanned_block BIGINT NOT NULL,
+    updated_at TIMESTAMP DEFAULT NOW(),
+    PRIMARY KEY (address, chain)
+);
+
+-- Resolved token metadata JSON by NFT id ("<contract>/<token id>" on EVM chains).
+-- A NULL metadata caches a token URI that could not be fetched or parsed.
+CREATE TABLE IF NOT EXISTS cached_nft_metadata (
+    id SERIAL PRIMARY KEY,
+    nft_id VARCHAR NOT NULL,
+    chain VARCHAR NOT NULL,
+    token_uri VARCHAR,
+    metadata JSONB,
+    created_at TIMESTAMP DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    UNIQUE(nft_id, chain)
+);
+
+CREATE INDEX IF NOT EXISTS idx_wallet_nfts_lookup ON wallet_nfts(address, chain);
+CREATE INDEX IF NOT EXISTS idx_cached_nft_metadata_lookup ON cached_nft_metadata(nft_id, chain, expires_at);
@@ -0,0 +1,9 @@
+-- Last downloaded copy of each configured token list, so the in-memory registry can start without a download.
+-- The validators are sent back as If-None-Match/If-Modified-Since when the list is refreshed.
+CREATE TABLE IF NOT EXISTS token_list_snapshots (
+    url VARCHAR PRIMARY KEY,
+    etag VARCHAR,
+    last_modified VARCHAR,
+    tokens JSONB NOT NULL,
+    updated_at TIMESTAMP DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+-- Confidence reported by the price source (Jupiter: "high", "medium" or "low").
+-- A NULL price_usd caches a token no source could price, as opposed to one priced at zero.
+ALTER TABLE cached_prices ADD COLUMN IF NOT EXISTS confidence VARCHAR;
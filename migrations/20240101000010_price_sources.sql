@@ -0,0 +1,3 @@
+-- Provider that supplied each cached price ("coingecko", "jupiter", "defillama" or "dex").
+-- NULL for unpriced tokens and for prices cached outside the provider chain, such as NFT floors.
+ALTER TABLE cached_prices ADD COLUMN IF NOT EXISTS source VARCHAR;
@@ -0,0 +1,27 @@
+-- USD price candles per token, bucketed at 5m, 1h and 1d. bucket_start is the time the bucket opens.
+-- Buckets are filled by the background collector and by backfills from the price providers' historical endpoints.
+CREATE TABLE IF NOT EXISTS price_history (
+    chain VARCHAR NOT NULL,
+    token_id VARCHAR NOT NULL,
+    resolution VARCHAR NOT NULL CHECK (resolution IN ('5m', '1h', '1d')),
+    bucket_start TIMESTAMPTZ NOT NULL,
+    -- Unscaled NUMERIC, so charts don't pick up binary rounding
+    open NUMERIC NOT NULL,
+    high NUMERIC NOT NULL,
+    low NUMERIC NOT NULL,
+    close NUMERIC NOT NULL,
+    updated_at TIMESTAMP DEFAULT NOW(),
+    PRIMARY KEY (chain, token_id, resolution, bucket_start)
+);
+
+-- Earliest bucket each token has been backfilled from, so ranges are only fetched from providers once
+CREATE TABLE IF NOT EXISTS price_history_backfills (
+    chain VARCHAR NOT NULL,
+    token_id VARCHAR NOT NULL,
+    resolution VARCHAR NOT NULL,
+    covered_from TIMESTAMPTZ NOT NULL,
+    backfilled_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (chain, token_id, resolution)
+);
+
+CREATE INDEX IF NOT EXISTS idx_price_history_pruning ON price_history(resolution, bucket_start);
@@ -0,0 +1,11 @@
+-- Currency portfolio values are converted to when a request doesn't name one; NULL means USD only
+ALTER TABLE users ADD COLUMN IF NOT EXISTS default_currency VARCHAR(3);
+
+-- Units of each fiat currency per USD, kept exact so converted values don't pick up binary rounding
+CREATE TABLE IF NOT EXISTS cached_fx_rates (
+    id SERIAL PRIMARY KEY,
+    currency VARCHAR(3) NOT NULL UNIQUE,
+    rate NUMERIC NOT NULL,
+    created_at TIMESTAMP DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,2 @@
+-- Token prices can be far below 1e-8 USD; an unscaled NUMERIC keeps every digit the provider returned
+ALTER TABLE cached_prices ALTER COLUMN price_usd TYPE NUMERIC;
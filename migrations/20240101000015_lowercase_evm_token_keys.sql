@@ -0,0 +1,15 @@
+-- Per-token EVM rows are keyed by lowercase address like wallets. Cached rows written under checksummed
+-- addresses are dropped; recorded price history is kept and rekeyed.
+DELETE FROM cached_metadata
+WHERE token_id ~* '^0x[0-9a-f]{40}$' AND token_id <> LOWER(token_id);
+
+DELETE FROM cached_prices
+WHERE token_id ~* '^0x[0-9a-f]{40}$' AND token_id <> LOWER(token_id);
+
+UPDATE price_history
+SET token_id = LOWER(token_id)
+WHERE token_id ~* '^0x[0-9a-f]{40}$' AND token_id <> LOWER(token_id);
+
+UPDATE price_history_backfills
+SET token_id = LOWER(token_id)
+WHERE token_id ~* '^0x[0-9a-f]{40}$' AND token_id <> LOWER(token_id);
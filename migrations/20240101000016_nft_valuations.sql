@@ -0,0 +1,9 @@
+-- Floor-price value of each wallet's NFTs from the last valuation, read by portfolios so they
+-- don't value NFTs inline
+CREATE TABLE IF NOT EXISTS nft_valuations (
+    address VARCHAR NOT NULL,
+    chain VARCHAR NOT NULL,
+    value_usd NUMERIC NOT NULL,
+    valued_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (address, chain)
+);
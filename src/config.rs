@@ -1,16 +1,206 @@
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
 
+// Multicall3 is deployed at the same address on every supported EVM chain
+const DEFAULT_MULTICALL_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+// Built-in EVM chains: (chain id, name, public RPC URL, native symbol, native CoinGecko id)
+const KNOWN_EVM_CHAINS: &[(u64, &str, &str, &str, &str)] = &[
+    (1, "ethereum", "https://eth.llamarpc.com", "ETH", "ethereum"),
+    (137, "polygon", "https://polygon-rpc.com", "POL", "polygon-ecosystem-token"),
+    (42161, "arbitrum", "https://arb1.arbitrum.io/rpc", "ETH", "ethereum"),
+    (10, "optimism", "https://mainnet.optimism.io", "ETH", "ethereum"),
+    (8453, "base", "https://mainnet.base.org", "ETH", "ethereum"),
+    (56, "bsc", "https://bsc-dataseed.bnbchain.org", "BNB", "binancecoin"),
+];
+
+// OpenSea chain slugs for the built-in chains it indexes, used for NFT floor prices: (chain id, slug)
+const KNOWN_OPENSEA_CHAINS: &[(u64, &str)] = &[
+    (1, "ethereum"),
+    (137, "matic"),
+    (42161, "arbitrum"),
+    (10, "optimism"),
+    (8453, "base"),
+];
+
+// How far behind the head a wallet's first discovery scan starts on built-in L2s and sidechains, about
+// 90 days of blocks; scanning their full history would take hundreds of log queries: (chain id, blocks)
+const KNOWN_DISCOVERY_LOOKBACK_BLOCKS: &[(u64, u64)] = &[
+    (137, 3_888_000),
+    (42161, 31_104_000),
+    (10, 3_888_000),
+    (8453, 3_888_000),
+    (56, 10_368_000),
+];
+
+// Uniswap V2-compatible routers used for on-chain DEX prices:
+// (chain id, router, wrapped native token, USD stablecoin, stablecoin decimals)
+const KNOWN_DEX_ROUTERS: &[(u64, &str, &str, &str, u8)] = &[
+    (1, "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D", "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", 6),
+    (137, "0xa5E0829CaCEd8fFDD4De3c43696c57F7D7A678ff", "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270", "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174", 6),
+    (42161, "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506", "0x82aF49447D8a07e3bd95BD0d56f35241523fBab1", "0xFF970A61A04b1cA14834A43f5dE4533eBDDB5CC8", 6),
+    (8453, "0x4752ba5DBc23f44D87826276BF6Fd6b1C372aD24", "0x4200000000000000000000000000000000000006", "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913", 6),
+    (56, "0x10ED43C718714eb63d5aA57B78B54704E256024E", "0xbb4CdB9CBd36B01bD8cBAE6dF8f7FBB7F8F3C9c3", "0x55d398326f99059fF775485246999027B3197955", 18),
+];
+
+// Price providers tried in order when PRICE_PROVIDERS doesn't list a chain
+const DEFAULT_SOLANA_PRICE_PROVIDERS: &[&str] = &["jupiter", "coingecko", "defillama"];
+const DEFAULT_BITCOIN_PRICE_PROVIDERS: &[&str] = &["coingecko", "defillama"];
+// On-chain DEX prices are opt-in through PRICE_PROVIDERS, e.g. "ethereum=coingecko,defillama,dex"
+const DEFAULT_EVM_PRICE_PROVIDERS: &[&str] = &["coingecko", "defillama"];
+
+// Popular tokens per chain, always balance-checked to seed wallets not yet scanned for discovery:
+// (chain id, address, symbol, CoinGecko id)
+const KNOWN_EVM_TOKENS: &[(u64, &str, &str, &str)] = &[
+    (1, "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "USDC", "usd-coin"),
+    (1, "0xdAC17F958D2ee523a2206206994597C13D831ec7", "USDT", "tether"),
+    (1, "0x6B175474E89094C44Da98b954EedeAC495271d0F", "DAI", "dai"),
+    (1, "0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599", "WBTC", "wrapped-bitcoin"),
+    (1, "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "WETH", "ethereum"),
+    (1, "0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984", "UNI", "uniswap"),
+    (1, "0x514910771AF9Ca656af840dff83E8264EcF986CA", "LINK", "chainlink"),
+    (1, "0x7Fc66500c84A76Ad7e9c93437bFc5Ac33E2DDaE9", "AAVE", "aave"),
+    (137, "0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359", "USDC", "usd-coin"),
+    (137, "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174", "USDC.e", "usd-coin"),
+    (137, "0xc2132D05D31c914a87C6611C10748AEb04B58e8F", "USDT", "tether"),
+    (137, "0x8f3Cf7ad23Cd3CaDbD9735AFf958023239c6A063", "DAI", "dai"),
+    (137, "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619", "WETH", "ethereum"),
+    (137, "0x1BFD67037B42Cf73acF2047067bd4F2C47D9BfD6", "WBTC", "wrapped-bitcoin"),
+    (137, "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270", "WPOL", "polygon-ecosystem-token"),
+    (42161, "0xaf88d065e77c8cC2239327C5EDb3A432268e5831", "USDC", "usd-coin"),
+    (42161, "0xFF970A61A04b1cA14834A43f5dE4533eBDDB5CC8", "USDC.e", "usd-coin"),
+    (42161, "0xFd086bC7CD5C481DCC9C85ebE478A1C0b69FCbb9", "USDT", "tether"),
+    (42161, "0xDA10009cBd5D07dd0CeCc66161FC93D7c9000da1", "DAI", "dai"),
+    (42161, "0x82aF49447D8a07e3bd95BD0d56f35241523fBab1", "WETH", "ethereum"),
+    (42161, "0x2f2a2543B76A4166549F7aaB2e75Bef0aefC5B0f", "WBTC", "wrapped-bitcoin"),
+    (42161, "0x912CE59144191C1204E64559FE8253a0e49E6548", "ARB", "arbitrum"),
+    (10, "0x0b2C639c533813f4Aa9D7837cAf62653d097Ff85", "USDC", "usd-coin"),
+    (10, "0x94b008aA00579c1307B0EF2c499aD98a8ce58e58", "USDT", "tether"),
+    (10, "0xDA10009cBd5D07dd0CeCc66161FC93D7c9000da1", "DAI", "dai"),
+    (10, "0x4200000000000000000000000000000000000006", "WETH", "ethereum"),
+    (10, "0x68f180fcCe6836688e9084f035309E29Bf0A2095", "WBTC", "wrapped-bitcoin"),
+    (10, "0x4200000000000000000000000000000000000042", "OP", "optimism"),
+    (8453, "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913", "USDC", "usd-coin"),
+    (8453, "0x50c5725949A6F0c72E6C4a641F24049A917DB0Cb", "DAI", "dai"),
+    (8453, "0x4200000000000000000000000000000000000006", "WETH", "ethereum"),
+    (8453, "0x2Ae3F1Ec7F1F5012CFEab0185bfc7aa3cf0DEc22", "cbETH", "coinbase-wrapped-staked-eth"),
+    (56, "0x55d398326f99059fF775485246999027B3197955", "USDT", "tether"),
+    (56, "0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d", "USDC", "usd-coin"),
+    (56, "0x2170Ed0880ac9A755fd29B2688956BD959F933F8", "ETH", "ethereum"),
+    (56, "0x7130d2A12B9BCbFAe4f2634d864A1Ee1Ce3Ead9c", "BTCB", "bitcoin"),
+    (56, "0xbb4CdB9CBd36B01bD8cBAE6dF8f7FBB7F8F3C9c3", "WBNB", "binancecoin"),
+    (56, "0x0E09FaBB73Bd3Ade0a17ECC321fD13a19e81cE82", "CAKE", "pancakeswap-token"),
+];
+
+// One EVM network served through EthereumClient
+#[derive(Clone, Debug, Deserialize)]
+pub struct EvmChainConfig {
+    pub chain_id: u64,
+    pub name: String, // Used as the chain key in responses, caches and stored history
+    pub rpc_url: String,
+    pub native_symbol: String,
+    #[serde(default)]
+    pub native_price_id: Option<String>,
+    #[serde(default)]
+    pub tokens: Vec<EvmTokenConfig>,
+    #[serde(default)]
+    pub discovery_start_block: u64,
+    #[serde(default)]
+    pub discovery_lookback_blocks: Option<u64>, // Limits a wallet's first discovery scan to recent blocks
+    #[serde(default)]
+    pub native_history_blocks: Option<u64>, // Indexes native transfers this close to the head; None skips them
+    #[serde(default = "default_multicall_address")]
+    pub multicall_address: String,
+    #[serde(default)]
+    pub opensea_chain: Option<String>, // OpenSea chain slug used for NFT floor prices
+    #[serde(default)]
+    pub dex: Option<DexConfig>, // Router used for on-chain DEX prices
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DexConfig {
+    pub router: String,
+    pub wrapped_native: String,
+    pub usd_token: String,
+    pub usd_decimals: u8,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EvmTokenConfig {
+    pub address: String,
+    pub symbol: String,
+    #[serde(default)]
+    pub price_id: Option<String>,
+}
+
+impl EvmChainConfig {
+    // CoinGecko id for a token, matched case-insensitively against the configured token list
+    pub fn price_id(&self, token_address: &str) -> Option<&str> {
+        self.tokens
+            .iter()
+            .find(|t| t.address.eq_ignore_ascii_case(token_address))
+            .and_then(|t| t.price_id.as_deref())
+    }
+
+    // Block a wallet's first token or NFT discovery scan starts from
+    pub fn first_discovery_block(&self, latest: u64) -> u64 {
+        match self.discovery_lookback_blocks {
+            Some(lookback) => latest.saturating_sub(lookback).max(self.discovery_start_block),
+            None => self.discovery_start_block,
+        }
+    }
+
+    // Lowest block whose native transfers are indexed. Native transfers emit no logs, so finding them
+    // costs one full block fetch per block and is limited to recent blocks on chains that opt in.
+    pub fn native_history_floor(&self, latest: u64) -> Option<u64> {
+        self.native_history_blocks
+            .map(|blocks| latest.saturating_sub(blocks).max(self.discovery_start_block))
+    }
+
+    // Address the native currency is priced by, where the chain has a known wrapped token
+    pub fn wrapped_native(&self) -> Option<&str> {
+        self.dex.as_ref().map(|d| d.wrapped_native.as_str())
+    }
+}
+
 #[derive(Clone)]
 pub struct Config {
     pub port: u16,
     pub solana_rpc_url: String,
-    pub ethereum_rpc_url: String,
+    pub solana_rpc_timeout_seconds: u64,
+    pub solana_commitment: String,
+    pub solana_das_url: Option<String>, // DAS-compatible API for compressed NFTs; skipped when unset
+    pub ethereum_history_lookback_blocks: u64,
+    pub ethereum_log_chunk_size: u64,
+    pub ethereum_discovery_chunk_size: u64,
+    pub evm_chains: Vec<EvmChainConfig>,
+    pub bitcoin_esplora_url: String,
+    pub bitcoin_network: String,
+    pub bitcoin_gap_limit: u32,
     pub database_url: String,
     pub cache_ttl_seconds: u64,
+    pub token_lookup_concurrency: usize,
+    pub price_providers: HashMap<String, Vec<String>>, // Priority order by chain key
+    pub coingecko_api_url: String,
+    pub coingecko_api_key: Option<String>, // Demo API key; requests are anonymous when unset
+    pub jupiter_price_api_url: String,
+    pub jupiter_api_key: Option<String>,
+    pub defillama_api_url: String,
 }
 
 impl Config {
     pub fn from_env() -> Result<Self, anyhow::Error> {
+        let ethereum_rpc_url = env::var("ETHEREUM_RPC_URL")
+            .unwrap_or_else(|_| "https://eth.llamarpc.com".to_string());
+        let ethereum_discovery_start_block = env::var("ETHEREUM_DISCOVERY_START_BLOCK")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+        let multicall_address = env::var("MULTICALL_ADDRESS")
+            .unwrap_or_else(|_| DEFAULT_MULTICALL_ADDRESS.to_string());
+        let evm_chains = load_evm_chains(&ethereum_rpc_url, ethereum_discovery_start_block, &multicall_address)?;
+
         Ok(Config {
             port: env::var("PORT")
                 .unwrap_or_else(|_| "8000".to_string())
@@ -18,15 +208,211 @@ impl Config {
                 .unwrap_or(8000),
             solana_rpc_url: env::var("SOLANA_RPC_URL")
                 .unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string()),
-            ethereum_rpc_url: env::var("ETHEREUM_RPC_URL")
-                .unwrap_or_else(|_| "https://eth.llamarpc.com".to_string()),
+            solana_rpc_timeout_seconds: env::var("SOLANA_RPC_TIMEOUT_SECONDS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            solana_commitment: env::var("SOLANA_COMMITMENT")
+                .unwrap_or_else(|_| "confirmed".to_string()),
+            solana_das_url: env::var("SOLANA_DAS_URL").ok(),
+            ethereum_history_lookback_blocks: env::var("ETHEREUM_HISTORY_LOOKBACK_BLOCKS")
+                .unwrap_or_else(|_| "10000".to_string())
+                .parse()
+                .unwrap_or(10000),
+            ethereum_log_chunk_size: env::var("ETHEREUM_LOG_CHUNK_SIZE")
+                .unwrap_or_else(|_| "2000".to_string())
+                .parse()
+                .unwrap_or(2000),
+            ethereum_discovery_chunk_size: env::var("ETHEREUM_DISCOVERY_CHUNK_SIZE")
+                .unwrap_or_else(|_| "100000".to_string())
+                .parse()
+                .unwrap_or(100000),
+            evm_chains,
+            bitcoin_esplora_url: env::var("BITCOIN_ESPLORA_URL")
+                .unwrap_or_else(|_| "https://blockstream.info/api".to_string()),
+            bitcoin_network: env::var("BITCOIN_NETWORK")
+                .unwrap_or_else(|_| "bitcoin".to_string()),
+            bitcoin_gap_limit: env::var("BITCOIN_GAP_LIMIT")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .unwrap_or(20),
             database_url: env::var("DATABASE_URL")
                 .map_err(|_| anyhow::anyhow!("DATABASE_URL environment variable is required"))?,
             cache_ttl_seconds: env::var("CACHE_TTL_SECONDS")
                 .unwrap_or_else(|_| "30".to_string())
                 .parse()
                 .unwrap_or(30),
+            token_lookup_concurrency: env::var("TOKEN_LOOKUP_CONCURRENCY")
+                .unwrap_or_else(|_| "8".to_string())
+                .parse()
+                .unwrap_or(8),
+            price_providers: parse_price_providers(&env::var("PRICE_PROVIDERS").unwrap_or_default()),
+            coingecko_api_url: env::var("COINGECKO_API_URL")
+                .unwrap_or_else(|_| "https://api.coingecko.com/api/v3".to_string()),
+            coingecko_api_key: env::var("COINGECKO_API_KEY").ok(),
+            jupiter_price_api_url: env::var("JUPITER_PRICE_API_URL")
+                .unwrap_or_else(|_| "https://api.jup.ag/price/v2".to_string()),
+            jupiter_api_key: env::var("JUPITER_API_KEY").ok(),
+            defillama_api_url: env::var("DEFILLAMA_API_URL")
+                .unwrap_or_else(|_| "https://coins.llama.fi".to_string()),
+        })
+    }
+
+    // Providers to try for a chain, in priority order
+    pub fn price_providers(&self, chain: &str) -> Vec<String> {
+        if let Some(providers) = self.price_providers.get(chain) {
+            return providers.clone();
+        }
+        let defaults = match chain {
+            "solana" => DEFAULT_SOLANA_PRICE_PROVIDERS,
+            "bitcoin" => DEFAULT_BITCOIN_PRICE_PROVIDERS,
+            _ => DEFAULT_EVM_PRICE_PROVIDERS,
+        };
+        defaults.iter().map(|p| p.to_string()).collect()
+    }
+}
+
+// Settings for service tests; RPC URLs and chains are filled in by each test
+#[cfg(test)]
+impl Config {
+    pub fn for_tests() -> Self {
+        Config {
+            port: 0,
+            solana_rpc_url: String::new(),
+            solana_rpc_timeout_seconds: 5,
+            solana_commitment: "confirmed".to_string(),
+            solana_das_url: None,
+            ethereum_history_lookback_blocks: 0,
+            ethereum_log_chunk_size: 0,
+            ethereum_discovery_chunk_size: 0,
+            evm_chains: Vec::new(),
+            bitcoin_esplora_url: String::new(),
+            bitcoin_network: "bitcoin".to_string(),
+            bitcoin_gap_limit: 0,
+            database_url: String::new(),
+            cache_ttl_seconds: 0,
+            token_lookup_concurrency: 8,
+            price_providers: HashMap::new(),
+            coingecko_api_url: String::new(),
+            coingecko_api_key: None,
+            jupiter_price_api_url: String::new(),
+            jupiter_api_key: None,
+            defillama_api_url: String::new(),
+        }
+    }
+}
+
+// PRICE_PROVIDERS overrides the order per chain, e.g. "solana=jupiter,defillama;polygon=defillama,dex"
+fn parse_price_providers(value: &str) -> HashMap<String, Vec<String>> {
+    value
+        .split(';')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(chain, providers)| {
+            let providers = providers
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(|p| p.to_string())
+                .collect();
+            (chain.trim().to_string(), providers)
         })
+        .collect()
+}
+
+fn default_multicall_address() -> String {
+    DEFAULT_MULTICALL_ADDRESS.to_string()
+}
+
+// EVM_CHAINS lists the enabled chain ids (default: Ethereum only). Built-in chains take their RPC URL
+// from EVM_RPC_URL_<chain id> and index native transfers when EVM_NATIVE_HISTORY_BLOCKS_<chain id> is set
+// (ETHEREUM_NATIVE_HISTORY_BLOCKS for mainnet); EVM_CHAINS_CONFIG may point to a JSON array of chain
+// entries that replace built-in ones or add new chains.
+fn load_evm_chains(ethereum_rpc_url: &str, ethereum_discovery_start_block: u64, multicall_address: &str) -> Result<Vec<EvmChainConfig>, anyhow::Error> {
+    let overrides: Vec<EvmChainConfig> = match env::var("EVM_CHAINS_CONFIG") {
+        Ok(path) => {
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow::anyhow!("Failed to read EVM_CHAINS_CONFIG {}: {}", path, e))?;
+            serde_json::from_str(&contents)
+                .map_err(|e| anyhow::anyhow!("Invalid EVM_CHAINS_CONFIG {}: {}", path, e))?
+        }
+        Err(_) => Vec::new(),
+    };
+
+    let enabled = env::var("EVM_CHAINS").unwrap_or_else(|_| "1".to_string());
+    let mut chains: Vec<EvmChainConfig> = Vec::new();
+    for id in enabled.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+        let chain_id: u64 = id
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid chain id in EVM_CHAINS: {}", id))?;
+        if chains.iter().any(|c| c.chain_id == chain_id) {
+            continue;
+        }
+
+        let chain = match overrides.iter().find(|c| c.chain_id == chain_id) {
+            Some(chain) => chain.clone(),
+            None => known_evm_chain(chain_id, ethereum_rpc_url, ethereum_discovery_start_block, multicall_address)
+                .ok_or_else(|| anyhow::anyhow!("EVM chain {} is not built in; describe it in EVM_CHAINS_CONFIG", chain_id))?,
+        };
+        if chains.iter().any(|c| c.name == chain.name) {
+            return Err(anyhow::anyhow!("Duplicate EVM chain name: {}", chain.name));
+        }
+        chains.push(chain);
     }
+
+    Ok(chains)
 }
 
+fn known_evm_chain(chain_id: u64, ethereum_rpc_url: &str, ethereum_discovery_start_block: u64, multicall_address: &str) -> Option<EvmChainConfig> {
+    let (_, name, public_rpc_url, native_symbol, native_price_id) = KNOWN_EVM_CHAINS.iter().find(|(id, ..)| *id == chain_id)?;
+
+    // Mainnet keeps its original settings so existing deployments behave the same
+    let (rpc_url, discovery_start_block) = if chain_id == 1 {
+        (ethereum_rpc_url.to_string(), ethereum_discovery_start_block)
+    } else {
+        (env::var(format!("EVM_RPC_URL_{}", chain_id)).unwrap_or_else(|_| public_rpc_url.to_string()), 0)
+    };
+    let native_history_var = if chain_id == 1 {
+        "ETHEREUM_NATIVE_HISTORY_BLOCKS".to_string()
+    } else {
+        format!("EVM_NATIVE_HISTORY_BLOCKS_{}", chain_id)
+    };
+
+    let tokens = KNOWN_EVM_TOKENS
+        .iter()
+        .filter(|(id, ..)| *id == chain_id)
+        .map(|(_, address, symbol, price_id)| EvmTokenConfig {
+            address: address.to_string(),
+            symbol: symbol.to_string(),
+            price_id: Some(price_id.to_string()),
+        })
+        .collect();
+
+    Some(EvmChainConfig {
+        chain_id,
+        name: name.to_string(),
+        rpc_url,
+        native_symbol: native_symbol.to_string(),
+        native_price_id: Some(native_price_id.to_string()),
+        tokens,
+        discovery_start_block,
+        discovery_lookback_blocks: KNOWN_DISCOVERY_LOOKBACK_BLOCKS
+            .iter()
+            .find(|(id, _)| *id == chain_id)
+            .map(|(_, blocks)| *blocks),
+        native_history_blocks: env::var(native_history_var).ok().and_then(|blocks| blocks.parse().ok()),
+        multicall_address: multicall_address.to_string(),
+        opensea_chain: KNOWN_OPENSEA_CHAINS
+            .iter()
+            .find(|(id, _)| *id == chain_id)
+            .map(|(_, slug)| slug.to_string()),
+        dex: KNOWN_DEX_ROUTERS
+            .iter()
+            .find(|(id, ..)| *id == chain_id)
+            .map(|(_, router, wrapped_native, usd_token, usd_decimals)| DexConfig {
+                router: router.to_string(),
+                wrapped_native: wrapped_native.to_string(),
+                usd_token: usd_token.to_string(),
+                usd_decimals: *usd_decimals,
+            }),
+    })
+}
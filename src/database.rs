@@ -15,3 +15,12 @@ pub async fn create_pool(database_url: &str) -> Result<PgPool> {
     Ok(pool)
 }
 
+
+// Pool for service tests: connecting always fails quickly, so every cache lookup misses
+#[cfg(test)]
+pub fn unreachable_pool() -> PgPool {
+    PgPoolOptions::new()
+        .acquire_timeout(std::time::Duration::from_millis(50))
+        .connect_lazy("postgres://blockfolio@127.0.0.1:1/blockfolio")
+        .unwrap()
+}
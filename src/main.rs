@@ -10,16 +10,24 @@ use axum::{
     routing::{get, post, delete},
     Router,
 };
+use std::sync::Arc;
 use tower_http::cors::CorsLayer;
-use tracing_subscriber;
 
 use config::Config;
 use database::create_pool;
 use services::cache::CacheService;
 use services::price_service::PriceService;
+use services::price_provider::CoinGeckoApi;
+use services::price_history::PriceHistory;
 use services::metadata_service::MetadataService;
+use services::token_registry::TokenRegistry;
+use services::name_service::NameService;
+use services::fx_service::FxService;
+use services::history_store::HistoryStore;
 use services::solana_client::SolanaClient;
-use services::ethereum_client::EthereumClient;
+use services::ethereum_client::{EthereumClient, EvmChainClient};
+use services::bitcoin_client::BitcoinClient;
+use services::chain_client::ChainRegistry;
 use state::AppState;
 
 #[tokio::main]
@@ -39,8 +47,29 @@ async fn main() -> anyhow::Result<()> {
 
     // Initialize services
     let cache = CacheService::new(pool.clone());
-    let price_service = PriceService::new(cache.clone());
-    let metadata_service = MetadataService::new(cache.clone());
+
+    // Token lists are served from memory; a cold start without a snapshot waits for the first download,
+    // otherwise the snapshot is served while it is revalidated in the background
+    let token_registry = TokenRegistry::new(cache.clone());
+    let downloaded = match token_registry.load_snapshot().await {
+        Ok(true) => false,
+        Ok(false) => {
+            token_registry.refresh().await;
+            true
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load token list snapshots: {}", e);
+            token_registry.refresh().await;
+            true
+        }
+    };
+    token_registry.spawn_refresh(downloaded);
+
+    let price_service = PriceService::new(cache.clone(), token_registry.clone(), config.clone());
+    let metadata_service = MetadataService::new(cache.clone(), token_registry, CoinGeckoApi::new(&config));
+    let history_store = HistoryStore::new(pool.clone());
+    let name_service = NameService::new(cache.clone());
+    let fx_service = FxService::new(cache.clone());
     let solana_client = SolanaClient::new(
         config.solana_rpc_url.clone(),
         price_service.clone(),
@@ -48,29 +77,48 @@ async fn main() -> anyhow::Result<()> {
         config.clone(),
     );
     let ethereum_client = EthereumClient::new(
-        config.ethereum_rpc_url.clone(),
         price_service.clone(),
         metadata_service.clone(),
+        history_store,
         config.clone(),
     );
+    let bitcoin_client = BitcoinClient::new(price_service.clone(), config.clone());
+
+    // Prices of every token held by a tracked wallet are sampled in the background for charts
+    let price_history = PriceHistory::new(pool.clone(), price_service.clone());
+    price_history.spawn_collector();
+
+    // Every chain is served through the registry; each configured EVM chain is registered by name
+    let mut chains = ChainRegistry::new();
+    chains.register(Arc::new(solana_client));
+    for chain in ethereum_client.chains() {
+        chains.register(Arc::new(EvmChainClient::new(ethereum_client.clone(), chain)));
+    }
+    chains.register(Arc::new(bitcoin_client));
 
     let app_state = AppState {
         pool: pool.clone(),
         cache,
         price_service,
-        solana_client,
-        ethereum_client,
+        price_history,
+        chains,
+        names: name_service,
+        fx: fx_service,
     };
 
     // Build application with routes
     let app = Router::new()
         .route("/health", get(routes::health::health_check))
-        .route("/solana/balances/:address", get(routes::solana::get_balances))
-        .route("/ethereum/balances/:address", get(routes::ethereum::get_balances))
-        .route("/solana/transactions/:address", get(routes::transactions::get_solana_transactions))
-        .route("/ethereum/transactions/:address", get(routes::transactions::get_ethereum_transactions))
+        .route("/:chain/balances/:address", get(routes::balances::get_balances))
+        .route("/:chain/transactions/:address", get(routes::transactions::get_transactions))
+        .route("/:chain/nfts/:address", get(routes::nfts::get_nfts))
+        .route("/evm/balances/:address", get(routes::evm::get_all_balances))
+        .route("/evm/:chain_id/balances/:address", get(routes::evm::get_balances))
+        .route("/evm/:chain_id/transactions/:address", get(routes::transactions::get_evm_transactions))
+        .route("/evm/:chain_id/nfts/:address", get(routes::nfts::get_evm_nfts))
+        .route("/prices/:chain/:token/history", get(routes::prices::get_price_history))
         .route("/users", post(routes::users::create_user))
-        .route("/users/:user_id", get(routes::users::get_user))
+        .route("/users/:user_id", get(routes::users::get_user).patch(routes::users::update_user))
         .route("/users/:user_id/wallets", get(routes::users::get_user_wallets))
         .route("/users/:user_id/wallets", post(routes::users::add_wallet))
         .route("/users/:user_id/wallets/:wallet_id", delete(routes::users::remove_wallet))
@@ -0,0 +1,166 @@
+use axum::{extract::{Path, Query, State}, Json, response::IntoResponse};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::services::chain_client::ChainClient;
+use crate::state::AppState;
+use crate::types::nft::{NftCollection, NftPortfolio};
+use crate::types::portfolio::PortfolioResponse;
+use crate::utils::errors::AppError;
+use crate::utils::amounts;
+use crate::utils::helpers::normalize_currency;
+
+// Values are converted to `currency` when given, otherwise to the default currency of `user_id`
+#[derive(Deserialize)]
+pub struct CurrencyQuery {
+    pub currency: Option<String>,
+    pub user_id: Option<i32>,
+}
+
+pub async fn get_balances(
+    Path((chain, address)): Path<(String, String)>,
+    Query(params): Query<CurrencyQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let client = state
+        .chains
+        .get(&chain)
+        .ok_or_else(|| AppError::InvalidRequest(format!("Unsupported chain: {}", chain)))?;
+    let currency = resolve_currency(&state, &params).await?;
+    let mut portfolio = fetch_portfolio(&state, client.as_ref(), &address).await?;
+    if let Some((currency, rate)) = &currency {
+        convert_portfolio(&mut portfolio, currency, *rate);
+    }
+    Ok(Json(portfolio).into_response())
+}
+
+// Resolves the address or name, then serves the portfolio with the wallet's primary name
+pub async fn fetch_portfolio(state: &AppState, client: &dyn ChainClient, input: &str) -> Result<PortfolioResponse, AppError> {
+    let (address, name) = resolve_address(state, client, input).await?;
+    let name = match name {
+        Some(name) => Some(name),
+        None => primary_name(state, client, &address).await,
+    };
+
+    let mut portfolio = cached_portfolio(state, client, &address).await?;
+    portfolio.name = name;
+    Ok(portfolio)
+}
+
+// Accepts an address or a name (e.g. "vitalik.eth", "toly.sol"), returning the normalized address and the name used
+pub async fn resolve_address(state: &AppState, client: &dyn ChainClient, input: &str) -> Result<(String, Option<String>), AppError> {
+    if client.is_name(input) {
+        let name = input.to_lowercase();
+        return match state.names.resolve(client, &name).await? {
+            Some(address) => Ok((client.normalize_address(&address), Some(name))),
+            None => Err(AppError::InvalidAddress(format!("Could not resolve name: {}", input))),
+        };
+    }
+
+    if !client.is_valid_address(input) {
+        return Err(AppError::InvalidAddress(format!("Invalid {} address: {}", client.chain(), input)));
+    }
+    Ok((client.normalize_address(input), None))
+}
+
+// Reverse lookups are best-effort; a failing name service doesn't fail the request
+pub async fn primary_name(state: &AppState, client: &dyn ChainClient, address: &str) -> Option<String> {
+    state.names.lookup(client, address).await.unwrap_or_else(|e| {
+        tracing::warn!("Failed to look up {} name for {}: {}", client.chain(), address, e);
+        None
+    })
+}
+
+// Serves the portfolio from cache or RPC; `address` must already be normalized
+pub async fn cached_portfolio(state: &AppState, client: &dyn ChainClient, address: &str) -> Result<PortfolioResponse, AppError> {
+    // Check cache first
+    if let Some(cached_data) = state.cache.get_balance(address, client.chain()).await? {
+        let portfolio: PortfolioResponse = serde_json::from_value(cached_data)?;
+        return Ok(portfolio);
+    }
+
+    // Cache miss - fetch from RPC
+    let portfolio: PortfolioResponse = client.fetch_portfolio(address).await?;
+    
+    // Store in cache
+    let ttl_seconds = std::env::var("CACHE_TTL_SECONDS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse()
+        .unwrap_or(30);
+    state.cache.set_balance(address, client.chain(), &serde_json::to_value(&portfolio)?, ttl_seconds).await?;
+
+    Ok(portfolio)
+}
+
+// The currency to convert to and its rate in units per USD; None serves USD figures only.
+// A currency named in the request fails the request when it can't be converted, while the
+// user's default degrades to USD so a rates outage doesn't take portfolios down with it.
+pub async fn resolve_currency(state: &AppState, params: &CurrencyQuery) -> Result<Option<(String, Decimal)>, AppError> {
+    let (requested, explicit) = match (&params.currency, params.user_id) {
+        (Some(currency), _) => (currency.clone(), true),
+        (None, Some(user_id)) => {
+            let default_currency: Option<Option<String>> = sqlx::query_scalar(
+                r#"
+                SELECT default_currency FROM users
+                WHERE id = $1
+                "#
+            )
+            .bind(user_id)
+            .fetch_optional(&state.pool)
+            .await?;
+            match default_currency.ok_or_else(|| AppError::NotFound(format!("User not found: {}", user_id)))? {
+                Some(currency) => (currency, false),
+                None => return Ok(None),
+            }
+        }
+        (None, None) => return Ok(None),
+    };
+
+    let currency = normalize_currency(&requested)
+        .ok_or_else(|| AppError::InvalidRequest(format!("Invalid currency: {}", requested)))?;
+    match state.fx.get_rate(&currency).await {
+        Ok(Some(rate)) => Ok(Some((currency, rate))),
+        Ok(None) if explicit => Err(AppError::InvalidRequest(format!("Unsupported currency: {}", currency))),
+        Err(e) if explicit => Err(AppError::Internal(e.context(format!("Failed to get {} exchange rate", currency)))),
+        Ok(None) => {
+            tracing::warn!("Default currency {} is no longer quoted", currency);
+            Ok(None)
+        }
+        Err(e) => {
+            tracing::warn!("Failed to get {} exchange rate: {}", currency, e);
+            Ok(None)
+        }
+    }
+}
+
+// Fills in the converted figures; cached portfolios stay USD-only so they remain comparable over time
+pub fn convert_portfolio(portfolio: &mut PortfolioResponse, currency: &str, rate: Decimal) {
+    portfolio.currency = Some(currency.to_string());
+    portfolio.fx_rate = Some(rate);
+    portfolio.native_price = Some(amounts::convert(portfolio.native_price_usd, rate));
+    portfolio.native_value = Some(amounts::convert(portfolio.native_value_usd, rate));
+    portfolio.nft_value = portfolio.nft_value_usd.map(|value| amounts::convert(value, rate));
+    portfolio.total_value = portfolio.total_value_usd.map(|value| amounts::convert(value, rate));
+    for token in &mut portfolio.tokens {
+        token.price = token.price_usd.map(|price| amounts::convert(price, rate));
+        token.value = Some(amounts::convert(token.value_usd, rate));
+    }
+    for position in &mut portfolio.staking {
+        position.value = Some(amounts::convert(position.value_usd, rate));
+    }
+    convert_collections(&mut portfolio.nfts, rate);
+}
+
+pub fn convert_nfts(nfts: &mut NftPortfolio, currency: &str, rate: Decimal) {
+    nfts.currency = Some(currency.to_string());
+    nfts.fx_rate = Some(rate);
+    nfts.total_value = nfts.total_value_usd.map(|value| amounts::convert(value, rate));
+    convert_collections(&mut nfts.collections, rate);
+}
+
+fn convert_collections(collections: &mut [NftCollection], rate: Decimal) {
+    for collection in collections {
+        collection.floor_price = collection.floor_price_usd.map(|price| amounts::convert(price, rate));
+        collection.value = collection.value_usd.map(|value| amounts::convert(value, rate));
+    }
+}
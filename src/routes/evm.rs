@@ -0,0 +1,78 @@
+use axum::{extract::{Path, Query, State}, Json, response::IntoResponse};
+use futures::future::join_all;
+use std::sync::Arc;
+
+use crate::routes::balances::{self, CurrencyQuery};
+use crate::services::chain_client::ChainClient;
+use crate::state::AppState;
+use crate::types::portfolio::MultiChainPortfolio;
+use crate::utils::errors::AppError;
+use crate::utils::amounts;
+use crate::utils::helpers::format_address;
+
+// Chain value for wallets that resolve on every configured EVM chain
+pub const EVM_WALLET_CHAIN: &str = "evm";
+
+pub async fn get_balances(
+    Path((chain_id, address)): Path<(u64, String)>,
+    Query(params): Query<CurrencyQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let client = resolve_chain(&state, chain_id)?;
+    let currency = balances::resolve_currency(&state, &params).await?;
+    let mut portfolio = balances::fetch_portfolio(&state, client.as_ref(), &address).await?;
+    if let Some((currency, rate)) = &currency {
+        balances::convert_portfolio(&mut portfolio, currency, *rate);
+    }
+    Ok(Json(portfolio).into_response())
+}
+
+// Resolves one address on every configured EVM chain; chains whose RPC fails are left out
+pub async fn get_all_balances(
+    Path(address): Path<String>,
+    Query(params): Query<CurrencyQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let clients: Vec<&Arc<dyn ChainClient>> = state.chains.evm_chains().collect();
+    let Some(first) = clients.first() else {
+        return Err(AppError::InvalidRequest("No EVM chains are configured".to_string()));
+    };
+
+    let currency = balances::resolve_currency(&state, &params).await?;
+
+    // Addresses and ENS names are the same on every EVM chain, so resolve them once
+    let (address, name) = balances::resolve_address(&state, first.as_ref(), &address).await?;
+    let name = match name {
+        Some(name) => Some(name),
+        None => balances::primary_name(&state, first.as_ref(), &address).await,
+    };
+
+    let results = join_all(clients.iter().map(|client| balances::cached_portfolio(&state, client.as_ref(), &address))).await;
+
+    let mut chains = Vec::new();
+    for (client, result) in clients.iter().zip(results) {
+        match result {
+            Ok(mut portfolio) => {
+                portfolio.name = name.clone();
+                if let Some((currency, rate)) = &currency {
+                    balances::convert_portfolio(&mut portfolio, currency, *rate);
+                }
+                chains.push(portfolio);
+            }
+            Err(e) => tracing::warn!("Failed to fetch {} portfolio for {}: {}", client.chain(), address, e),
+        }
+    }
+    let total_value_usd = amounts::sum(chains.iter().filter_map(|p| p.total_value_usd));
+
+    let address = format_address(&address, EVM_WALLET_CHAIN);
+    let (currency, fx_rate) = currency.unzip();
+    let total_value = fx_rate.map(|rate| amounts::convert(total_value_usd, rate));
+    Ok(Json(MultiChainPortfolio { address, name, chains, total_value_usd, currency, fx_rate, total_value }).into_response())
+}
+
+pub fn resolve_chain(state: &AppState, chain_id: u64) -> Result<&Arc<dyn ChainClient>, AppError> {
+    state
+        .chains
+        .get_evm(chain_id)
+        .ok_or_else(|| AppError::InvalidRequest(format!("Unsupported EVM chain: {}", chain_id)))
+}
@@ -1,6 +1,8 @@
 pub mod health;
-pub mod solana;
-pub mod ethereum;
+pub mod balances;
+pub mod evm;
 pub mod users;
 pub mod transactions;
+pub mod nfts;
+pub mod prices;
 
@@ -0,0 +1,44 @@
+use axum::{extract::{Path, Query, State}, Json, response::IntoResponse};
+use crate::routes::balances::{self, CurrencyQuery};
+use crate::routes::evm;
+use crate::services::chain_client::ChainClient;
+use crate::state::AppState;
+use crate::types::nft::NftPortfolio;
+use crate::utils::errors::AppError;
+
+pub async fn get_nfts(
+    Path((chain, address)): Path<(String, String)>,
+    Query(params): Query<CurrencyQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let client = state
+        .chains
+        .get(&chain)
+        .ok_or_else(|| AppError::InvalidRequest(format!("Unsupported chain: {}", chain)))?;
+    let nfts = fetch_nfts(&state, client.as_ref(), &address, &params).await?;
+    Ok(Json(nfts).into_response())
+}
+
+pub async fn get_evm_nfts(
+    Path((chain_id, address)): Path<(u64, String)>,
+    Query(params): Query<CurrencyQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let client = evm::resolve_chain(&state, chain_id)?;
+    let nfts = fetch_nfts(&state, client.as_ref(), &address, &params).await?;
+    Ok(Json(nfts).into_response())
+}
+
+async fn fetch_nfts(state: &AppState, client: &dyn ChainClient, input: &str, params: &CurrencyQuery) -> Result<NftPortfolio, AppError> {
+    if !client.supports_nfts() {
+        return Err(AppError::InvalidRequest(format!("NFTs are not supported on {}", client.chain())));
+    }
+
+    let currency = balances::resolve_currency(state, params).await?;
+    let (address, _) = balances::resolve_address(state, client, input).await?;
+    let mut nfts = client.fetch_nfts(&address).await?;
+    if let Some((currency, rate)) = &currency {
+        balances::convert_nfts(&mut nfts, currency, *rate);
+    }
+    Ok(nfts)
+}
@@ -0,0 +1,45 @@
+use axum::{extract::{Path, Query, State}, Json, response::IntoResponse};
+use serde::Deserialize;
+use crate::state::AppState;
+use crate::types::price::{PriceHistoryResponse, PriceRange};
+use crate::utils::errors::AppError;
+use crate::utils::helpers;
+
+#[derive(Deserialize)]
+pub struct PriceHistoryQuery {
+    pub range: Option<String>,
+}
+
+// `token` is a mint or contract address, or the chain's native symbol (SOL, ETH, BTC, ...)
+pub async fn get_price_history(
+    Path((chain, token)): Path<(String, String)>,
+    Query(params): Query<PriceHistoryQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let client = state
+        .chains
+        .get(&chain)
+        .ok_or_else(|| AppError::InvalidRequest(format!("Unsupported chain: {}", chain)))?;
+    let range: PriceRange = params
+        .range
+        .as_deref()
+        .unwrap_or("7d")
+        .parse()
+        .map_err(AppError::InvalidRequest)?;
+
+    let token_id = if client.is_valid_address(&token) { client.normalize_address(&token) } else { token.clone() };
+    let token_ref = state
+        .price_service
+        .token_ref(client.chain(), &token_id, None)
+        .ok_or_else(|| AppError::InvalidRequest(format!("Unknown {} token: {}", chain, token)))?;
+    let candles = state.price_history.get_candles(&token_ref, range).await?;
+
+    Ok(Json(PriceHistoryResponse {
+        chain: client.chain().to_string(),
+        token: helpers::format_address(&token_ref.id, client.chain()),
+        range: range.to_string(),
+        resolution: range.resolution().as_str().to_string(),
+        candles,
+    })
+    .into_response())
+}
@@ -1,36 +1,60 @@
 use axum::{extract::{Path, Query, State}, Json, response::IntoResponse};
 use serde::Deserialize;
+use crate::routes::{balances, evm};
+use crate::services::chain_client::ChainClient;
 use crate::state::AppState;
-use crate::types::transaction::Transaction;
+use crate::types::transaction::TransactionPage;
 use crate::utils::errors::AppError;
 
+// Upper bound on a single page so clients page through history instead of pulling it all
+const MAX_PAGE_SIZE: usize = 100;
+
 #[derive(Deserialize)]
 pub struct TransactionQuery {
     pub limit: Option<usize>,
+    pub before: Option<String>,
+    pub until: Option<String>,
+}
+
+impl TransactionQuery {
+    fn page_size(&self) -> usize {
+        self.limit.unwrap_or(10).clamp(1, MAX_PAGE_SIZE)
+    }
 }
 
-pub async fn get_solana_transactions(
-    Path(address): Path<String>,
+pub async fn get_transactions(
+    Path((chain, address)): Path<(String, String)>,
     Query(params): Query<TransactionQuery>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, AppError> {
-    let limit = params.limit.unwrap_or(10);
-    let transactions = state.solana_client.fetch_transactions(&address, limit).await?;
-    Ok(Json(transactions).into_response())
+    let client = state
+        .chains
+        .get(&chain)
+        .ok_or_else(|| AppError::InvalidRequest(format!("Unsupported chain: {}", chain)))?;
+    let page = fetch_transactions(&state, client.as_ref(), &address, &params).await?;
+    Ok(Json(page).into_response())
 }
 
-pub async fn get_ethereum_transactions(
-    Path(address): Path<String>,
+pub async fn get_evm_transactions(
+    Path((chain_id, address)): Path<(u64, String)>,
     Query(params): Query<TransactionQuery>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, AppError> {
-    let limit = params.limit.unwrap_or(10);
-    let transactions = state.ethereum_client.fetch_transactions(&address, limit).await?;
-    Ok(Json(transactions).into_response())
+    let client = evm::resolve_chain(&state, chain_id)?;
+    let page = fetch_transactions(&state, client.as_ref(), &address, &params).await?;
+    Ok(Json(page).into_response())
 }
 
-
-
-
-
-
+async fn fetch_transactions(state: &AppState, client: &dyn ChainClient, input: &str, params: &TransactionQuery) -> Result<TransactionPage, AppError> {
+    for cursor in [&params.before, &params.until].into_iter().flatten() {
+        if !client.is_valid_cursor(cursor) {
+            return Err(AppError::InvalidRequest(format!("Invalid cursor: {}", cursor)));
+        }
+    }
+
+    let (address, _) = balances::resolve_address(state, client, input).await?;
+    let page = client
+        .fetch_transactions(&address, params.page_size(), params.before.as_deref(), params.until.as_deref())
+        .await?;
+    Ok(page)
+}
@@ -1,22 +1,31 @@
 use axum::{extract::{Path, State}, Json, response::IntoResponse};
 
+use crate::routes::balances;
+use crate::routes::evm::EVM_WALLET_CHAIN;
 use crate::state::AppState;
-use crate::types::user::{User, CreateUserRequest, UserWallet, AddWalletRequest};
+use crate::types::user::{User, CreateUserRequest, UpdateUserRequest, UserWallet, AddWalletRequest};
 use crate::utils::errors::AppError;
+use crate::utils::helpers::{format_address, normalize_currency};
 
 pub async fn create_user(
     State(state): State<AppState>,
     Json(payload): Json<CreateUserRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    let default_currency = match &payload.default_currency {
+        Some(currency) => Some(validate_currency(&state, currency).await?),
+        None => None,
+    };
+
     let user: User = sqlx::query_as::<_, User>(
         r#"
-        INSERT INTO users (email, username)
-        VALUES ($1, $2)
-        RETURNING id, email, username, created_at, updated_at
+        INSERT INTO users (email, username, default_currency)
+        VALUES ($1, $2, $3)
+        RETURNING id, email, username, default_currency, created_at, updated_at
         "#
     )
     .bind(&payload.email)
     .bind(&payload.username)
+    .bind(&default_currency)
     .fetch_one(&state.pool)
     .await?;
 
@@ -29,7 +38,7 @@ pub async fn get_user(
 ) -> Result<impl IntoResponse, AppError> {
     let user: Option<User> = sqlx::query_as::<_, User>(
         r#"
-        SELECT id, email, username, created_at, updated_at
+        SELECT id, email, username, default_currency, created_at, updated_at
         FROM users
         WHERE id = $1
         "#
@@ -40,7 +49,41 @@ pub async fn get_user(
 
     match user {
         Some(u) => Ok(Json(u).into_response()),
-        None => Err(AppError::InvalidAddress(format!("User not found: {}", user_id))),
+        None => Err(AppError::NotFound(format!("User not found: {}", user_id))),
+    }
+}
+
+pub async fn update_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+    Json(payload): Json<UpdateUserRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let default_currency = match &payload.default_currency {
+        Some(Some(currency)) => Some(validate_currency(&state, currency).await?),
+        _ => None,
+    };
+
+    // $4 is set when default_currency was sent at all, so null clears it
+    let user: Option<User> = sqlx::query_as::<_, User>(
+        r#"
+        UPDATE users
+        SET username = COALESCE($2, username),
+            default_currency = CASE WHEN $4 THEN $3 ELSE default_currency END,
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING id, email, username, default_currency, created_at, updated_at
+        "#
+    )
+    .bind(user_id)
+    .bind(&payload.username)
+    .bind(&default_currency)
+    .bind(payload.default_currency.is_some())
+    .fetch_optional(&state.pool)
+    .await?;
+
+    match user {
+        Some(u) => Ok(Json(u).into_response()),
+        None => Err(AppError::NotFound(format!("User not found: {}", user_id))),
     }
 }
 
@@ -60,6 +103,7 @@ pub async fn get_user_wallets(
     .fetch_all(&state.pool)
     .await?;
 
+    let wallets: Vec<UserWallet> = wallets.into_iter().map(display_wallet).collect();
     Ok(Json(wallets).into_response())
 }
 
@@ -68,21 +112,17 @@ pub async fn add_wallet(
     Path(user_id): Path<i32>,
     Json(payload): Json<AddWalletRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Validate chain
-    if payload.chain != "solana" && payload.chain != "ethereum" {
-        return Err(AppError::InvalidAddress(format!("Invalid chain: {}", payload.chain)));
+    // Validate chain: "evm" registers the address once for every configured EVM chain
+    let client = if payload.chain == EVM_WALLET_CHAIN {
+        state.chains.evm_chains().next()
+    } else {
+        state.chains.get(&payload.chain)
     }
+    .ok_or_else(|| AppError::InvalidAddress(format!("Invalid chain: {}", payload.chain)))?;
 
-    // Validate address format based on chain
-    if payload.chain == "solana" {
-        if !is_valid_solana_address(&payload.address) {
-            return Err(AppError::InvalidAddress(format!("Invalid Solana address: {}", payload.address)));
-        }
-    } else if payload.chain == "ethereum" {
-        if !is_valid_ethereum_address(&payload.address) {
-            return Err(AppError::InvalidAddress(format!("Invalid Ethereum address: {}", payload.address)));
-        }
-    }
+    // Validate the address format, or resolve an ENS/SNS name to the address it points at
+    let (address, name) = balances::resolve_address(&state, client.as_ref(), &payload.address).await?;
+    let label = payload.label.clone().or(name);
 
     // If this is set as primary, unset other primary wallets for this user/chain
     if payload.is_primary.unwrap_or(false) {
@@ -110,14 +150,18 @@ pub async fn add_wallet(
         "#
     )
     .bind(user_id)
-    .bind(&payload.address)
+    .bind(&address)
     .bind(&payload.chain)
-    .bind(&payload.label)
+    .bind(&label)
     .bind(payload.is_primary.unwrap_or(false))
     .fetch_one(&state.pool)
-    .await?;
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db) if db.is_foreign_key_violation() => AppError::NotFound(format!("User not found: {}", user_id)),
+        _ => AppError::Database(e),
+    })?;
 
-    Ok(Json(wallet).into_response())
+    Ok(Json(display_wallet(wallet)).into_response())
 }
 
 pub async fn remove_wallet(
@@ -138,16 +182,24 @@ pub async fn remove_wallet(
 
     match result {
         Some(_) => Ok(Json(serde_json::json!({ "success": true })).into_response()),
-        None => Err(AppError::InvalidAddress("Wallet not found or access denied".to_string())),
+        None => Err(AppError::NotFound(format!("Wallet not found: {}", wallet_id))),
     }
 }
 
-fn is_valid_solana_address(address: &str) -> bool {
-    use bs58;
-    bs58::decode(address).into_vec().is_ok() && address.len() >= 32 && address.len() <= 44
+// Default currencies must be ones the FX rates source quotes
+async fn validate_currency(state: &AppState, currency: &str) -> Result<String, AppError> {
+    let normalized = normalize_currency(currency)
+        .ok_or_else(|| AppError::InvalidRequest(format!("Invalid currency: {}", currency)))?;
+    match state.fx.get_rate(&normalized).await? {
+        Some(_) => Ok(normalized),
+        None => Err(AppError::InvalidRequest(format!("Unsupported currency: {}", normalized))),
+    }
 }
 
-fn is_valid_ethereum_address(address: &str) -> bool {
-    address.starts_with("0x") && address.len() == 42 && address[2..].chars().all(|c| c.is_ascii_hexdigit())
+// Wallets are stored in canonical form and returned in the chain's display form
+fn display_wallet(wallet: UserWallet) -> UserWallet {
+    UserWallet {
+        address: format_address(&wallet.address, &wallet.chain),
+        ..wallet
+    }
 }
-
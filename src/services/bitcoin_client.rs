@@ -0,0 +1,721 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use bitcoin::base58;
+use bitcoin::bip32::{ChildNumber, Xpub};
+use bitcoin::secp256k1::{Secp256k1, VerifyOnly};
+use bitcoin::{Address, Network};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use crate::types::portfolio::PortfolioResponse;
+use crate::types::transaction::{BitcoinCursor, Transaction, TransactionPage};
+use crate::services::chain_client::ChainClient;
+use crate::services::price_service::PriceService;
+use crate::utils::amounts;
+use crate::config::Config;
+
+const BTC_DECIMALS: u8 = 8;
+
+// Esplora returns confirmed history in pages of 25 transactions
+const ESPLORA_PAGE_SIZE: usize = 25;
+
+// BIP32 serialization versions: (version, is mainnet, script type)
+const EXTENDED_KEY_VERSIONS: &[([u8; 4], bool, ScriptKind)] = &[
+    ([0x04, 0x88, 0xB2, 0x1E], true, ScriptKind::Legacy),        // xpub
+    ([0x04, 0x9D, 0x7C, 0xB2], true, ScriptKind::NestedSegwit),  // ypub
+    ([0x04, 0xB2, 0x47, 0x46], true, ScriptKind::NativeSegwit),  // zpub
+    ([0x04, 0x35, 0x87, 0xCF], false, ScriptKind::Legacy),       // tpub
+    ([0x04, 0x4A, 0x52, 0x62], false, ScriptKind::NestedSegwit), // upub
+    ([0x04, 0x5F, 0x1C, 0xF6], false, ScriptKind::NativeSegwit), // vpub
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScriptKind {
+    Legacy,       // P2PKH
+    NestedSegwit, // P2SH-P2WPKH
+    NativeSegwit, // P2WPKH
+    Taproot,      // P2TR, only reachable through a tr() descriptor
+}
+
+// A single address, or an account-level extended public key whose receive and change chains are scanned
+#[derive(Debug, Clone)]
+enum BitcoinWallet {
+    Address(Address),
+    ExtendedKey { xpub: Xpub, script: ScriptKind },
+}
+
+#[derive(Debug, Deserialize)]
+struct EsploraAddress {
+    chain_stats: EsploraAddressStats,
+    mempool_stats: EsploraAddressStats,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsploraAddressStats {
+    tx_count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsploraUtxo {
+    value: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EsploraTx {
+    txid: String,
+    vin: Vec<EsploraInput>,
+    vout: Vec<EsploraOutput>,
+    #[serde(default)]
+    fee: u64,
+    status: EsploraStatus,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EsploraInput {
+    prevout: Option<EsploraOutput>, // None for coinbase inputs
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EsploraOutput {
+    scriptpubkey_address: Option<String>,
+    value: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EsploraStatus {
+    confirmed: bool,
+    block_height: Option<u64>,
+    block_time: Option<i64>,
+}
+
+impl EsploraTx {
+    fn cursor(&self) -> BitcoinCursor {
+        BitcoinCursor {
+            height: self.status.block_height.filter(|_| self.status.confirmed).unwrap_or(u64::MAX),
+            txid: self.txid.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct BitcoinClient {
+    http: reqwest::Client,
+    esplora_url: String,
+    network: Network,
+    secp: Arc<Secp256k1<VerifyOnly>>,
+    price_service: PriceService,
+    config: Config,
+}
+
+impl BitcoinClient {
+    pub fn new(price_service: PriceService, config: Config) -> Self {
+        let network = Network::from_str(&config.bitcoin_network).unwrap_or_else(|_| {
+            tracing::warn!("Unknown BITCOIN_NETWORK '{}', using bitcoin", config.bitcoin_network);
+            Network::Bitcoin
+        });
+
+        Self {
+            http: reqwest::Client::new(),
+            esplora_url: config.bitcoin_esplora_url.trim_end_matches('/').to_string(),
+            network,
+            secp: Arc::new(Secp256k1::verification_only()),
+            price_service,
+            config,
+        }
+    }
+
+    pub async fn fetch_portfolio(&self, address: &str) -> Result<PortfolioResponse> {
+        let wallet = self.parse_wallet(address)?;
+        let addresses = self.wallet_addresses(&wallet).await?;
+
+        // Balance is the sum of unspent outputs, including unconfirmed ones
+        let concurrency = self.config.token_lookup_concurrency.max(1);
+        let utxos: Vec<Vec<EsploraUtxo>> = stream::iter(addresses)
+            .map(|addr| self.get_json::<Vec<EsploraUtxo>>(format!("/address/{}/utxo", addr)))
+            .buffered(concurrency)
+            .try_collect()
+            .await?;
+        let sats: u64 = utxos.iter().flatten().map(|utxo| utxo.value).sum();
+        let raw_balance = sats.to_string();
+        let btc_balance = amounts::to_f64(&raw_balance, BTC_DECIMALS);
+
+        // Get BTC price
+        let (btc_price, _btc_price_change) = self.price_service.get_bitcoin_price_with_change().await.unwrap_or_default();
+        let btc_value = amounts::usd_value(&raw_balance, BTC_DECIMALS, btc_price);
+
+        Ok(PortfolioResponse {
+            chain: "bitcoin".to_string(),
+            chain_id: None,
+            address: address.to_string(),
+            name: None,
+            native_balance: btc_balance,
+            native_raw_balance: raw_balance,
+            native_decimals: BTC_DECIMALS,
+            native_price_usd: btc_price,
+            native_value_usd: btc_value,
+            tokens: Vec::new(),
+            staking: Vec::new(),
+            nfts: Vec::new(),
+            total_tokens_count: Some(if sats > 0 { 1 } else { 0 }),
+            nft_value_usd: None,
+            total_value_usd: Some(btc_value),
+            last_updated: Some(chrono::Utc::now().to_rfc3339()),
+            ..Default::default()
+        })
+    }
+
+    pub async fn fetch_transactions(&self, address: &str, limit: usize, before: Option<BitcoinCursor>, until: Option<BitcoinCursor>) -> Result<TransactionPage> {
+        let wallet = self.parse_wallet(address)?;
+        let addresses = self.wallet_addresses(&wallet).await?;
+        let owned: HashSet<String> = addresses.iter().map(|a| a.to_string()).collect();
+        let resumable = self.cursor_addresses(&wallet, before.as_ref(), &owned).await;
+
+        // Each address is paged separately, so merge and order them before cutting the page
+        let concurrency = self.config.token_lookup_concurrency.max(1);
+        let histories: Vec<Vec<EsploraTx>> = stream::iter(addresses)
+            .map(|addr| {
+                let resume_after = before.as_ref().filter(|_| resumable.contains(&addr.to_string())).map(|b| b.txid.as_str());
+                self.address_history(addr, limit, resume_after, before.as_ref(), until.as_ref())
+            })
+            .buffered(concurrency)
+            .try_collect()
+            .await?;
+
+        let mut txs: HashMap<String, EsploraTx> = HashMap::new();
+        for tx in histories.into_iter().flatten() {
+            txs.entry(tx.txid.clone()).or_insert(tx);
+        }
+        let mut txs: Vec<EsploraTx> = txs
+            .into_values()
+            .filter(|tx| before.as_ref().is_none_or(|b| tx.cursor() < *b))
+            .filter(|tx| until.as_ref().is_none_or(|u| tx.cursor() > *u))
+            .collect();
+        txs.sort_by_key(|tx| std::cmp::Reverse(tx.cursor()));
+
+        // A full page means there may be older transactions to fetch
+        let full = txs.len() >= limit;
+        txs.truncate(page_end(&txs, limit));
+        let next_cursor = if full {
+            txs.last().map(|tx| tx.cursor().to_string())
+        } else {
+            None
+        };
+
+        Ok(TransactionPage {
+            transactions: txs.iter().map(|tx| to_transaction(tx, &owned)).collect(),
+            next_cursor,
+        })
+    }
+
+    // Owned addresses the `before` transaction spent from or paid to, whose history can resume right
+    // after it. A single address's cursor always comes from its own history, so it needs no lookup.
+    async fn cursor_addresses(&self, wallet: &BitcoinWallet, before: Option<&BitcoinCursor>, owned: &HashSet<String>) -> HashSet<String> {
+        // Unconfirmed transactions are not part of the chain history Esplora pages through
+        let Some(before) = before.filter(|b| b.height != u64::MAX) else {
+            return HashSet::new();
+        };
+        if let BitcoinWallet::Address(_) = wallet {
+            return owned.clone();
+        }
+
+        match self.get_json::<EsploraTx>(format!("/tx/{}", before.txid)).await {
+            Ok(tx) => tx
+                .vin
+                .iter()
+                .filter_map(|i| i.prevout.as_ref())
+                .chain(&tx.vout)
+                .filter_map(|o| o.scriptpubkey_address.clone())
+                .filter(|a| owned.contains(a))
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Failed to look up cursor transaction {}: {}", before.txid, e);
+                HashSet::new()
+            }
+        }
+    }
+
+    // Newest first, paging back until `limit` transactions older than `before` are found or `until` is reached.
+    // Paging continues past the block holding the limit-th of them, so page_end can end the page on a whole block.
+    async fn address_history(
+        &self,
+        address: Address,
+        limit: usize,
+        resume_after: Option<&str>,
+        before: Option<&BitcoinCursor>,
+        until: Option<&BitcoinCursor>,
+    ) -> Result<Vec<EsploraTx>> {
+        let mut history: Vec<EsploraTx> = Vec::new();
+        let mut path = match resume_after {
+            Some(txid) => format!("/address/{}/txs/chain/{}", address, txid),
+            None => format!("/address/{}/txs", address),
+        };
+        loop {
+            let page: Vec<EsploraTx> = self.get_json(path).await?;
+            let confirmed: Vec<&EsploraTx> = page.iter().filter(|tx| tx.status.confirmed).collect();
+            let last_confirmed = confirmed.last().map(|tx| tx.txid.clone());
+            let is_last_page = confirmed.len() < ESPLORA_PAGE_SIZE;
+            history.extend(page);
+
+            let older: Vec<&EsploraTx> = history.iter().filter(|tx| before.is_none_or(|b| tx.cursor() < *b)).collect();
+            let covered = older.len() >= limit
+                && history.last().is_some_and(|last| last.cursor().height < older[limit.max(1) - 1].cursor().height);
+            let reached_until = until.is_some_and(|u| history.last().is_some_and(|tx| tx.cursor() <= *u));
+            match last_confirmed {
+                Some(txid) if !is_last_page && !covered && !reached_until => {
+                    path = format!("/address/{}/txs/chain/{}", address, txid);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(history)
+    }
+
+    // Addresses are used as-is; extended keys are expanded to every used address on the receive and change chains
+    async fn wallet_addresses(&self, wallet: &BitcoinWallet) -> Result<Vec<Address>> {
+        let (xpub, script) = match wallet {
+            BitcoinWallet::Address(address) => return Ok(vec![address.clone()]),
+            BitcoinWallet::ExtendedKey { xpub, script } => (xpub, *script),
+        };
+
+        let gap_limit = self.config.bitcoin_gap_limit.max(1);
+        let concurrency = self.config.token_lookup_concurrency.max(1);
+        let mut used = Vec::new();
+        for chain in [0, 1] {
+            // Scan in windows of gap_limit until that many consecutive addresses have no history
+            let mut next_index = 0u32;
+            let mut last_used: Option<u32> = None;
+            loop {
+                let window: Vec<(u32, Address)> = (next_index..next_index + gap_limit)
+                    .map(|index| Ok((index, self.derive_address(xpub, script, chain, index)?)))
+                    .collect::<Result<_>>()?;
+                let paths: Vec<String> = window.iter().map(|(_, addr)| format!("/address/{}", addr)).collect();
+                let stats: Vec<EsploraAddress> = stream::iter(paths)
+                    .map(|path| self.get_json::<EsploraAddress>(path))
+                    .buffered(concurrency)
+                    .try_collect()
+                    .await?;
+
+                for ((index, addr), stats) in window.into_iter().zip(stats) {
+                    if stats.chain_stats.tx_count + stats.mempool_stats.tx_count > 0 {
+                        last_used = Some(index);
+                        used.push(addr);
+                    }
+                }
+                next_index += gap_limit;
+
+                let unused_run = next_index - last_used.map(|i| i + 1).unwrap_or(0);
+                if unused_run >= gap_limit {
+                    break;
+                }
+            }
+        }
+
+        Ok(used)
+    }
+
+    fn derive_address(&self, xpub: &Xpub, script: ScriptKind, chain: u32, index: u32) -> Result<Address> {
+        let path = [ChildNumber::from_normal_idx(chain)?, ChildNumber::from_normal_idx(index)?];
+        let child = xpub.derive_pub(&self.secp, &path)?;
+        let public_key = child.to_pub();
+
+        Ok(match script {
+            ScriptKind::Legacy => Address::p2pkh(public_key, self.network),
+            ScriptKind::NestedSegwit => Address::p2shwpkh(&public_key, self.network),
+            ScriptKind::NativeSegwit => Address::p2wpkh(&public_key, self.network),
+            ScriptKind::Taproot => Address::p2tr(&self.secp, child.public_key.x_only_public_key().0, None, self.network),
+        })
+    }
+
+    // Accepts an address, an x/y/zpub (or testnet t/u/vpub), or a pkh/sh(wpkh)/wpkh/tr descriptor around one
+    fn parse_wallet(&self, input: &str) -> Result<BitcoinWallet> {
+        let input = input.trim();
+        if let Ok(address) = Address::from_str(input) {
+            return Ok(BitcoinWallet::Address(address.require_network(self.network)?));
+        }
+
+        let (key, descriptor_script) = match strip_descriptor(input) {
+            Some((key, script)) => (key, Some(script)),
+            None => (input, None),
+        };
+        let (xpub, key_script) = self.parse_extended_key(key)?;
+
+        // A descriptor's script type takes precedence over the key's version prefix
+        let script = descriptor_script.unwrap_or(key_script);
+        Ok(BitcoinWallet::ExtendedKey { xpub, script })
+    }
+
+    fn parse_extended_key(&self, key: &str) -> Result<(Xpub, ScriptKind)> {
+        let mut data = base58::decode_check(key).map_err(|e| anyhow::anyhow!("Invalid Bitcoin address or extended key: {}", e))?;
+        if data.len() != 78 {
+            return Err(anyhow::anyhow!("Invalid extended key length: {}", data.len()));
+        }
+
+        let (_, is_mainnet, script) = EXTENDED_KEY_VERSIONS
+            .iter()
+            .find(|(version, ..)| data[..4] == version[..])
+            .ok_or_else(|| anyhow::anyhow!("Unsupported extended key version"))?;
+        if *is_mainnet != (self.network == Network::Bitcoin) {
+            return Err(anyhow::anyhow!("Extended key is for a different network than {}", self.network));
+        }
+
+        // ypub/zpub differ from xpub only in their version bytes, which encode the script type
+        let xpub_version = if *is_mainnet { EXTENDED_KEY_VERSIONS[0].0 } else { EXTENDED_KEY_VERSIONS[3].0 };
+        data[..4].copy_from_slice(&xpub_version);
+        Ok((Xpub::decode(&data)?, *script))
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, path: String) -> Result<T> {
+        let url = format!("{}{}", self.esplora_url, path);
+        Ok(self.http.get(&url).send().await?.error_for_status()?.json().await?)
+    }
+}
+
+#[async_trait]
+impl ChainClient for BitcoinClient {
+    fn chain(&self) -> &str {
+        "bitcoin"
+    }
+
+    fn is_valid_address(&self, address: &str) -> bool {
+        self.parse_wallet(address).is_ok()
+    }
+
+    // Bech32 addresses are case-insensitive and canonically lowercase; base58 addresses and keys are kept as given
+    fn normalize_address(&self, address: &str) -> String {
+        match self.parse_wallet(address) {
+            Ok(BitcoinWallet::Address(address)) => address.to_string(),
+            _ => address.trim().to_string(),
+        }
+    }
+
+    fn is_valid_cursor(&self, cursor: &str) -> bool {
+        cursor.parse::<BitcoinCursor>().is_ok()
+    }
+
+    async fn fetch_portfolio(&self, address: &str) -> Result<PortfolioResponse> {
+        BitcoinClient::fetch_portfolio(self, address).await
+    }
+
+    async fn fetch_transactions(&self, address: &str, limit: usize, before: Option<&str>, until: Option<&str>) -> Result<TransactionPage> {
+        let before = before.map(|c| c.parse::<BitcoinCursor>()).transpose().map_err(anyhow::Error::msg)?;
+        let until = until.map(|c| c.parse::<BitcoinCursor>()).transpose().map_err(anyhow::Error::msg)?;
+        BitcoinClient::fetch_transactions(self, address, limit, before, until).await
+    }
+}
+
+// Returns the key inside a single-key descriptor and the script type the descriptor implies
+fn strip_descriptor(input: &str) -> Option<(&str, ScriptKind)> {
+    // Checksums ("#abcd1234") are not verified
+    let input = input.split('#').next()?;
+    let wrappers = [
+        ("sh(wpkh(", "))", ScriptKind::NestedSegwit),
+        ("wpkh(", ")", ScriptKind::NativeSegwit),
+        ("pkh(", ")", ScriptKind::Legacy),
+        ("tr(", ")", ScriptKind::Taproot),
+    ];
+    wrappers.iter().find_map(|(prefix, suffix, script)| {
+        let key = input.strip_prefix(prefix)?.strip_suffix(suffix)?;
+        // Derivation steps after the key are implied: receive and change chains are always scanned
+        let key = key.split('/').next()?;
+        Some((key, *script))
+    })
+}
+
+// Length of the page to serve from `txs`, sorted newest first. Esplora orders a block's transactions
+// differently from cursors, so a page never ends partway through a block: history resumed after the
+// cursor's transaction would skip the block's transactions Esplora lists before it.
+fn page_end(txs: &[EsploraTx], limit: usize) -> usize {
+    if txs.len() <= limit {
+        return txs.len();
+    }
+
+    let cut = txs[limit].cursor().height;
+    match txs[..limit].iter().position(|tx| tx.cursor().height == cut) {
+        // One block fills the page, so all of it is served
+        Some(0) => txs.iter().position(|tx| tx.cursor().height != cut).unwrap_or(txs.len()),
+        Some(start) => start,
+        None => limit,
+    }
+}
+
+// Describes a transaction from the wallet's point of view, netting inputs it spent against outputs it received
+fn to_transaction(tx: &EsploraTx, owned: &HashSet<String>) -> Transaction {
+    let is_owned = |address: &Option<String>| address.as_ref().is_some_and(|a| owned.contains(a));
+
+    let spent: u64 = tx.vin.iter().filter_map(|i| i.prevout.as_ref()).filter(|o| is_owned(&o.scriptpubkey_address)).map(|o| o.value).sum();
+    let received: u64 = tx.vout.iter().filter(|o| is_owned(&o.scriptpubkey_address)).map(|o| o.value).sum();
+    let external_out: u64 = tx.vout.iter().filter(|o| !is_owned(&o.scriptpubkey_address)).map(|o| o.value).sum();
+
+    let first_input = |ours: bool| {
+        tx.vin
+            .iter()
+            .filter_map(|i| i.prevout.as_ref()?.scriptpubkey_address.clone())
+            .find(|a| owned.contains(a) == ours)
+    };
+    let first_output = |ours: bool| {
+        tx.vout
+            .iter()
+            .filter_map(|o| o.scriptpubkey_address.clone())
+            .find(|a| owned.contains(a) == ours)
+    };
+
+    let (transaction_type, sats, from, to, counterparty) = if spent == 0 {
+        let sender = first_input(false);
+        ("receive", received, sender.clone(), first_output(true), sender)
+    } else if external_out == 0 {
+        ("self", received, first_input(true), first_output(true), None)
+    } else {
+        let recipient = first_output(false);
+        ("send", external_out, first_input(true), recipient.clone(), recipient)
+    };
+
+    let raw_amount = sats.to_string();
+    Transaction {
+        hash: tx.txid.clone(),
+        timestamp: tx.status.block_time.unwrap_or_else(|| chrono::Utc::now().timestamp()),
+        transaction_type: transaction_type.to_string(),
+        amount: amounts::to_f64(&raw_amount, BTC_DECIMALS),
+        raw_amount,
+        decimals: BTC_DECIMALS,
+        token_symbol: "BTC".to_string(),
+        chain: "bitcoin".to_string(),
+        // Bitcoin transactions cannot fail once mined; unconfirmed ones are reported as pending
+        status: if tx.status.confirmed { "success" } else { "pending" }.to_string(),
+        from: from.unwrap_or_default(),
+        to: to.unwrap_or_default(),
+        token_mint: None,
+        counterparty,
+        // The fee is only paid when the wallet funded the transaction
+        fee: (spent > 0).then(|| amounts::to_f64(&tx.fee.to_string(), BTC_DECIMALS)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::services::cache::CacheService;
+    use crate::services::esplora_stub;
+    use crate::services::token_registry::TokenRegistry;
+    use serde_json::{json, Value};
+    use std::sync::Mutex;
+
+    // BIP84 test vector account key, m/84'/0'/0'
+    const ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+    const RECEIVE_0: &str = "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu";
+    const RECEIVE_1: &str = "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g";
+    const CHANGE_0: &str = "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el";
+    const EXTERNAL: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+    // Prices come from a database that never answers, so only the stub Esplora server is read
+    fn client(esplora_url: String, network: &str) -> BitcoinClient {
+        let cache = CacheService::new(database::unreachable_pool());
+        let config = Config {
+            bitcoin_esplora_url: esplora_url,
+            bitcoin_network: network.to_string(),
+            bitcoin_gap_limit: 2,
+            ..Config::for_tests()
+        };
+        let price_service = PriceService::new(cache.clone(), TokenRegistry::new(cache), config.clone());
+        BitcoinClient::new(price_service, config)
+    }
+
+    fn txid(n: u64) -> String {
+        format!("{:064x}", n)
+    }
+
+    fn tx_json(n: u64, height: u64, inputs: &[(&str, u64)], outputs: &[(&str, u64)]) -> Value {
+        json!({
+            "txid": txid(n),
+            "vin": inputs.iter().map(|(a, v)| json!({ "prevout": { "scriptpubkey_address": a, "value": v } })).collect::<Vec<_>>(),
+            "vout": outputs.iter().map(|(a, v)| json!({ "scriptpubkey_address": a, "value": v })).collect::<Vec<_>>(),
+            "fee": 200,
+            "status": { "confirmed": true, "block_height": height, "block_time": 1_700_000_000 + height as i64 },
+        })
+    }
+
+    fn esplora_tx(value: Value) -> EsploraTx {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn address_stats(tx_count: u64) -> Value {
+        json!({ "chain_stats": { "tx_count": tx_count }, "mempool_stats": { "tx_count": 0 } })
+    }
+
+    // Answers /txs and /txs/chain/<txid> for one address's history, newest first, in pages of 25
+    fn history_page(history: &[Value], rest: &str) -> Option<Value> {
+        let start = match rest {
+            "/txs" => 0,
+            rest => {
+                let after = rest.strip_prefix("/txs/chain/")?;
+                history.iter().position(|tx| tx["txid"] == after)? + 1
+            }
+        };
+        Some(Value::Array(history.iter().skip(start).take(ESPLORA_PAGE_SIZE).cloned().collect()))
+    }
+
+    fn derive(client: &BitcoinClient, wallet: &str, chain: u32, index: u32) -> String {
+        match client.parse_wallet(wallet).unwrap() {
+            BitcoinWallet::ExtendedKey { xpub, script } => client.derive_address(&xpub, script, chain, index).unwrap().to_string(),
+            BitcoinWallet::Address(_) => panic!("expected an extended key"),
+        }
+    }
+
+    #[tokio::test]
+    async fn derives_known_answer_addresses() {
+        let mainnet = client(String::new(), "bitcoin");
+        assert_eq!(derive(&mainnet, ZPUB, 0, 0), RECEIVE_0);
+        assert_eq!(derive(&mainnet, ZPUB, 0, 1), RECEIVE_1);
+        assert_eq!(derive(&mainnet, ZPUB, 1, 0), CHANGE_0);
+
+        // BIP44 test vector, m/44'/0'/0'
+        let xpub = "xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj";
+        assert_eq!(derive(&mainnet, xpub, 0, 0), "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA");
+
+        // BIP86 test vector, m/86'/0'/0'
+        let tr = "tr(xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*)";
+        assert_eq!(derive(&mainnet, tr, 0, 0), "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr");
+
+        // BIP49 test vector, m/49'/1'/0' on testnet
+        let testnet = client(String::new(), "testnet");
+        let upub = "upub5EFU65HtV5TeiSHmZZm7FUffBGy8UKeqp7vw43jYbvZPpoVsgU93oac7Wk3u6moKegAEWtGNF8DehrnHtv21XXEMYRUocHqguyjknFHYfgY";
+        assert_eq!(derive(&testnet, upub, 0, 0), "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2");
+
+        // Keys are only accepted on their own network
+        assert!(testnet.parse_wallet(ZPUB).is_err());
+        assert!(mainnet.parse_wallet(upub).is_err());
+    }
+
+    #[tokio::test]
+    async fn descriptors_override_the_version_of_a_rewritten_key() {
+        let client = client(String::new(), "bitcoin");
+
+        // The same key re-encoded with xpub version bytes, as descriptors carry it
+        let mut data = base58::decode_check(ZPUB).unwrap();
+        data[..4].copy_from_slice(&EXTENDED_KEY_VERSIONS[0].0);
+        let xpub = base58::encode_check(&data);
+
+        assert_eq!(derive(&client, &format!("wpkh({}/0/*)#8ax2ppfz", xpub), 0, 1), RECEIVE_1);
+        assert_eq!(derive(&client, &format!("wpkh({})", ZPUB), 1, 0), CHANGE_0);
+        assert_eq!(strip_descriptor(&format!("sh(wpkh({}/1/*))#abcdefgh", xpub)), Some((xpub.as_str(), ScriptKind::NestedSegwit)));
+        assert_eq!(strip_descriptor(&format!("pkh({})", ZPUB)), Some((ZPUB, ScriptKind::Legacy)));
+        assert_eq!(strip_descriptor(ZPUB), None);
+
+        // Without a descriptor the plain xpub derives legacy addresses from the same key
+        assert!(derive(&client, &xpub, 0, 1).starts_with('1'));
+    }
+
+    #[test]
+    fn nets_inputs_against_outputs_from_the_wallet_point_of_view() {
+        let owned: HashSet<String> = [RECEIVE_0, CHANGE_0].iter().map(|a| a.to_string()).collect();
+
+        let receive = to_transaction(&esplora_tx(tx_json(1, 100, &[(EXTERNAL, 60_000)], &[(RECEIVE_0, 50_000), (EXTERNAL, 9_800)])), &owned);
+        assert_eq!((receive.transaction_type.as_str(), receive.raw_amount.as_str()), ("receive", "50000"));
+        assert_eq!((receive.from.as_str(), receive.to.as_str()), (EXTERNAL, RECEIVE_0));
+        assert_eq!(receive.counterparty.as_deref(), Some(EXTERNAL));
+        assert_eq!(receive.fee, None);
+
+        // Change back to the wallet is netted out of the amount sent
+        let send = to_transaction(&esplora_tx(tx_json(2, 101, &[(RECEIVE_0, 50_000)], &[(EXTERNAL, 30_000), (CHANGE_0, 19_800)])), &owned);
+        assert_eq!((send.transaction_type.as_str(), send.raw_amount.as_str()), ("send", "30000"));
+        assert_eq!((send.from.as_str(), send.to.as_str()), (RECEIVE_0, EXTERNAL));
+        assert_eq!(send.fee, Some(0.000002));
+
+        let consolidate = to_transaction(&esplora_tx(tx_json(3, 102, &[(RECEIVE_0, 30_000), (CHANGE_0, 19_800)], &[(RECEIVE_0, 49_600)])), &owned);
+        assert_eq!((consolidate.transaction_type.as_str(), consolidate.raw_amount.as_str()), ("self", "49600"));
+        assert_eq!(consolidate.counterparty, None);
+        assert_eq!(consolidate.fee, Some(0.000002));
+    }
+
+    #[test]
+    fn pages_end_on_block_boundaries() {
+        let txs: Vec<EsploraTx> = [10, 9, 9, 9, 8].iter().enumerate().map(|(n, h)| esplora_tx(tx_json(n as u64, *h, &[], &[]))).collect();
+        assert_eq!(page_end(&txs, 2), 1);
+        assert_eq!(page_end(&txs, 3), 1);
+        assert_eq!(page_end(&txs, 4), 4);
+        assert_eq!(page_end(&txs, 5), 5);
+        // A block larger than the page is served whole
+        assert_eq!(page_end(&txs[1..], 2), 3);
+    }
+
+    #[tokio::test]
+    async fn resumes_address_history_after_the_cursor_transaction() {
+        // One transaction per block, from height 130 down to 101
+        let history: Vec<Value> = (0..30).map(|n| tx_json(n, 130 - n, &[(EXTERNAL, 1_000)], &[(RECEIVE_0, 900)])).collect();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let url = esplora_stub::spawn(move |path| {
+            seen.lock().unwrap().push(path.to_string());
+            history_page(&history, path.strip_prefix(&format!("/address/{}", RECEIVE_0))?)
+        })
+        .await;
+        let client = client(url, "bitcoin");
+
+        let first = client.fetch_transactions(RECEIVE_0, 10, None, None).await.unwrap();
+        assert_eq!(first.transactions.len(), 10);
+        let cursor: BitcoinCursor = first.next_cursor.unwrap().parse().unwrap();
+        assert_eq!(cursor, BitcoinCursor { height: 121, txid: txid(9) });
+        assert_eq!(*requests.lock().unwrap(), vec![format!("/address/{}/txs", RECEIVE_0)]);
+
+        requests.lock().unwrap().clear();
+        let second = client.fetch_transactions(RECEIVE_0, 10, Some(cursor), None).await.unwrap();
+        let hashes: Vec<String> = second.transactions.iter().map(|tx| tx.hash.clone()).collect();
+        assert_eq!(hashes, (10..20).map(txid).collect::<Vec<_>>());
+        assert_eq!(*requests.lock().unwrap(), vec![format!("/address/{}/txs/chain/{}", RECEIVE_0, txid(9))]);
+    }
+
+    #[tokio::test]
+    async fn scans_both_chains_up_to_the_gap_limit_and_resumes_the_cursor_addresses() {
+        let receive_history = vec![
+            tx_json(3, 103, &[(EXTERNAL, 5_000)], &[(RECEIVE_0, 4_000)]),
+            tx_json(2, 102, &[(EXTERNAL, 5_000)], &[(RECEIVE_0, 4_000)]),
+            tx_json(1, 101, &[(EXTERNAL, 5_000)], &[(RECEIVE_0, 4_000)]),
+        ];
+        let change_history = vec![tx_json(4, 104, &[(EXTERNAL, 5_000)], &[(CHANGE_0, 4_000)])];
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let url = esplora_stub::spawn(move |path| {
+            seen.lock().unwrap().push(path.to_string());
+            if let Some(txid) = path.strip_prefix("/tx/") {
+                return receive_history.iter().chain(&change_history).find(|tx| tx["txid"] == txid).cloned();
+            }
+            let (address, rest) = path.strip_prefix("/address/")?.split_once('/').unwrap_or((path.strip_prefix("/address/")?, ""));
+            let history = match address {
+                RECEIVE_0 => &receive_history,
+                CHANGE_0 => &change_history,
+                _ => return (rest.is_empty()).then(|| address_stats(0)),
+            };
+            match rest {
+                "" => Some(address_stats(history.len() as u64)),
+                rest => history_page(history, &format!("/{}", rest)),
+            }
+        })
+        .await;
+        let client = client(url, "bitcoin");
+
+        // With a gap limit of 2, each chain stops after two windows: the used index 0 and two unused after it
+        let wallet = client.parse_wallet(ZPUB).unwrap();
+        let used: Vec<String> = client.wallet_addresses(&wallet).await.unwrap().iter().map(|a| a.to_string()).collect();
+        assert_eq!(used, vec![RECEIVE_0, CHANGE_0]);
+        assert_eq!(requests.lock().unwrap().len(), 8);
+
+        let first = client.fetch_transactions(ZPUB, 2, None, None).await.unwrap();
+        let hashes: Vec<String> = first.transactions.iter().map(|tx| tx.hash.clone()).collect();
+        assert_eq!(hashes, vec![txid(4), txid(3)]);
+
+        // Only the address the cursor transaction paid resumes after it; the change address starts over
+        requests.lock().unwrap().clear();
+        let cursor = first.next_cursor.unwrap().parse().unwrap();
+        let second = client.fetch_transactions(ZPUB, 2, Some(cursor), None).await.unwrap();
+        let hashes: Vec<String> = second.transactions.iter().map(|tx| tx.hash.clone()).collect();
+        assert_eq!(hashes, vec![txid(2), txid(1)]);
+        let requests = requests.lock().unwrap();
+        assert!(requests.contains(&format!("/tx/{}", txid(3))));
+        assert!(requests.contains(&format!("/address/{}/txs/chain/{}", RECEIVE_0, txid(3))));
+        assert!(requests.contains(&format!("/address/{}/txs", CHANGE_0)));
+        assert!(!requests.contains(&format!("/address/{}/txs", RECEIVE_0)));
+    }
+}
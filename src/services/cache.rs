@@ -2,6 +2,10 @@ use sqlx::{PgPool, Row};
 use serde_json::Value;
 use chrono::{Utc, Duration};
 use anyhow::Result;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use crate::services::price_provider::{PriceQuote, TokenRef};
+use crate::services::token_registry::TokenListSnapshot;
 
 #[derive(Clone)]
 pub struct CacheService {
@@ -51,7 +55,43 @@ impl CacheService {
         Ok(())
     }
 
-    pub async fn get_price(&self, token_id: &str, chain: &str) -> Result<Option<f64>> {
+    pub async fn get_fx_rate(&self, currency: &str) -> Result<Option<Decimal>> {
+        let result = sqlx::query(
+            r#"
+            SELECT rate FROM cached_fx_rates
+            WHERE currency = $1 AND expires_at > NOW()
+            "#
+        )
+        .bind(currency)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result
+            .map(|row| row.try_get::<Decimal, _>("rate"))
+            .transpose()?)
+    }
+
+    pub async fn set_fx_rate(&self, currency: &str, rate: Decimal, ttl_seconds: u64) -> Result<()> {
+        let expires_at = (Utc::now() + Duration::seconds(ttl_seconds as i64)).naive_utc();
+
+        sqlx::query(
+            r#"
+            INSERT INTO cached_fx_rates (currency, rate, expires_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (currency)
+            DO UPDATE SET rate = $2, expires_at = $3, created_at = NOW()
+            "#
+        )
+        .bind(currency)
+        .bind(rate)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_price(&self, token_id: &str, chain: &str) -> Result<Option<Decimal>> {
         let result = sqlx::query(
             r#"
             SELECT price_usd FROM cached_prices
@@ -64,14 +104,15 @@ impl CacheService {
         .await?;
 
         Ok(result
-            .map(|row| row.try_get::<f64, _>("price_usd"))
-            .transpose()?)
+            .map(|row| row.try_get::<Option<Decimal>, _>("price_usd"))
+            .transpose()?
+            .map(|price| price.unwrap_or_default()))
     }
 
-    pub async fn get_price_with_change(&self, token_id: &str, chain: &str) -> Result<Option<(f64, Option<f64>)>> {
+    pub async fn get_price_with_change(&self, token_id: &str, chain: &str) -> Result<Option<(Decimal, Option<f64>)>> {
         let result = sqlx::query(
             r#"
-            SELECT price_usd, price_change_24h FROM cached_prices
+            SELECT price_usd, price_change_24h::FLOAT8 AS price_change_24h FROM cached_prices
             WHERE token_id = $1 AND chain = $2 AND expires_at > NOW()
             "#
         )
@@ -82,15 +123,16 @@ impl CacheService {
 
         match result {
             Some(row) => {
-                let price: f64 = row.try_get("price_usd")?;
-                let change: Option<f64> = row.try_get("price_change_24h").ok();
-                Ok(Some((price, change)))
+                // Unpriced tokens are cached with a NULL price
+                let price: Option<Decimal> = row.try_get("price_usd")?;
+                let change: Option<f64> = row.try_get("price_change_24h").ok().flatten();
+                Ok(Some((price.unwrap_or_default(), change)))
             }
             None => Ok(None),
         }
     }
 
-    pub async fn set_price(&self, token_id: &str, chain: &str, price: f64, ttl_seconds: u64) -> Result<()> {
+    pub async fn set_price(&self, token_id: &str, chain: &str, price: Decimal, ttl_seconds: u64) -> Result<()> {
         let expires_at = (Utc::now() + Duration::seconds(ttl_seconds as i64)).naive_utc();
 
         sqlx::query(
@@ -111,7 +153,7 @@ impl CacheService {
         Ok(())
     }
 
-    pub async fn set_price_with_change(&self, token_id: &str, chain: &str, price: f64, price_change_24h: Option<f64>, ttl_seconds: u64) -> Result<()> {
+    pub async fn set_price_with_change(&self, token_id: &str, chain: &str, price: Decimal, price_change_24h: Option<f64>, ttl_seconds: u64) -> Result<()> {
         let expires_at = (Utc::now() + Duration::seconds(ttl_seconds as i64)).naive_utc();
 
         sqlx::query(
@@ -119,7 +161,7 @@ impl CacheService {
             INSERT INTO cached_prices (token_id, chain, price_usd, price_change_24h, expires_at)
             VALUES ($1, $2, $3, $4, $5)
             ON CONFLICT (token_id, chain) 
-            DO UPDATE SET price_usd = $3, price_change_24h = $4, expires_at = $5, created_at = NOW()
+            DO UPDATE SET price_usd = $3, price_change_24h = $4, confidence = NULL, source = NULL, expires_at = $5, created_at = NOW()
             "#
         )
         .bind(token_id)
@@ -133,6 +175,78 @@ impl CacheService {
         Ok(())
     }
 
+    // Unexpired quotes keyed by (chain, token id); tokens may span several chains
+    pub async fn get_price_quotes(&self, tokens: &[TokenRef]) -> Result<HashMap<(String, String), PriceQuote>> {
+        let token_ids: Vec<&str> = tokens.iter().map(|t| t.id.as_str()).collect();
+        let chains: Vec<&str> = tokens.iter().map(|t| t.chain.as_str()).collect();
+        let rows = sqlx::query(
+            r#"
+            SELECT p.token_id, p.chain, p.price_usd, p.price_change_24h::FLOAT8 AS price_change_24h,
+                   p.confidence, p.source
+            FROM cached_prices p
+            JOIN UNNEST($1::VARCHAR[], $2::VARCHAR[]) AS t(token_id, chain)
+                ON p.token_id = t.token_id AND p.chain = t.chain
+            WHERE p.expires_at > NOW()
+            "#
+        )
+        .bind(&token_ids)
+        .bind(&chains)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut quotes = HashMap::new();
+        for row in rows {
+            let key: (String, String) = (row.try_get("chain")?, row.try_get("token_id")?);
+            quotes.insert(key, PriceQuote {
+                price_usd: row.try_get("price_usd")?,
+                price_change_24h: row.try_get("price_change_24h")?,
+                confidence: row.try_get("confidence")?,
+                source: row.try_get("source")?,
+            });
+        }
+
+        Ok(quotes)
+    }
+
+    // Writes every quote in one upsert; each token must appear only once
+    pub async fn set_price_quotes(&self, quotes: &[(&TokenRef, &PriceQuote)], ttl_seconds: u64) -> Result<()> {
+        if quotes.is_empty() {
+            return Ok(());
+        }
+        let expires_at = (Utc::now() + Duration::seconds(ttl_seconds as i64)).naive_utc();
+
+        let token_ids: Vec<&str> = quotes.iter().map(|(t, _)| t.id.as_str()).collect();
+        let chains: Vec<&str> = quotes.iter().map(|(t, _)| t.chain.as_str()).collect();
+        let prices: Vec<Option<Decimal>> = quotes.iter().map(|(_, q)| q.price_usd).collect();
+        let changes: Vec<Option<f64>> = quotes.iter().map(|(_, q)| q.price_change_24h).collect();
+        let confidences: Vec<Option<&str>> = quotes.iter().map(|(_, q)| q.confidence.as_deref()).collect();
+        let sources: Vec<Option<&str>> = quotes.iter().map(|(_, q)| q.source.as_deref()).collect();
+
+        sqlx::query(
+            r#"
+            INSERT INTO cached_prices (token_id, chain, price_usd, price_change_24h, confidence, source, expires_at)
+            SELECT token_id, chain, price_usd, price_change_24h, confidence, source, $7
+            FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::NUMERIC[], $4::FLOAT8[], $5::VARCHAR[], $6::VARCHAR[])
+                AS t(token_id, chain, price_usd, price_change_24h, confidence, source)
+            ON CONFLICT (token_id, chain) 
+            DO UPDATE SET price_usd = EXCLUDED.price_usd, price_change_24h = EXCLUDED.price_change_24h,
+                          confidence = EXCLUDED.confidence, source = EXCLUDED.source,
+                          expires_at = EXCLUDED.expires_at, created_at = NOW()
+            "#
+        )
+        .bind(&token_ids)
+        .bind(&chains)
+        .bind(&prices)
+        .bind(&changes)
+        .bind(&confidences)
+        .bind(&sources)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn get_metadata(&self, token_id: &str, chain: &str) -> Result<Option<Value>> {
         let result = sqlx::query(
             r#"
@@ -158,7 +272,7 @@ impl CacheService {
             INSERT INTO cached_metadata (token_id, chain, metadata, expires_at)
             VALUES ($1, $2, $3, $4)
             ON CONFLICT (token_id, chain) 
-            DO UPDATE SET metadata = $3, expires_at = $4, created_at = NOW()
+            DO UPDATE SET metadata = cached_metadata.metadata || $3, expires_at = $4, created_at = NOW()
             "#
         )
         .bind(token_id)
@@ -170,5 +284,206 @@ impl CacheService {
 
         Ok(())
     }
-}
 
+    pub async fn get_metadata_batch(&self, token_ids: &[String], chain: &str) -> Result<HashMap<String, Value>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT token_id, metadata FROM cached_metadata
+            WHERE token_id = ANY($1) AND chain = $2 AND expires_at > NOW()
+            "#
+        )
+        .bind(token_ids)
+        .bind(chain)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut metadata = HashMap::new();
+        for row in rows {
+            metadata.insert(row.try_get("token_id")?, row.try_get("metadata")?);
+        }
+
+        Ok(metadata)
+    }
+
+    // Marks rows stale without dropping them, so decimals stay readable through get_token_details
+    pub async fn expire_metadata(&self, token_ids: &[String], chain: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE cached_metadata SET expires_at = NOW()
+            WHERE token_id = ANY($1) AND chain = $2 AND expires_at > NOW()
+            "#
+        )
+        .bind(token_ids)
+        .bind(chain)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Decimals and symbols never change for a token, so they are read regardless of the row's expiry
+    pub async fn get_token_details(&self, token_ids: &[String], chain: &str) -> Result<HashMap<String, (u8, Option<String>)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT token_id, (metadata->>'decimals')::INTEGER AS decimals, metadata->>'symbol' AS symbol
+            FROM cached_metadata
+            WHERE token_id = ANY($1) AND chain = $2 AND metadata ? 'decimals'
+            "#
+        )
+        .bind(token_ids)
+        .bind(chain)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut details = HashMap::new();
+        for row in rows {
+            let token_id: String = row.try_get("token_id")?;
+            let decimals: i32 = row.try_get("decimals")?;
+            let symbol: Option<String> = row.try_get("symbol")?;
+            details.insert(token_id, (decimals as u8, symbol));
+        }
+
+        Ok(details)
+    }
+
+    pub async fn set_token_details(&self, token_id: &str, chain: &str, decimals: u8, symbol: Option<&str>) -> Result<()> {
+        // New rows are inserted already expired so name/logo lookups still treat them as a miss
+        sqlx::query(
+            r#"
+            INSERT INTO cached_metadata (token_id, chain, metadata, expires_at)
+            VALUES ($1, $2, jsonb_strip_nulls(jsonb_build_object('decimals', $3::INTEGER, 'symbol', $4::TEXT)), NOW())
+            ON CONFLICT (token_id, chain) 
+            DO UPDATE SET metadata = cached_metadata.metadata || jsonb_strip_nulls(jsonb_build_object('decimals', $3::INTEGER, 'symbol', $4::TEXT))
+            "#
+        )
+        .bind(token_id)
+        .bind(chain)
+        .bind(decimals as i32)
+        .bind(symbol)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Outer None is a cache miss; Some(None) is a cached lookup that found nothing
+    pub async fn get_name_lookup(&self, lookup: &str, naming_system: &str, direction: &str) -> Result<Option<Option<String>>> {
+        let result = sqlx::query(
+            r#"
+            SELECT result FROM cached_names
+            WHERE lookup = $1 AND naming_system = $2 AND direction = $3 AND expires_at > NOW()
+            "#
+        )
+        .bind(lookup)
+        .bind(naming_system)
+        .bind(direction)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result
+            .map(|row| row.try_get::<Option<String>, _>("result"))
+            .transpose()?)
+    }
+
+    pub async fn set_name_lookup(&self, lookup: &str, naming_system: &str, direction: &str, result: Option<&str>, ttl_seconds: u64) -> Result<()> {
+        let expires_at = (Utc::now() + Duration::seconds(ttl_seconds as i64)).naive_utc();
+
+        sqlx::query(
+            r#"
+            INSERT INTO cached_names (lookup, naming_system, direction, result, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (lookup, naming_system, direction)
+            DO UPDATE SET result = $4, expires_at = $5, created_at = NOW()
+            "#
+        )
+        .bind(lookup)
+        .bind(naming_system)
+        .bind(direction)
+        .bind(result)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Unexpired token URIs and metadata by NFT id; None metadata is a token URI that could not be fetched
+    pub async fn get_nft_metadata(&self, nft_ids: &[String], chain: &str) -> Result<HashMap<String, (Option<String>, Option<Value>)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT nft_id, token_uri, metadata FROM cached_nft_metadata
+            WHERE nft_id = ANY($1) AND chain = $2 AND expires_at > NOW()
+            "#
+        )
+        .bind(nft_ids)
+        .bind(chain)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut metadata = HashMap::new();
+        for row in rows {
+            let nft_id: String = row.try_get("nft_id")?;
+            let token_uri: Option<String> = row.try_get("token_uri")?;
+            let data: Option<Value> = row.try_get("metadata")?;
+            metadata.insert(nft_id, (token_uri, data));
+        }
+
+        Ok(metadata)
+    }
+
+    pub async fn set_nft_metadata(&self, nft_id: &str, chain: &str, token_uri: Option<&str>, metadata: Option<&Value>, ttl_seconds: u64) -> Result<()> {
+        let expires_at = (Utc::now() + Duration::seconds(ttl_seconds as i64)).naive_utc();
+
+        sqlx::query(
+            r#"
+            INSERT INTO cached_nft_metadata (nft_id, chain, token_uri, metadata, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (nft_id, chain)
+            DO UPDATE SET token_uri = $3, metadata = $4, expires_at = $5, created_at = NOW()
+            "#
+        )
+        .bind(nft_id)
+        .bind(chain)
+        .bind(token_uri)
+        .bind(metadata)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Snapshots never expire; they are replaced whenever a list is downloaded again
+    pub async fn get_token_list_snapshots(&self, urls: &[String]) -> Result<Vec<TokenListSnapshot>> {
+        let snapshots = sqlx::query_as::<_, TokenListSnapshot>(
+            r#"
+            SELECT url, etag, last_modified, tokens FROM token_list_snapshots
+            WHERE url = ANY($1)
+            "#
+        )
+        .bind(urls)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(snapshots)
+    }
+
+    pub async fn set_token_list_snapshot(&self, snapshot: &TokenListSnapshot) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO token_list_snapshots (url, etag, last_modified, tokens)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (url)
+            DO UPDATE SET etag = $2, last_modified = $3, tokens = $4, updated_at = NOW()
+            "#
+        )
+        .bind(&snapshot.url)
+        .bind(&snapshot.etag)
+        .bind(&snapshot.last_modified)
+        .bind(&snapshot.tokens)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use crate::types::nft::NftPortfolio;
+use crate::types::portfolio::PortfolioResponse;
+use crate::types::transaction::TransactionPage;
+
+// Operations every supported chain provides to the routes
+#[async_trait]
+pub trait ChainClient: Send + Sync {
+    // Key used in routes, caches and stored data, e.g. "solana" or "polygon"
+    fn chain(&self) -> &str;
+
+    // EVM chain id, for chains served through EthereumClient
+    fn evm_chain_id(&self) -> Option<u64> {
+        None
+    }
+
+    fn is_valid_address(&self, address: &str) -> bool;
+
+    // Canonical form used for cache keys and storage; only called on valid addresses
+    fn normalize_address(&self, address: &str) -> String;
+
+    // Cursors are opaque to routes but must be rejected before reaching the node
+    fn is_valid_cursor(&self, cursor: &str) -> bool;
+
+    // Naming system used as the cache namespace, e.g. "ens" or "sns"; None if the chain has none
+    fn naming_system(&self) -> Option<&'static str> {
+        None
+    }
+
+    // Whether the input is a name such as "vitalik.eth" rather than an address
+    fn is_name(&self, _input: &str) -> bool {
+        false
+    }
+
+    async fn resolve_name(&self, _name: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    async fn lookup_name(&self, _address: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    async fn fetch_portfolio(&self, address: &str) -> Result<PortfolioResponse>;
+
+    async fn fetch_transactions(&self, address: &str, limit: usize, before: Option<&str>, until: Option<&str>) -> Result<TransactionPage>;
+
+    // Routes only ask for NFTs on chains that support them
+    fn supports_nfts(&self) -> bool {
+        false
+    }
+
+    async fn fetch_nfts(&self, _address: &str) -> Result<NftPortfolio> {
+        Err(anyhow::anyhow!("NFTs are not supported on {}", self.chain()))
+    }
+}
+
+// Chain clients by chain key, in registration order
+#[derive(Clone, Default)]
+pub struct ChainRegistry {
+    clients: Vec<Arc<dyn ChainClient>>,
+}
+
+impl ChainRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, client: Arc<dyn ChainClient>) {
+        self.clients.retain(|c| c.chain() != client.chain());
+        self.clients.push(client);
+    }
+
+    pub fn get(&self, chain: &str) -> Option<&Arc<dyn ChainClient>> {
+        self.clients.iter().find(|c| c.chain() == chain)
+    }
+
+    pub fn get_evm(&self, chain_id: u64) -> Option<&Arc<dyn ChainClient>> {
+        self.clients.iter().find(|c| c.evm_chain_id() == Some(chain_id))
+    }
+
+    pub fn evm_chains(&self) -> impl Iterator<Item = &Arc<dyn ChainClient>> {
+        self.clients.iter().filter(|c| c.evm_chain_id().is_some())
+    }
+}
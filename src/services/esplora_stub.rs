@@ -0,0 +1,21 @@
+use axum::{extract::State, http::{StatusCode, Uri}, Json, Router};
+use serde_json::Value;
+use std::sync::Arc;
+
+// In-process Esplora REST server for tests. Each GET is answered with handler(path);
+// None becomes a 404, the way Esplora answers unknown addresses and transactions.
+
+type Handler = Arc<dyn Fn(&str) -> Option<Value> + Send + Sync>;
+
+pub async fn spawn(handler: impl Fn(&str) -> Option<Value> + Send + Sync + 'static) -> String {
+    let handler: Handler = Arc::new(handler);
+    let app = Router::new().fallback(answer).with_state(handler);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    url
+}
+
+async fn answer(State(handler): State<Handler>, uri: Uri) -> Result<Json<Value>, StatusCode> {
+    handler(uri.path()).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
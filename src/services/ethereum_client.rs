@@ -1,169 +1,1641 @@
 use anyhow::Result;
-use ethers::providers::{Provider, Http, Middleware};
-use ethers::types::Address as EthAddress;
-use ethers::contract::Contract;
-use ethers::abi::Abi;
-use std::sync::Arc;
+use async_trait::async_trait;
+use ethers::providers::{Provider, Http, Middleware, ProviderError};
+use ethers::abi::{AbiDecode, AbiEncode, ParamType, Token as AbiToken};
+use ethers::contract::abigen;
+use ethers::contract::multicall_contract::{Call3, Multicall3};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address as EthAddress, Block, Bytes, Filter, Log, Transaction as EthTransaction, TransactionReceipt, TransactionRequest, H256, U256};
+use ethers::utils::{hex, keccak256, to_checksum};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use futures::stream::{self, StreamExt};
+use crate::types::nft::{DiscoveredNft, Nft, NftCollection, NftPortfolio};
 use crate::types::portfolio::PortfolioResponse;
 use crate::types::token::Token;
-use crate::services::price_service::PriceService;
-use crate::services::metadata_service::MetadataService;
-use crate::config::Config;
-
-// Popular ERC-20 tokens
-const POPULAR_TOKENS: &[(&str, &str)] = &[
-    ("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "USDC"),
-    ("0xdAC17F958D2ee523a2206206994597C13D831ec7", "USDT"),
-    ("0x6B175474E89094C44Da98b954EedeAC495271d0F", "DAI"),
-    ("0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599", "WBTC"),
-    ("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "WETH"),
-    ("0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984", "UNI"),
-    ("0x514910771AF9Ca656af840dff83E8264EcF986CA", "LINK"),
-    ("0x7Fc66500c84A76Ad7e9c93437bFc5Ac33E2DDaE9", "AAVE"),
-];
+use crate::types::transaction::{EthereumCursor, EthereumTransfer, Transaction, TransactionPage};
+use crate::services::chain_client::ChainClient;
+use crate::services::price_provider::PriceQuote;
+use crate::services::price_service::{self, PriceService};
+use crate::services::metadata_service::{self, MetadataService};
+use crate::services::history_store::HistoryStore;
+use crate::utils::{amounts, helpers};
+use crate::config::{Config, EvmChainConfig};
+
+abigen!(
+    Erc20,
+    r#"[
+        function balanceOf(address account) external view returns (uint256)
+        function decimals() external view returns (uint8)
+        function symbol() external view returns (string)
+    ]"#
+);
+
+// NFT bindings get their own module because ERC-1155 balanceOf clashes with the ERC-20 one
+mod nft_abi {
+    use ethers::contract::abigen;
+
+    abigen!(
+        Erc721,
+        r#"[
+            function name() external view returns (string)
+            function ownerOf(uint256 tokenId) external view returns (address)
+            function tokenURI(uint256 tokenId) external view returns (string)
+        ]"#
+    );
+
+    abigen!(
+        Erc1155,
+        r#"[
+            function balanceOf(address account, uint256 id) external view returns (uint256)
+            function uri(uint256 id) external view returns (string)
+        ]"#
+    );
+}
+
+// Calls per aggregate3 request, kept well below typical eth_call gas and payload limits
+const MULTICALL_BATCH_SIZE: usize = 300;
+
+// The ENS registry lives on Ethereum mainnet; names resolve to the same address on every EVM chain
+const ENS_CHAIN_ID: u64 = 1;
+
+// Every supported EVM chain uses an 18-decimal native currency
+const NATIVE_DECIMALS: u8 = 18;
+
+const TRANSFER_EVENT: &str = "Transfer(address,address,uint256)";
+const TRANSFER_SINGLE_EVENT: &str = "TransferSingle(address,address,address,uint256,uint256)";
+const TRANSFER_BATCH_EVENT: &str = "TransferBatch(address,address,address,uint256[],uint256[])";
+
+// An NFT the wallet was verified to hold
+struct HeldNft {
+    contract: EthAddress,
+    token_id: U256,
+    standard: String,
+    amount: U256, // Always 1 for ERC-721
+}
 
 #[derive(Clone)]
 pub struct EthereumClient {
-    rpc_url: String,
+    chains: Arc<Vec<EvmChainConfig>>,
     price_service: PriceService,
     metadata_service: MetadataService,
+    history: HistoryStore,
     config: Config,
+    refreshes: Arc<Mutex<HashMap<String, Instant>>>, // Last start of each background refresh by key
+    history_workers: Arc<Semaphore>, // Each running history worker holds a pooled connection for its lock
 }
 
 impl EthereumClient {
-    pub fn new(rpc_url: String, price_service: PriceService, metadata_service: MetadataService, config: Config) -> Self {
+    pub fn new(price_service: PriceService, metadata_service: MetadataService, history: HistoryStore, config: Config) -> Self {
         Self {
-            rpc_url,
+            chains: Arc::new(config.evm_chains.clone()),
             price_service,
             metadata_service,
+            history,
             config,
+            refreshes: Arc::new(Mutex::new(HashMap::new())),
+            history_workers: Arc::new(Semaphore::new(
+                std::env::var("EVM_HISTORY_WORKERS")
+                    .unwrap_or_else(|_| "2".to_string())
+                    .parse()
+                    .unwrap_or(2),
+            )),
         }
     }
 
-    pub async fn fetch_portfolio(&self, address: &str) -> Result<PortfolioResponse> {
+    // Configured chains, in the order given by EVM_CHAINS
+    pub fn chains(&self) -> &[EvmChainConfig] {
+        &self.chains
+    }
+
+    pub fn chain(&self, chain_id: u64) -> Option<&EvmChainConfig> {
+        self.chains.iter().find(|c| c.chain_id == chain_id)
+    }
+
+    // Claims a background refresh unless the same one started within EVM_REFRESH_INTERVAL_SECONDS,
+    // so a wallet that is requested repeatedly doesn't queue the same work on every request
+    fn claim_refresh(&self, key: String) -> bool {
+        let interval = Duration::from_secs(
+            std::env::var("EVM_REFRESH_INTERVAL_SECONDS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .unwrap_or(300),
+        );
+        let now = Instant::now();
+        let mut refreshes = self.refreshes.lock().unwrap_or_else(|e| e.into_inner());
+        refreshes.retain(|_, started| now.duration_since(*started) < interval);
+        if refreshes.contains_key(&key) {
+            return false;
+        }
+        refreshes.insert(key, now);
+        true
+    }
+
+    fn provider(&self, chain_id: u64) -> Result<(&EvmChainConfig, Provider<Http>)> {
+        let chain = self
+            .chain(chain_id)
+            .ok_or_else(|| anyhow::anyhow!("EVM chain {} is not configured", chain_id))?;
+        let provider = Provider::<Http>::try_from(chain.rpc_url.as_str())?;
+        Ok((chain, provider))
+    }
+
+    pub async fn resolve_ens(&self, name: &str) -> Result<Option<String>> {
+        let Ok((_, provider)) = self.provider(ENS_CHAIN_ID) else {
+            return Ok(None);
+        };
+        match provider.resolve_name(name).await {
+            Ok(address) if !address.is_zero() => Ok(Some(format!("{:?}", address))),
+            Ok(_) | Err(ProviderError::EnsError(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Reverse record, which ethers checks against the forward record
+    pub async fn lookup_ens(&self, address: &str) -> Result<Option<String>> {
+        let addr: EthAddress = address.parse()?;
+        let Ok((_, provider)) = self.provider(ENS_CHAIN_ID) else {
+            return Ok(None);
+        };
+        match provider.lookup_address(addr).await {
+            Ok(name) => Ok(Some(name).filter(|n| !n.is_empty())),
+            Err(ProviderError::EnsError(_)) | Err(ProviderError::EnsNotOwned(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn fetch_portfolio(&self, chain_id: u64, address: &str) -> Result<PortfolioResponse> {
         let addr: EthAddress = address.parse()?;
         
         // Create provider
-        let provider = Provider::<Http>::try_from(self.rpc_url.as_str())?;
-        
-        // Fetch ETH balance
-        let balance = provider.get_balance(addr, None).await?;
-        let eth_balance = balance.as_u128() as f64 / 1e18;
+        let (chain, provider) = self.provider(chain_id)?;
         
-        // Get ETH price
-        let (eth_price, _eth_price_change) = self.price_service.get_ethereum_price_with_change("ETH").await.unwrap_or((0.0, None));
-        let eth_value = eth_balance * eth_price;
+        // Fetch native balance
+        let balance = provider.get_balance(addr, None).await?.to_string();
+        let native_balance = amounts::to_f64(&balance, NATIVE_DECIMALS);
 
-        // Fetch ERC-20 token balances
-        let mut tokens = Vec::new();
-        
-        for (token_address, _symbol) in POPULAR_TOKENS {
-            if let Ok((balance, decimals, symbol)) = self.get_token_info(&provider, &addr, token_address).await {
-                if balance > 0.0 {
-                    let (price, price_change) = self.price_service.get_ethereum_price_with_change(token_address).await.unwrap_or((0.0, None));
-                    let value = balance * price;
-
-                    // Get metadata
-                    let (name, logo_uri) = self.metadata_service.get_ethereum_metadata(token_address).await.unwrap_or((None, None));
+        // Discovery scans logs, so it runs in the background at most once per refresh interval per wallet;
+        // portfolios use the tokens found so far plus the chain's seed list
+        let address_key = helpers::evm_address_key(&addr);
+        if self.claim_refresh(format!("tokens:{}:{}", chain.name, address_key)) {
+            let client = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = client.discover_tokens(chain_id, addr).await {
+                    tracing::warn!("Failed to discover tokens for {:?} on chain {}: {}", addr, chain_id, e);
+                }
+            });
+        }
+        let discovered = self.history.get_discovered_tokens(&address_key, &chain.name).await.unwrap_or_default();
 
-                    tokens.push(Token {
-                        symbol: symbol.clone(),
-                        mint_or_address: token_address.to_string(),
-                        amount: balance,
-                        decimals,
-                        price_usd: price,
-                        value_usd: value,
-                        name,
-                        logo_uri,
-                        price_change_24h: price_change,
-                    });
+        let mut token_addresses: Vec<EthAddress> = chain.tokens.iter().filter_map(|t| t.address.parse().ok()).collect();
+        for token in discovered {
+            if let Ok(token_addr) = token.parse::<EthAddress>() {
+                if !token_addresses.contains(&token_addr) {
+                    token_addresses.push(token_addr);
                 }
             }
         }
 
-        let total_tokens_count = tokens.len() + if eth_balance > 0.0 { 1 } else { 0 };
+        // Read all balances in as few aggregate3 calls as possible, with decimals/symbol from the permanent cache
+        let provider = Arc::new(provider);
+        let details = self.get_token_details(chain, &provider, &token_addresses).await?;
+        let balance_calls: Vec<Call3> = token_addresses
+            .iter()
+            .map(|token| Call3 {
+                target: *token,
+                allow_failure: true,
+                call_data: BalanceOfCall { account: addr }.encode().into(),
+            })
+            .collect();
+        let balances = aggregate(chain, &provider, balance_calls).await?;
+
+        let mut held: Vec<(EthAddress, U256, u8, String)> = Vec::new();
+        for (token_addr, result) in token_addresses.iter().zip(balances) {
+            let Some(raw_balance) = result.and_then(|data| BalanceOfReturn::decode(data).ok()).map(|r| r.0) else {
+                continue;
+            };
+            let Some((decimals, symbol)) = details.get(token_addr) else { continue };
+            if !raw_balance.is_zero() {
+                held.push((*token_addr, raw_balance, *decimals, symbol.clone()));
+            }
+        }
+
+        // Price the native asset and every held token in one batch
+        let mut price_refs = vec![price_service::evm_native_token(chain)];
+        price_refs.extend(held.iter().map(|(token_addr, _, decimals, _)| price_service::evm_token(chain, token_addr, Some(*decimals))));
+        let mut quotes = self.price_service.get_prices(&price_refs).await.unwrap_or_else(|e| {
+            tracing::warn!("Failed to get {} prices for {:?}: {}", chain.name, addr, e);
+            vec![PriceQuote::default(); price_refs.len()]
+        });
+        let token_quotes = quotes.split_off(1);
+        let native_price = quotes.pop().and_then(|quote| quote.price_usd).unwrap_or_default();
+        let native_value = amounts::usd_value(&balance, NATIVE_DECIMALS, native_price);
+
+        // Metadata lookups run concurrently, bounded to avoid hammering upstream APIs
+        let concurrency = self.config.token_lookup_concurrency.max(1);
+        let tokens: Vec<Token> = stream::iter(held.into_iter().zip(token_quotes))
+            .map(|((token_addr, raw_balance, decimals, symbol), price)| {
+                self.build_token(chain, token_addr, raw_balance, decimals, symbol, price)
+            })
+            .buffered(concurrency)
+            .collect()
+            .await;
+
+        // NFTs only count towards the total when they can be valued at floor price. Valuing them means
+        // scanning logs and fetching metadata and floors, so portfolios use the last stored valuation
+        // and refresh it in the background.
+        let nft_value_usd = if self.nft_valuation_enabled(chain) {
+            if self.claim_refresh(format!("nfts:{}:{}", chain.name, address_key)) {
+                let client = self.clone();
+                let address = address.to_string();
+                tokio::spawn(async move {
+                    if let Err(e) = client.fetch_nfts(chain_id, &address).await {
+                        tracing::warn!("Failed to value NFTs for {} on chain {}: {}", address, chain_id, e);
+                    }
+                });
+            }
+            self.history.get_nft_valuation(&address_key, &chain.name).await.unwrap_or_else(|e| {
+                tracing::warn!("Failed to read {} NFT valuation for {}: {}", chain.name, address_key, e);
+                None
+            })
+        } else {
+            None
+        };
+
+        let total_tokens_count = tokens.len() + if native_balance > 0.0 { 1 } else { 0 };
+        let total_value_usd = amounts::sum(std::iter::once(native_value).chain(tokens.iter().map(|t| t.value_usd)).chain(nft_value_usd));
         let last_updated = chrono::Utc::now().to_rfc3339();
 
         Ok(PortfolioResponse {
-            chain: "ethereum".to_string(),
-            address: address.to_string(),
-            native_balance: eth_balance,
-            native_price_usd: eth_price,
-            native_value_usd: eth_value,
+            chain: chain.name.clone(),
+            chain_id: Some(chain.chain_id),
+            address: to_checksum(&addr, None),
+            name: None,
+            native_balance,
+            native_raw_balance: balance,
+            native_decimals: NATIVE_DECIMALS,
+            native_price_usd: native_price,
+            native_value_usd: native_value,
             tokens,
+            staking: Vec::new(),
+            nfts: Vec::new(),
             total_tokens_count: Some(total_tokens_count),
+            nft_value_usd,
+            total_value_usd: Some(total_value_usd),
             last_updated: Some(last_updated),
+            ..Default::default()
         })
     }
 
-    async fn get_token_info(&self, provider: &Provider<Http>, owner: &EthAddress, token_address: &str) -> Result<(f64, u8, String)> {
-        let token_addr: EthAddress = token_address.parse()?;
-        let provider_arc = Arc::new(provider.clone());
-        
-        // ERC-20 ABI
-        let abi_json = r#"[
-            {
-                "constant": true,
-                "inputs": [{"name": "_owner", "type": "address"}],
-                "name": "balanceOf",
-                "outputs": [{"name": "balance", "type": "uint256"}],
-                "type": "function"
-            },
-            {
-                "constant": true,
-                "inputs": [],
-                "name": "decimals",
-                "outputs": [{"name": "", "type": "uint8"}],
-                "type": "function"
-            },
-            {
-                "constant": true,
-                "inputs": [],
-                "name": "symbol",
-                "outputs": [{"name": "", "type": "string"}],
-                "type": "function"
-            }
-        ]"#;
-        
-        let abi: Abi = serde_json::from_str(abi_json)?;
-        let contract = Contract::new(token_addr, abi, provider_arc);
-        
-        // Get balance
-        let balance: ethers::types::U256 = contract
-            .method::<_, ethers::types::U256>("balanceOf", owner.clone())?
-            .call()
-            .await?;
-        
-        // Get decimals
-        let decimals: u8 = contract
-            .method::<_, u8>("decimals", ())?
-            .call()
-            .await?;
-        
-        // Get symbol
-        let symbol: String = contract
-            .method::<_, String>("symbol", ())?
-            .call()
-            .await?;
-        
-        let balance_f64 = balance.as_u128() as f64 / 10_f64.powi(decimals as i32);
-        
-        Ok((balance_f64, decimals, symbol))
+    // Tokens no provider can price come with an empty quote and are unpriced rather than worth zero
+    async fn build_token(&self, chain: &EvmChainConfig, token_addr: EthAddress, raw_balance: U256, decimals: u8, symbol: String, price: PriceQuote) -> Token {
+        let token_address = to_checksum(&token_addr, None);
+        let raw_amount = raw_balance.to_string();
+        let price_id = chain.price_id(&token_address);
+        let value = amounts::usd_value(&raw_amount, decimals, price.price_usd.unwrap_or_default());
+
+        // Get metadata
+        let (name, logo_uri) = self
+            .metadata_service
+            .get_evm_metadata(&chain.name, &helpers::evm_address_key(&token_addr), price_id)
+            .await
+            .unwrap_or((None, None));
+
+        Token {
+            symbol,
+            mint_or_address: token_address,
+            amount: amounts::to_f64(&raw_amount, decimals),
+            raw_amount,
+            decimals,
+            price_usd: price.price_usd,
+            value_usd: value,
+            price_confidence: price.confidence,
+            name,
+            logo_uri,
+            price_change_24h: price.price_change_24h,
+            token_program: None,
+            extensions: None,
+            ..Default::default()
+        }
     }
 
-    pub async fn fetch_transactions(&self, address: &str, _limit: usize) -> Result<Vec<crate::types::transaction::Transaction>> {
-        let _addr: EthAddress = address.parse()?;
-        let _provider = Provider::<Http>::try_from(self.rpc_url.as_str())?;
-        
-        // Get recent transactions
-        // Note: This is a simplified implementation
-        // In production, you'd use a service like Etherscan API or The Graph
-        let transactions = Vec::new();
-        
-        // For now, return empty list - would need Etherscan API or similar for transaction history
-        // Ethereum RPC doesn't provide easy access to transaction history
-        
-        Ok(transactions)
+    // Decimals and symbols are immutable, so they are cached permanently and only read on-chain once
+    async fn get_token_details(&self, chain: &EvmChainConfig, provider: &Arc<Provider<Http>>, tokens: &[EthAddress]) -> Result<HashMap<EthAddress, (u8, String)>> {
+        let keys: Vec<String> = tokens.iter().map(helpers::evm_address_key).collect();
+        let cached = self.metadata_service.get_evm_token_details(&chain.name, &keys).await.unwrap_or_default();
+
+        let mut details = HashMap::new();
+        let mut missing = Vec::new();
+        for (token, key) in tokens.iter().zip(&keys) {
+            match cached.get(key) {
+                Some((decimals, Some(symbol))) => {
+                    details.insert(*token, (*decimals, symbol.clone()));
+                }
+                _ => missing.push(*token),
+            }
+        }
+        if missing.is_empty() {
+            return Ok(details);
+        }
+
+        let mut calls = Vec::with_capacity(missing.len() * 2);
+        for token in &missing {
+            calls.push(Call3 { target: *token, allow_failure: true, call_data: DecimalsCall.encode().into() });
+            calls.push(Call3 { target: *token, allow_failure: true, call_data: SymbolCall.encode().into() });
+        }
+        let results = aggregate(chain, provider, calls).await?;
+
+        for (token, pair) in missing.iter().zip(results.chunks(2)) {
+            // Contracts that don't answer decimals() are not treated as ERC-20 tokens
+            let Some(decimals) = pair[0].as_ref().and_then(|data| DecimalsReturn::decode(data).ok()).map(|r| r.0) else {
+                continue;
+            };
+            let symbol = pair[1]
+                .as_ref()
+                .and_then(decode_symbol)
+                .or_else(|| seed_symbol(chain, token))
+                .unwrap_or_else(|| to_checksum(token, None)[..8].to_string());
+
+            if let Err(e) = self.metadata_service.set_evm_token_details(&chain.name, &helpers::evm_address_key(token), decimals, Some(&symbol)).await {
+                tracing::warn!("Failed to cache token details for {:?}: {}", token, e);
+            }
+            details.insert(*token, (decimals, symbol));
+        }
+
+        Ok(details)
+    }
+
+    // Records every token contract that has sent the wallet a Transfer, scanning forward from the last run
+    async fn discover_tokens(&self, chain_id: u64, addr: EthAddress) -> Result<()> {
+        let (chain, provider) = self.provider(chain_id)?;
+        let address_key = &helpers::evm_address_key(&addr);
+        let latest = provider.get_block_number().await?.as_u64();
+        let from_block = match self.history.get_discovery_block(address_key, &chain.name).await? {
+            Some(block) => block + 1,
+            None => chain.first_discovery_block(latest),
+        };
+
+        let mut scanner = LogRangeScanner::new(from_block, latest, self.config.ethereum_discovery_chunk_size);
+        while let Some((from_block, to_block)) = scanner.window() {
+            let filter = Filter::new()
+                .from_block(from_block)
+                .to_block(to_block)
+                .event(TRANSFER_EVENT)
+                .topic2(H256::from(addr));
+
+            match provider.get_logs(&filter).await {
+                Ok(logs) => {
+                    let mut found: Vec<(String, u64)> = Vec::new();
+                    for log in logs {
+                        // Skip ERC-721 transfers, which index the token id as a fourth topic
+                        if log.topics.len() != 3 {
+                            continue;
+                        }
+                        let token_address = helpers::evm_address_key(&log.address);
+                        if !found.iter().any(|(t, _)| *t == token_address) {
+                            let block = log.block_number.map(|b| b.as_u64()).unwrap_or(from_block);
+                            found.push((token_address, block));
+                        }
+                    }
+                    self.history.store_discovered_tokens(address_key, &chain.name, &found, to_block).await?;
+                    scanner.advance();
+                }
+                Err(e) => {
+                    if !scanner.shrink() {
+                        return Err(e.into());
+                    }
+                    tracing::debug!("Log query {}-{} failed, shrinking range: {}", from_block, to_block, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn nft_valuation_enabled(&self, chain: &EvmChainConfig) -> bool {
+        chain.opensea_chain.is_some() && self.price_service.nft_floor_prices_enabled()
+    }
+
+    pub async fn fetch_nfts(&self, chain_id: u64, address: &str) -> Result<NftPortfolio> {
+        let addr: EthAddress = address.parse()?;
+        let address_key = helpers::evm_address_key(&addr);
+        let (chain, provider) = self.provider(chain_id)?;
+
+        // Discover NFTs the wallet has received; fall back to what is already stored on failure
+        if let Err(e) = self.discover_nfts(chain, &provider, addr, &address_key).await {
+            tracing::warn!("Failed to discover {} NFTs for {}: {}", chain.name, address_key, e);
+        }
+        let candidates = self.history.get_discovered_nfts(&address_key, &chain.name).await?;
+
+        let provider = Arc::new(provider);
+        let held = self.verify_nft_ownership(chain, &provider, addr, &candidates).await?;
+        let metadata = self.get_nft_metadata(chain, &provider, &held).await?;
+
+        let mut contracts: Vec<EthAddress> = Vec::new();
+        for nft in &held {
+            if !contracts.contains(&nft.contract) {
+                contracts.push(nft.contract);
+            }
+        }
+        let collection_names = self.get_collection_names(chain, &provider, &contracts).await?;
+
+        // One collection per contract, in the order the wallet first received them
+        let mut collections: Vec<NftCollection> = contracts
+            .iter()
+            .map(|contract| NftCollection {
+                address: to_checksum(contract, None),
+                name: collection_names.get(contract).cloned(),
+                floor_price_usd: None,
+                value_usd: None,
+                nfts: Vec::new(),
+                ..Default::default()
+            })
+            .collect();
+        for nft in &held {
+            let Some(index) = contracts.iter().position(|c| *c == nft.contract) else { continue };
+            let (token_uri, data) = metadata.get(&nft_id(&nft.contract, &nft.token_id)).cloned().unwrap_or((None, None));
+            collections[index].nfts.push(build_nft(nft, token_uri, data.as_ref()));
+        }
+
+        let valued = self.nft_valuation_enabled(chain);
+        if valued {
+            let concurrency = self.config.token_lookup_concurrency.max(1);
+            let addresses: Vec<String> = collections.iter().map(|c| c.address.clone()).collect();
+            let floors: Vec<Option<Decimal>> = stream::iter(addresses)
+                .map(|contract| async move {
+                    self.price_service.get_nft_floor_price(chain, &contract).await.unwrap_or_else(|e| {
+                        tracing::warn!("Failed to get {} floor price for {}: {}", chain.name, contract, e);
+                        None
+                    })
+                })
+                .buffered(concurrency)
+                .collect()
+                .await;
+
+            for ((collection, contract), floor) in collections.iter_mut().zip(&contracts).zip(floors) {
+                let items: u64 = held
+                    .iter()
+                    .filter(|nft| nft.contract == *contract)
+                    .map(|nft| nft.amount.min(U256::from(u64::MAX)).as_u64())
+                    .fold(0, u64::saturating_add);
+                collection.floor_price_usd = floor;
+                collection.value_usd = floor.map(|price| amounts::convert(price, Decimal::from(items)));
+            }
+        }
+
+        let total_value_usd = valued.then(|| amounts::sum(collections.iter().filter_map(|c| c.value_usd)));
+        if let Some(value) = total_value_usd {
+            if let Err(e) = self.history.set_nft_valuation(&address_key, &chain.name, value).await {
+                tracing::warn!("Failed to store {} NFT valuation for {}: {}", chain.name, address_key, e);
+            }
+        }
+
+        Ok(NftPortfolio {
+            chain: chain.name.clone(),
+            chain_id: Some(chain.chain_id),
+            address: to_checksum(&addr, None),
+            total_nfts_count: held.len(),
+            collections,
+            total_value_usd,
+            last_updated: chrono::Utc::now().to_rfc3339(),
+            ..Default::default()
+        })
+    }
+
+    // Records every ERC-721/ERC-1155 token sent to the wallet, scanning forward from the last run
+    async fn discover_nfts(&self, chain: &EvmChainConfig, provider: &Provider<Http>, addr: EthAddress, address_key: &str) -> Result<()> {
+        let latest = provider.get_block_number().await?.as_u64();
+        let from_block = match self.history.get_nft_discovery_block(address_key, &chain.name).await? {
+            Some(block) => block + 1,
+            None => chain.first_discovery_block(latest),
+        };
+
+        let mut scanner = LogRangeScanner::new(from_block, latest, self.config.ethereum_discovery_chunk_size);
+        while let Some((from_block, to_block)) = scanner.window() {
+            let range = Filter::new().from_block(from_block).to_block(to_block);
+
+            // ERC-721 indexes the recipient as topic2; ERC-1155 indexes the operator first, so it is topic3
+            let erc721 = range.clone().event(TRANSFER_EVENT).topic2(H256::from(addr));
+            let erc1155 = range.events([TRANSFER_SINGLE_EVENT, TRANSFER_BATCH_EVENT]).topic3(H256::from(addr));
+
+            match futures::try_join!(provider.get_logs(&erc721), provider.get_logs(&erc1155)) {
+                Ok((erc721_logs, erc1155_logs)) => {
+                    let mut found: Vec<DiscoveredNft> = Vec::new();
+                    for log in erc721_logs.iter().chain(&erc1155_logs) {
+                        let block = log.block_number.map(|b| b.as_u64()).unwrap_or(from_block) as i64;
+                        let contract_address = helpers::evm_address_key(&log.address);
+                        for (token_id, standard) in nft_log_tokens(log) {
+                            let token_id = token_id.to_string();
+                            if !found.iter().any(|n| n.contract_address == contract_address && n.token_id == token_id) {
+                                found.push(DiscoveredNft {
+                                    contract_address: contract_address.clone(),
+                                    token_id,
+                                    standard: standard.to_string(),
+                                    first_seen_block: block,
+                                });
+                            }
+                        }
+                    }
+                    self.history.store_discovered_nfts(address_key, &chain.name, &found, to_block).await?;
+                    scanner.advance();
+                }
+                Err(e) => {
+                    if !scanner.shrink() {
+                        return Err(e.into());
+                    }
+                    tracing::debug!("NFT log query {}-{} failed, shrinking range: {}", from_block, to_block, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Keeps the discovered NFTs the wallet still holds
+    async fn verify_nft_ownership(
+        &self,
+        chain: &EvmChainConfig,
+        provider: &Arc<Provider<Http>>,
+        addr: EthAddress,
+        candidates: &[DiscoveredNft],
+    ) -> Result<Vec<HeldNft>> {
+        let parsed: Vec<(EthAddress, U256, String)> = candidates
+            .iter()
+            .filter_map(|nft| Some((nft.contract_address.parse().ok()?, U256::from_dec_str(&nft.token_id).ok()?, nft.standard.clone())))
+            .collect();
+
+        let calls: Vec<Call3> = parsed
+            .iter()
+            .map(|(contract, token_id, standard)| {
+                let call_data = if standard == "erc1155" {
+                    nft_abi::BalanceOfCall { account: addr, id: *token_id }.encode()
+                } else {
+                    nft_abi::OwnerOfCall { token_id: *token_id }.encode()
+                };
+                Call3 { target: *contract, allow_failure: true, call_data: call_data.into() }
+            })
+            .collect();
+        let results = aggregate(chain, provider, calls).await?;
+
+        let mut held = Vec::new();
+        for ((contract, token_id, standard), result) in parsed.into_iter().zip(results) {
+            // ownerOf reverts for burned tokens, which are simply no longer held
+            let Some(data) = result else { continue };
+            let amount = if standard == "erc1155" {
+                nft_abi::BalanceOfReturn::decode(data).map(|r| r.0).unwrap_or_default()
+            } else {
+                match nft_abi::OwnerOfReturn::decode(data) {
+                    Ok(owner) if owner.0 == addr => U256::one(),
+                    _ => U256::zero(),
+                }
+            };
+            if !amount.is_zero() {
+                held.push(HeldNft { contract, token_id, standard, amount });
+            }
+        }
+
+        Ok(held)
+    }
+
+    // Token URI and metadata by NFT id, reading token URIs on-chain only for NFTs missing from the cache
+    async fn get_nft_metadata(
+        &self,
+        chain: &EvmChainConfig,
+        provider: &Arc<Provider<Http>>,
+        held: &[HeldNft],
+    ) -> Result<HashMap<String, (Option<String>, Option<serde_json::Value>)>> {
+        let ids: Vec<String> = held.iter().map(|nft| nft_id(&nft.contract, &nft.token_id)).collect();
+        let mut metadata = self.metadata_service.get_cached_nft_metadata(&chain.name, &ids).await.unwrap_or_default();
+
+        let missing: Vec<(&String, &HeldNft)> = ids.iter().zip(held).filter(|(id, _)| !metadata.contains_key(*id)).collect();
+        if missing.is_empty() {
+            return Ok(metadata);
+        }
+
+        let calls: Vec<Call3> = missing
+            .iter()
+            .map(|(_, nft)| {
+                let call_data = if nft.standard == "erc1155" {
+                    nft_abi::UriCall { id: nft.token_id }.encode()
+                } else {
+                    nft_abi::TokenURICall { token_id: nft.token_id }.encode()
+                };
+                Call3 { target: nft.contract, allow_failure: true, call_data: call_data.into() }
+            })
+            .collect();
+        let results = aggregate(chain, provider, calls).await?;
+
+        let mut uris: Vec<(String, String)> = Vec::new();
+        for ((id, nft), result) in missing.into_iter().zip(results) {
+            // tokenURI and uri both return a single string, so either return type decodes them
+            let Some(uri) = result.and_then(|data| nft_abi::UriReturn::decode(data).ok()).map(|r| r.0) else {
+                continue;
+            };
+            let uri = uri.trim();
+            if uri.is_empty() {
+                continue;
+            }
+            // ERC-1155 URIs may contain an {id} placeholder for the zero-padded hex token id
+            let uri = if nft.standard == "erc1155" {
+                let mut bytes = [0u8; 32];
+                nft.token_id.to_big_endian(&mut bytes);
+                uri.replace("{id}", &hex::encode(bytes))
+            } else {
+                uri.to_string()
+            };
+            uris.push((id.clone(), uri));
+        }
+
+        let fetched = self
+            .metadata_service
+            .fetch_nft_metadata_batch(&chain.name, uris, self.config.token_lookup_concurrency)
+            .await;
+        for (id, uri, data) in fetched {
+            metadata.insert(id, (Some(uri), data));
+        }
+
+        Ok(metadata)
+    }
+
+    async fn get_collection_names(&self, chain: &EvmChainConfig, provider: &Arc<Provider<Http>>, contracts: &[EthAddress]) -> Result<HashMap<EthAddress, String>> {
+        let calls: Vec<Call3> = contracts
+            .iter()
+            .map(|contract| Call3 { target: *contract, allow_failure: true, call_data: nft_abi::NameCall.encode().into() })
+            .collect();
+        let results = aggregate(chain, provider, calls).await?;
+
+        // name() is optional for both standards
+        Ok(contracts
+            .iter()
+            .zip(results)
+            .filter_map(|(contract, result)| {
+                let name = nft_abi::NameReturn::decode(result?).ok()?.0;
+                Some((*contract, name)).filter(|(_, n)| !n.is_empty())
+            })
+            .collect())
+    }
+
+    pub async fn fetch_transactions(
+        &self,
+        chain_id: u64,
+        address: &str,
+        limit: usize,
+        before: Option<EthereumCursor>,
+        until: Option<EthereumCursor>,
+    ) -> Result<TransactionPage> {
+        let addr: EthAddress = address.parse()?;
+        let address_key = helpers::evm_address_key(&addr);
+        let chain = self
+            .chain(chain_id)
+            .ok_or_else(|| anyhow::anyhow!("EVM chain {} is not configured", chain_id))?;
+
+        let state = self.history.get_index_state(&address_key, &chain.name).await?;
+        let transfers = self.history.get_transfers(&address_key, &chain.name, limit, before, until).await?;
+
+        // A short page that reaches past the oldest indexed block asks the worker to index further back
+        let unindexed_before = state
+            .map(|(first, _)| first)
+            .filter(|first| *first > chain.discovery_start_block)
+            .filter(|first| until.is_none_or(|u| (u.block as u64) < *first));
+        let backfill = transfers.len() < limit && unindexed_before.is_some();
+        self.spawn_history_worker(chain_id, addr, backfill);
+
+        // Until the older blocks are indexed the page continues from where it stopped, so a client
+        // following next_cursor reaches them once the worker has caught up
+        let next_cursor = match (transfers.last(), unindexed_before) {
+            (Some(last), _) if transfers.len() == limit || backfill => {
+                Some(EthereumCursor { block: last.block_number, log_index: last.log_index })
+            }
+            (None, Some(first)) => Some(before.unwrap_or(EthereumCursor { block: first as i64, log_index: i64::MIN })),
+            _ => None,
+        };
+
+        Ok(TransactionPage {
+            transactions: transfers.into_iter().map(|t| transfer_to_transaction(t, &address_key, &chain.name)).collect(),
+            next_cursor: next_cursor.map(|c| c.to_string()),
+        })
+    }
+
+    // Requests are served from stored history while a background worker brings it up to date.
+    // Without a free worker slot the run is skipped; the next request for the address retries it.
+    fn spawn_history_worker(&self, chain_id: u64, addr: EthAddress, backfill: bool) {
+        let Ok(permit) = self.history_workers.clone().try_acquire_owned() else {
+            return;
+        };
+        let client = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = client.index_history(chain_id, addr, backfill).await {
+                tracing::warn!("Failed to index chain {} history for {:?}: {}", chain_id, addr, e);
+            }
+            drop(permit);
+        });
+    }
+
+    // Scans from the last indexed block (or a lookback window on first use) to the chain head, then
+    // on backfill one more lookback window below the oldest indexed block. The advisory lock keeps
+    // concurrent requests and other instances from scanning the same address at once.
+    async fn index_history(&self, chain_id: u64, addr: EthAddress, backfill: bool) -> Result<()> {
+        let (chain, provider) = self.provider(chain_id)?;
+        let address_key = helpers::evm_address_key(&addr);
+        let Some(lock) = self.history.try_lock_history(&address_key, &chain.name).await? else {
+            return Ok(());
+        };
+
+        let latest = provider.get_block_number().await?.as_u64();
+        let native_floor = chain.native_history_floor(latest);
+        let lookback = self.config.ethereum_history_lookback_blocks.max(1);
+        let chunk_size = self.config.ethereum_log_chunk_size.max(1);
+        let (first, start) = match self.history.get_index_state(&address_key, &chain.name).await? {
+            Some((first, last)) => (first, last + 1),
+            None => {
+                let start = latest.saturating_sub(lookback).max(chain.discovery_start_block);
+                (start, start)
+            }
+        };
+
+        let mut scanner = LogRangeScanner::new(start, latest, chunk_size);
+        self.index_windows(chain, &provider, addr, native_floor, &mut scanner).await?;
+
+        // Newest window first, so the indexed range stays contiguous if a window fails
+        if backfill && first > chain.discovery_start_block {
+            let floor = first.saturating_sub(lookback).max(chain.discovery_start_block);
+            let mut scanner = LogRangeScanner::descending(floor, first - 1, chunk_size);
+            self.index_windows(chain, &provider, addr, native_floor, &mut scanner).await?;
+        }
+
+        lock.commit().await?;
+
+        Ok(())
+    }
+
+    // Stores each window's transfers as it is scanned, halving windows the provider rejects
+    async fn index_windows(
+        &self,
+        chain: &EvmChainConfig,
+        provider: &Provider<Http>,
+        addr: EthAddress,
+        native_floor: Option<u64>,
+        scanner: &mut LogRangeScanner,
+    ) -> Result<()> {
+        let address_key = helpers::evm_address_key(&addr);
+        while let Some((from_block, to_block)) = scanner.window() {
+            match self.scan_transfers(chain, provider, addr, from_block, to_block, native_floor).await {
+                Ok(transfers) => {
+                    self.history.store_transfers(&address_key, &chain.name, &transfers, from_block, to_block).await?;
+                    scanner.advance();
+                }
+                Err(e) => {
+                    if !scanner.shrink() {
+                        return Err(e);
+                    }
+                    tracing::debug!("History scan {}-{} failed, shrinking range: {}", from_block, to_block, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Native transfers are only scanned from `native_floor` up, and not at all when it is None
+    async fn scan_transfers(
+        &self,
+        chain: &EvmChainConfig,
+        provider: &Provider<Http>,
+        addr: EthAddress,
+        from_block: u64,
+        to_block: u64,
+        native_floor: Option<u64>,
+    ) -> Result<Vec<EthereumTransfer>> {
+        // Logs come first: a window too large for the provider fails before any block is fetched
+        let logs = self.token_transfer_logs(provider, addr, from_block, to_block).await?;
+
+        // Blocks the native scan fetched give token transfers in them their timestamps
+        let mut timestamps: HashMap<u64, i64> = HashMap::new();
+        let native_from = native_floor.map(|floor| floor.max(from_block)).filter(|from| *from <= to_block);
+        let mut transfers = match native_from {
+            Some(native_from) => self.scan_native_transfers(chain, provider, addr, native_from, to_block, &mut timestamps).await?,
+            None => Vec::new(),
+        };
+        transfers.extend(self.decode_token_transfers(chain, provider, logs, &mut timestamps).await?);
+        Ok(transfers)
+    }
+
+    async fn token_transfer_logs(&self, provider: &Provider<Http>, addr: EthAddress, from_block: u64, to_block: u64) -> Result<Vec<Log>> {
+        // Indexed topics are left-padded addresses: topic1 is the sender, topic2 the recipient
+        let address_topic = H256::from(addr);
+        let filter = Filter::new().from_block(from_block).to_block(to_block).event(TRANSFER_EVENT);
+        let mut logs = provider.get_logs(&filter.clone().topic1(address_topic)).await?;
+        for log in provider.get_logs(&filter.topic2(address_topic)).await? {
+            // Self-transfers match both queries
+            if !logs.iter().any(|l| l.transaction_hash == log.transaction_hash && l.log_index == log.log_index) {
+                logs.push(log);
+            }
+        }
+        Ok(logs)
+    }
+
+    async fn decode_token_transfers(
+        &self,
+        chain: &EvmChainConfig,
+        provider: &Provider<Http>,
+        logs: Vec<Log>,
+        timestamps: &mut HashMap<u64, i64>,
+    ) -> Result<Vec<EthereumTransfer>> {
+        // Resolve decimals and symbols for every token in the range at once
+        let mut token_addresses: Vec<EthAddress> = Vec::new();
+        for log in &logs {
+            if !token_addresses.contains(&log.address) {
+                token_addresses.push(log.address);
+            }
+        }
+        let token_details = if token_addresses.is_empty() {
+            HashMap::new()
+        } else {
+            self.get_token_details(chain, &Arc::new(provider.clone()), &token_addresses).await?
+        };
+
+        let mut transfers = Vec::new();
+        for log in logs {
+            // ERC-721 shares the Transfer signature but indexes the token id as a fourth topic
+            if log.topics.len() != 3 || log.data.len() != 32 || log.removed == Some(true) {
+                continue;
+            }
+            let (Some(block_number), Some(log_index), Some(tx_hash)) = (log.block_number, log.log_index, log.transaction_hash) else {
+                continue;
+            };
+
+            // Logs from contracts get_token_details rejected are not ERC-20 transfers
+            let Some((decimals, symbol)) = token_details.get(&log.address).cloned() else {
+                continue;
+            };
+
+            transfers.push(EthereumTransfer {
+                tx_hash: format!("{:?}", tx_hash),
+                block_number: block_number.as_u64() as i64,
+                log_index: log_index.as_u64() as i64,
+                timestamp: 0,
+                from_address: helpers::evm_address_key(&EthAddress::from(log.topics[1])),
+                to_address: helpers::evm_address_key(&EthAddress::from(log.topics[2])),
+                token_address: Some(helpers::evm_address_key(&log.address)),
+                token_symbol: symbol,
+                decimals: decimals as i16,
+                raw_amount: U256::from_big_endian(&log.data).to_string(),
+                // Logs are only emitted by successful transactions
+                status: "success".to_string(),
+                fee_wei: None,
+            });
+        }
+
+        self.block_timestamps(provider, transfers.iter().map(|t| t.block_number as u64), timestamps).await?;
+        for transfer in &mut transfers {
+            transfer.timestamp = timestamps.get(&(transfer.block_number as u64)).copied().unwrap_or(0);
+        }
+
+        Ok(transfers)
+    }
+
+    async fn scan_native_transfers(
+        &self,
+        chain: &EvmChainConfig,
+        provider: &Provider<Http>,
+        addr: EthAddress,
+        from_block: u64,
+        to_block: u64,
+        timestamps: &mut HashMap<u64, i64>,
+    ) -> Result<Vec<EthereumTransfer>> {
+        // Native transfers emit no logs, so every block body in the range is inspected
+        let concurrency = self.config.token_lookup_concurrency.max(1);
+        let blocks: Vec<Result<Option<Block<EthTransaction>>, _>> = stream::iter(from_block..=to_block)
+            .map(|number| provider.get_block_with_txs(number))
+            .buffered(concurrency)
+            .collect()
+            .await;
+
+        let mut matches: Vec<(u64, i64, EthTransaction)> = Vec::new();
+        for block in blocks {
+            let Some(block) = block? else { continue };
+            let Some(block_number) = block.number else { continue };
+            let timestamp = block.timestamp.as_u64() as i64;
+            timestamps.insert(block_number.as_u64(), timestamp);
+
+            for tx in block.transactions {
+                if !tx.value.is_zero() && (tx.from == addr || tx.to == Some(addr)) {
+                    matches.push((block_number.as_u64(), timestamp, tx));
+                }
+            }
+        }
+
+        // Receipts give the status and fee: one eth_getBlockReceipts call per block with a match
+        let mut match_blocks: Vec<u64> = matches.iter().map(|(number, _, _)| *number).collect();
+        match_blocks.dedup();
+        let block_receipts: Vec<Result<Vec<TransactionReceipt>>> = stream::iter(match_blocks)
+            .map(|number| {
+                let hashes: Vec<H256> = matches.iter().filter(|(n, _, _)| *n == number).map(|(_, _, tx)| tx.hash).collect();
+                self.block_receipts(provider, number, hashes)
+            })
+            .buffered(concurrency)
+            .collect()
+            .await;
+        let mut receipts: HashMap<H256, TransactionReceipt> = HashMap::new();
+        for block in block_receipts {
+            receipts.extend(block?.into_iter().map(|r| (r.transaction_hash, r)));
+        }
+
+        let mut transfers = Vec::new();
+        for (block_number, timestamp, tx) in matches {
+            let is_sender = tx.from == addr;
+            let receipt = receipts.get(&tx.hash);
+            let status = match receipt.and_then(|r| r.status) {
+                Some(status) if status.is_zero() => "failed",
+                _ => "success",
+            };
+            // The fee is only paid by the sender
+            let fee_wei = receipt
+                .filter(|_| is_sender)
+                .and_then(|r| Some(r.gas_used? * r.effective_gas_price?))
+                .map(|fee| fee.to_string());
+            let transaction_index = tx.transaction_index.map(|i| i.as_u64()).unwrap_or(0) as i64;
+
+            transfers.push(EthereumTransfer {
+                tx_hash: format!("{:?}", tx.hash),
+                block_number: block_number as i64,
+                log_index: -(transaction_index + 1),
+                timestamp,
+                from_address: helpers::evm_address_key(&tx.from),
+                to_address: tx.to.map(|to| helpers::evm_address_key(&to)).unwrap_or_default(),
+                token_address: None,
+                token_symbol: chain.native_symbol.clone(),
+                decimals: NATIVE_DECIMALS as i16,
+                raw_amount: tx.value.to_string(),
+                status: status.to_string(),
+                fee_wei,
+            });
+        }
+
+        Ok(transfers)
+    }
+
+    // Nodes without eth_getBlockReceipts fall back to one lookup per transaction
+    async fn block_receipts(&self, provider: &Provider<Http>, block_number: u64, hashes: Vec<H256>) -> Result<Vec<TransactionReceipt>> {
+        match provider.get_block_receipts(block_number).await {
+            Ok(receipts) => return Ok(receipts),
+            Err(e) => tracing::debug!("eth_getBlockReceipts failed for block {}, fetching receipts individually: {}", block_number, e),
+        }
+
+        let receipts: Vec<Result<Option<TransactionReceipt>, _>> = stream::iter(hashes)
+            .map(|hash| provider.get_transaction_receipt(hash))
+            .buffered(self.config.token_lookup_concurrency.max(1))
+            .collect()
+            .await;
+        let mut found = Vec::new();
+        for receipt in receipts {
+            found.extend(receipt?);
+        }
+        Ok(found)
+    }
+
+    // Adds the timestamps of blocks not already in `timestamps`, fetched concurrently
+    async fn block_timestamps(&self, provider: &Provider<Http>, blocks: impl Iterator<Item = u64>, timestamps: &mut HashMap<u64, i64>) -> Result<()> {
+        let mut missing: Vec<u64> = blocks.filter(|number| !timestamps.contains_key(number)).collect();
+        missing.sort_unstable();
+        missing.dedup();
+
+        let fetched: Vec<Result<(u64, i64), ProviderError>> = stream::iter(missing)
+            .map(|number| async move {
+                let block = provider.get_block(number).await?;
+                Ok((number, block.map(|block| block.timestamp.as_u64() as i64).unwrap_or(0)))
+            })
+            .buffered(self.config.token_lookup_concurrency.max(1))
+            .collect()
+            .await;
+        for block in fetched {
+            let (number, timestamp) = block?;
+            timestamps.insert(number, timestamp);
+        }
+
+        Ok(())
+    }
+}
+
+// Exposes one configured chain of an EthereumClient to the chain registry
+pub struct EvmChainClient {
+    client: EthereumClient,
+    chain_id: u64,
+    name: String,
+}
+
+impl EvmChainClient {
+    pub fn new(client: EthereumClient, chain: &EvmChainConfig) -> Self {
+        Self {
+            client,
+            chain_id: chain.chain_id,
+            name: chain.name.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl ChainClient for EvmChainClient {
+    fn chain(&self) -> &str {
+        &self.name
+    }
+
+    fn evm_chain_id(&self) -> Option<u64> {
+        Some(self.chain_id)
+    }
+
+    fn is_valid_address(&self, address: &str) -> bool {
+        helpers::is_valid_evm_address(address)
+    }
+
+    fn normalize_address(&self, address: &str) -> String {
+        helpers::normalize_evm_address(address)
+    }
+
+    fn is_valid_cursor(&self, cursor: &str) -> bool {
+        cursor.parse::<EthereumCursor>().is_ok()
+    }
+
+    fn naming_system(&self) -> Option<&'static str> {
+        Some("ens")
+    }
+
+    fn is_name(&self, input: &str) -> bool {
+        input.contains('.')
+    }
+
+    async fn resolve_name(&self, name: &str) -> Result<Option<String>> {
+        self.client.resolve_ens(name).await
+    }
+
+    async fn lookup_name(&self, address: &str) -> Result<Option<String>> {
+        self.client.lookup_ens(address).await
+    }
+
+    async fn fetch_portfolio(&self, address: &str) -> Result<PortfolioResponse> {
+        self.client.fetch_portfolio(self.chain_id, address).await
+    }
+
+    async fn fetch_transactions(&self, address: &str, limit: usize, before: Option<&str>, until: Option<&str>) -> Result<TransactionPage> {
+        let before = before.map(|c| c.parse::<EthereumCursor>()).transpose().map_err(anyhow::Error::msg)?;
+        let until = until.map(|c| c.parse::<EthereumCursor>()).transpose().map_err(anyhow::Error::msg)?;
+        self.client.fetch_transactions(self.chain_id, address, limit, before, until).await
+    }
+
+    fn supports_nfts(&self) -> bool {
+        true
+    }
+
+    async fn fetch_nfts(&self, address: &str) -> Result<NftPortfolio> {
+        self.client.fetch_nfts(self.chain_id, address).await
+    }
+}
+
+// Runs calls through Multicall3 aggregate3, returning the return data of each call that succeeded
+pub async fn aggregate(chain: &EvmChainConfig, provider: &Arc<Provider<Http>>, calls: Vec<Call3>) -> Result<Vec<Option<Bytes>>> {
+    let multicall_address: EthAddress = chain.multicall_address.parse()?;
+    let multicall = Multicall3::new(multicall_address, provider.clone());
+
+    let mut results = Vec::with_capacity(calls.len());
+    for batch in calls.chunks(MULTICALL_BATCH_SIZE) {
+        match multicall.aggregate_3(batch.to_vec()).call().await {
+            Ok(batch_results) => {
+                results.extend(batch_results.into_iter().map(|r| if r.success { Some(r.return_data) } else { None }));
+            }
+            // Nodes without Multicall3 deployed (e.g. a bare local devnet) fall back to individual calls
+            Err(e) => {
+                tracing::debug!("aggregate3 failed, falling back to individual calls: {}", e);
+                for call in batch {
+                    let tx: TypedTransaction = TransactionRequest::new().to(call.target).data(call.call_data.clone()).into();
+                    results.push(provider.call(&tx, None).await.ok());
+                }
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+// Walks a block range in windows for log queries. Providers cap log ranges and result counts,
+// so a window whose query fails is retried at half the size. Each successful window doubles the
+// size again, up to the configured one, so one dense stretch doesn't slow the rest of the scan.
+struct LogRangeScanner {
+    remaining: Option<(u64, u64)>,
+    chunk_size: u64,
+    max_chunk_size: u64,
+    descending: bool,
+}
+
+impl LogRangeScanner {
+    fn new(from_block: u64, latest: u64, chunk_size: u64) -> Self {
+        Self::with_order(from_block, latest, chunk_size, false)
+    }
+
+    // Covers the range newest window first
+    fn descending(from_block: u64, to_block: u64, chunk_size: u64) -> Self {
+        Self::with_order(from_block, to_block, chunk_size, true)
+    }
+
+    fn with_order(from_block: u64, to_block: u64, chunk_size: u64, descending: bool) -> Self {
+        Self {
+            remaining: (from_block <= to_block).then_some((from_block, to_block)),
+            chunk_size: chunk_size.max(1),
+            max_chunk_size: chunk_size.max(1),
+            descending,
+        }
+    }
+
+    // Next (from, to) window to query, or None once the range is covered
+    fn window(&self) -> Option<(u64, u64)> {
+        let (from_block, to_block) = self.remaining?;
+        let span = self.chunk_size - 1;
+        Some(if self.descending {
+            (to_block.saturating_sub(span).max(from_block), to_block)
+        } else {
+            (from_block, from_block.saturating_add(span).min(to_block))
+        })
+    }
+
+    // Moves past the current window once its logs are stored
+    fn advance(&mut self) {
+        if let (Some((from_block, to_block)), Some((window_from, window_to))) = (self.remaining, self.window()) {
+            self.remaining = if self.descending {
+                (window_from > from_block).then(|| (from_block, window_from - 1))
+            } else {
+                (window_to < to_block).then(|| (window_to + 1, to_block))
+            };
+        }
+        self.chunk_size = self.chunk_size.saturating_mul(2).min(self.max_chunk_size);
+    }
+
+    // Halves the window after a failed query; false when it is already a single block
+    fn shrink(&mut self) -> bool {
+        if self.chunk_size == 1 {
+            return false;
+        }
+        self.chunk_size /= 2;
+        true
+    }
+}
+
+fn transfer_to_transaction(transfer: EthereumTransfer, address: &str, chain: &str) -> Transaction {
+    let is_sender = transfer.from_address == address;
+    let is_receiver = transfer.to_address == address;
+    let (transaction_type, counterparty) = match (is_sender, is_receiver) {
+        (true, true) => ("self", None),
+        (true, false) => ("send", Some(helpers::checksum_evm_address(&transfer.to_address))),
+        _ => ("receive", Some(helpers::checksum_evm_address(&transfer.from_address))),
+    };
+
+    Transaction {
+        hash: transfer.tx_hash,
+        timestamp: transfer.timestamp,
+        transaction_type: transaction_type.to_string(),
+        amount: amounts::to_f64(&transfer.raw_amount, transfer.decimals as u8),
+        raw_amount: transfer.raw_amount,
+        decimals: transfer.decimals as u8,
+        token_symbol: transfer.token_symbol,
+        chain: chain.to_string(),
+        status: transfer.status,
+        from: helpers::checksum_evm_address(&transfer.from_address),
+        to: helpers::checksum_evm_address(&transfer.to_address),
+        token_mint: transfer.token_address.as_deref().map(helpers::checksum_evm_address),
+        counterparty,
+        fee: transfer.fee_wei.map(|fee| amounts::to_f64(&fee, NATIVE_DECIMALS)),
+    }
+}
+
+// symbol() returns a string for most tokens but bytes32 for a few early ones (e.g. MKR)
+fn decode_symbol(data: &Bytes) -> Option<String> {
+    if let Ok(symbol) = SymbolReturn::decode(data) {
+        return Some(symbol.0).filter(|s| !s.is_empty());
+    }
+    if data.len() == 32 {
+        let symbol = String::from_utf8(data.iter().copied().take_while(|b| *b != 0).collect()).ok()?;
+        return Some(symbol).filter(|s| !s.is_empty());
     }
+    None
 }
 
+fn seed_symbol(chain: &EvmChainConfig, token: &EthAddress) -> Option<String> {
+    chain
+        .tokens
+        .iter()
+        .find(|t| t.address.parse::<EthAddress>().ok().as_ref() == Some(token))
+        .map(|t| t.symbol.clone())
+}
+
+// Key for cached NFT metadata: "<contract>/<token id>"
+fn nft_id(contract: &EthAddress, token_id: &U256) -> String {
+    format!("{:?}/{}", contract, token_id)
+}
+
+// Token ids moved by an ERC-721 Transfer or an ERC-1155 TransferSingle/TransferBatch log
+fn nft_log_tokens(log: &Log) -> Vec<(U256, &'static str)> {
+    let Some(topic0) = log.topics.first().copied() else {
+        return Vec::new();
+    };
+    if log.removed == Some(true) {
+        return Vec::new();
+    }
+
+    if topic0 == H256::from(keccak256(TRANSFER_EVENT)) {
+        // ERC-20 transfers share the signature but only ERC-721 indexes the token id as a fourth topic
+        return match log.topics.as_slice() {
+            [_, _, _, token_id] => vec![(U256::from_big_endian(token_id.as_bytes()), "erc721")],
+            _ => Vec::new(),
+        };
+    }
+    if topic0 == H256::from(keccak256(TRANSFER_SINGLE_EVENT)) {
+        // Data is (id, value)
+        return match log.data.get(..32) {
+            Some(token_id) => vec![(U256::from_big_endian(token_id), "erc1155")],
+            None => Vec::new(),
+        };
+    }
+    if topic0 == H256::from(keccak256(TRANSFER_BATCH_EVENT)) {
+        // Data is (ids[], values[])
+        let id_list = ParamType::Array(Box::new(ParamType::Uint(256)));
+        let Ok(decoded) = ethers::abi::decode(&[id_list.clone(), id_list], &log.data) else {
+            return Vec::new();
+        };
+        let Some(AbiToken::Array(ids)) = decoded.into_iter().next() else {
+            return Vec::new();
+        };
+        return ids.into_iter().filter_map(|id| id.into_uint()).map(|id| (id, "erc1155")).collect();
+    }
+
+    Vec::new()
+}
+
+fn build_nft(nft: &HeldNft, token_uri: Option<String>, metadata: Option<&serde_json::Value>) -> Nft {
+    let image = metadata_service::nft_field(metadata, "image").or_else(|| metadata_service::nft_field(metadata, "image_url"));
+
+    Nft {
+        token_id: nft.token_id.to_string(),
+        standard: nft.standard.clone(),
+        amount: nft.amount.to_string(),
+        name: metadata_service::nft_field(metadata, "name"),
+        description: metadata_service::nft_field(metadata, "description"),
+        image: image.map(|image| metadata_service::gateway_url(&image)),
+        token_uri: metadata_service::display_token_uri(token_uri),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::services::cache::CacheService;
+    use crate::services::price_provider::CoinGeckoApi;
+    use crate::services::rpc_stub;
+    use crate::services::token_registry::TokenRegistry;
+    use ethers::types::U64;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const WALLET: &str = "0x1111111111111111111111111111111111111111";
+    const OTHER: &str = "0x2222222222222222222222222222222222222222";
+    const TOKEN: &str = "0x3333333333333333333333333333333333333333";
+    const MULTICALL: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+    fn address(s: &str) -> EthAddress {
+        s.parse().unwrap()
+    }
+
+    fn hash(n: u64) -> H256 {
+        H256::from_low_u64_be(n)
+    }
+
+    // Services are backed by a database that never answers, so scans can only read the stub node
+    fn client(rpc_url: String) -> EthereumClient {
+        let chain = EvmChainConfig {
+            chain_id: 1,
+            name: "ethereum".to_string(),
+            rpc_url,
+            native_symbol: "ETH".to_string(),
+            native_price_id: None,
+            tokens: Vec::new(),
+            discovery_start_block: 0,
+            discovery_lookback_blocks: None,
+            native_history_blocks: None,
+            multicall_address: MULTICALL.to_string(),
+            opensea_chain: None,
+            dex: None,
+        };
+        let cache = CacheService::new(database::unreachable_pool());
+        let config = Config { evm_chains: vec![chain], ..Config::for_tests() };
+        let token_registry = TokenRegistry::new(cache.clone());
+        let price_service = PriceService::new(cache.clone(), token_registry.clone(), config.clone());
+        let metadata_service = MetadataService::new(cache, token_registry, CoinGeckoApi::new(&config));
+        EthereumClient::new(price_service, metadata_service, HistoryStore::new(database::unreachable_pool()), config)
+    }
+
+    fn block_json(number: u64, transactions: Value) -> Value {
+        json!({
+            "hash": hash(1_000 + number),
+            "number": U64::from(number),
+            "timestamp": U256::from(1_700_000_000 + number * 12),
+            "logsBloom": format!("0x{}", "00".repeat(256)),
+            "transactions": transactions,
+        })
+    }
+
+    fn tx_json(n: u64, block: u64, index: u64, from: &str, to: &str, value: u64) -> Value {
+        json!({
+            "hash": hash(n),
+            "nonce": "0x0",
+            "blockHash": hash(1_000 + block),
+            "blockNumber": U64::from(block),
+            "transactionIndex": U64::from(index),
+            "from": from,
+            "to": to,
+            "value": U256::from(value),
+            "gasPrice": U256::from(10),
+            "gas": U256::from(21_000),
+            "input": "0x",
+            "v": "0x1",
+            "r": "0x1",
+            "s": "0x1",
+        })
+    }
+
+    fn receipt_json(n: u64, block: u64, index: u64, status: u64) -> Value {
+        json!({
+            "transactionHash": hash(n),
+            "transactionIndex": U64::from(index),
+            "blockHash": hash(1_000 + block),
+            "blockNumber": U64::from(block),
+            "from": WALLET,
+            "to": OTHER,
+            "cumulativeGasUsed": U256::from(21_000),
+            "gasUsed": U256::from(21_000),
+            "effectiveGasPrice": U256::from(10),
+            "logs": [],
+            "status": U64::from(status),
+            "logsBloom": format!("0x{}", "00".repeat(256)),
+        })
+    }
+
+    // Block 101 holds a payment out of the wallet, a failed payment into it and one between other
+    // accounts; blocks 100 and 102 hold nothing for the wallet
+    fn native_answer(method: &str, params: &Value, block_receipts: bool, receipt_calls: &AtomicUsize) -> Option<Value> {
+        let block = u64::from_str_radix(params[0].as_str()?.trim_start_matches("0x"), 16).ok();
+        match method {
+            "eth_getBlockByNumber" => {
+                let block = block?;
+                let transactions = match block {
+                    101 => json!([
+                        tx_json(1, 101, 0, WALLET, OTHER, 1_000),
+                        tx_json(2, 101, 1, OTHER, WALLET, 500),
+                        tx_json(3, 101, 2, OTHER, TOKEN, 700),
+                    ]),
+                    _ => json!([tx_json(10 + block, block, 0, OTHER, TOKEN, 1)]),
+                };
+                // Without full transactions the block lists their hashes
+                if params[1] == json!(false) {
+                    let hashes: Vec<Value> = transactions.as_array()?.iter().map(|tx| tx["hash"].clone()).collect();
+                    return Some(block_json(block, json!(hashes)));
+                }
+                Some(block_json(block, transactions))
+            }
+            "eth_getBlockReceipts" if block_receipts => {
+                receipt_calls.fetch_add(1, Ordering::SeqCst);
+                match block? {
+                    101 => Some(json!([receipt_json(1, 101, 0, 1), receipt_json(2, 101, 1, 0), receipt_json(3, 101, 2, 1)])),
+                    other => Some(json!([receipt_json(10 + other, other, 0, 1)])),
+                }
+            }
+            "eth_getTransactionReceipt" => {
+                receipt_calls.fetch_add(1, Ordering::SeqCst);
+                match params[0].as_str()?.parse::<H256>().ok()?.to_low_u64_be() {
+                    1 => Some(receipt_json(1, 101, 0, 1)),
+                    2 => Some(receipt_json(2, 101, 1, 0)),
+                    _ => Some(Value::Null),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    async fn native_node(block_receipts: bool, receipt_calls: Arc<AtomicUsize>) -> String {
+        rpc_stub::spawn(move |method, params| native_answer(method, params, block_receipts, &receipt_calls)).await
+    }
+
+    fn assert_native_transfers(transfers: &[EthereumTransfer]) {
+        assert_eq!(transfers.len(), 2);
+
+        let sent = &transfers[0];
+        assert_eq!(sent.tx_hash, format!("{:?}", hash(1)));
+        assert_eq!((sent.block_number, sent.log_index), (101, -1));
+        assert_eq!(sent.timestamp, 1_700_000_000 + 101 * 12);
+        assert_eq!((sent.from_address.as_str(), sent.to_address.as_str()), (WALLET, OTHER));
+        assert_eq!((sent.token_symbol.as_str(), sent.raw_amount.as_str()), ("ETH", "1000"));
+        assert_eq!(sent.status, "success");
+        assert_eq!(sent.fee_wei.as_deref(), Some("210000"));
+
+        // Only the sender pays the fee
+        let received = &transfers[1];
+        assert_eq!((received.block_number, received.log_index), (101, -2));
+        assert_eq!(received.status, "failed");
+        assert_eq!(received.fee_wei, None);
+    }
+
+    #[tokio::test]
+    async fn native_transfers_read_status_and_fees_from_block_receipts() {
+        let receipt_calls = Arc::new(AtomicUsize::new(0));
+        let url = native_node(true, receipt_calls.clone()).await;
+        let client = client(url);
+        let (chain, provider) = client.provider(1).unwrap();
+
+        let transfers = client.scan_native_transfers(chain, &provider, address(WALLET), 100, 102, &mut HashMap::new()).await.unwrap();
+
+        assert_native_transfers(&transfers);
+        // One call for the only block with a match
+        assert_eq!(receipt_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn native_transfers_fall_back_to_transaction_receipts() {
+        let receipt_calls = Arc::new(AtomicUsize::new(0));
+        let url = native_node(false, receipt_calls.clone()).await;
+        let client = client(url);
+        let (chain, provider) = client.provider(1).unwrap();
+
+        let transfers = client.scan_native_transfers(chain, &provider, address(WALLET), 100, 102, &mut HashMap::new()).await.unwrap();
+
+        assert_native_transfers(&transfers);
+        assert_eq!(receipt_calls.load(Ordering::SeqCst), 2);
+    }
+
+    // A USDC-like Transfer log in block 101
+    fn transfer_log_json(n: u64, from: &str, to: &str, amount: u64) -> Value {
+        json!({
+            "address": TOKEN,
+            "topics": [H256::from(keccak256(TRANSFER_EVENT)), H256::from(address(from)), H256::from(address(to))],
+            "data": H256::from_low_u64_be(amount),
+            "blockHash": hash(1_101),
+            "blockNumber": U64::from(101),
+            "transactionHash": hash(n),
+            "transactionIndex": U64::from(n),
+            "logIndex": U256::from(n),
+            "removed": false,
+        })
+    }
+
+    // Answers TOKEN's decimals and symbol calls; Multicall3 isn't deployed, so they are read individually
+    fn token_details_call(params: &Value) -> Option<Value> {
+        let tx = &params[0];
+        let data: Bytes = tx.get("input").or(tx.get("data"))?.as_str()?.parse().ok()?;
+        if tx["to"].as_str()? != TOKEN {
+            return None;
+        }
+        if data.as_ref() == DecimalsCall.encode().as_slice() {
+            Some(json!(Bytes::from(U256::from(6).encode())))
+        } else {
+            Some(json!(Bytes::from("USDC".to_string().encode())))
+        }
+    }
+
+    #[tokio::test]
+    async fn token_transfers_decode_logs_sent_and_received() {
+        let sent = transfer_log_json(1, WALLET, OTHER, 2_500_000);
+        let received = transfer_log_json(2, OTHER, WALLET, 1_000_000);
+        let to_self = transfer_log_json(3, WALLET, WALLET, 1);
+        // An ERC-721 transfer shares the signature but indexes the token id
+        let mut nft = transfer_log_json(4, OTHER, WALLET, 0);
+        nft["topics"].as_array_mut().unwrap().push(json!(hash(7)));
+        nft["data"] = json!("0x");
+
+        let block_calls = Arc::new(AtomicUsize::new(0));
+        let calls = block_calls.clone();
+        let url = rpc_stub::spawn(move |method, params| match method {
+            "eth_chainId" => Some(json!("0x1")),
+            "eth_getLogs" => {
+                let topics = &params[0]["topics"];
+                if topics[1].as_str().is_some() {
+                    Some(json!([sent, to_self]))
+                } else {
+                    Some(json!([received, to_self, nft]))
+                }
+            }
+            "eth_getBlockByNumber" => {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Some(block_json(101, json!([])))
+            }
+            "eth_call" => token_details_call(params),
+            _ => None,
+        })
+        .await;
+        let client = client(url);
+        let (chain, provider) = client.provider(1).unwrap();
+
+        let mut transfers = client.scan_transfers(chain, &provider, address(WALLET), 100, 102, None).await.unwrap();
+        transfers.sort_by_key(|t| t.log_index);
+
+        let summary: Vec<(i64, &str, &str, &str)> = transfers
+            .iter()
+            .map(|t| (t.log_index, t.from_address.as_str(), t.to_address.as_str(), t.raw_amount.as_str()))
+            .collect();
+        assert_eq!(summary, vec![(1, WALLET, OTHER, "2500000"), (2, OTHER, WALLET, "1000000"), (3, WALLET, WALLET, "1")]);
+        for transfer in &transfers {
+            assert_eq!(transfer.token_address.as_deref(), Some(TOKEN));
+            assert_eq!((transfer.token_symbol.as_str(), transfer.decimals), ("USDC", 6));
+            assert_eq!(transfer.timestamp, 1_700_000_000 + 101 * 12);
+            assert_eq!(transfer.status, "success");
+        }
+        // Every transfer is in block 101, so its timestamp is read once
+        assert_eq!(block_calls.load(Ordering::SeqCst), 1);
+    }
+
+    // Blocks below the native floor are only log-scanned, and a token transfer in a block the
+    // native scan fetched reuses that block's timestamp
+    #[tokio::test]
+    async fn history_scan_limits_native_blocks_and_reuses_their_timestamps() {
+        let block_calls = Arc::new(AtomicUsize::new(0));
+        let receipt_calls = AtomicUsize::new(0);
+        let calls = block_calls.clone();
+        let received = transfer_log_json(4, OTHER, WALLET, 1_000_000);
+        let url = rpc_stub::spawn(move |method, params| match method {
+            "eth_chainId" => Some(json!("0x1")),
+            "eth_getLogs" if params[0]["topics"][2].as_str().is_some() => Some(json!([received])),
+            "eth_getLogs" => Some(json!([])),
+            "eth_call" => token_details_call(params),
+            _ => {
+                if method == "eth_getBlockByNumber" {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                }
+                native_answer(method, params, true, &receipt_calls)
+            }
+        })
+        .await;
+        let client = client(url);
+        let (chain, provider) = client.provider(1).unwrap();
+
+        let mut transfers = client.scan_transfers(chain, &provider, address(WALLET), 90, 102, Some(100)).await.unwrap();
+        transfers.sort_by_key(|t| t.log_index);
+
+        assert_eq!(transfers.iter().map(|t| t.log_index).collect::<Vec<_>>(), vec![-2, -1, 4]);
+        assert_eq!(transfers[2].timestamp, 1_700_000_000 + 101 * 12);
+        // Blocks 100-102 only, with nothing fetched again for the token transfer
+        assert_eq!(block_calls.load(Ordering::SeqCst), 3);
+
+        let transfers = client.scan_transfers(chain, &provider, address(WALLET), 90, 102, None).await.unwrap();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(block_calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn log_range_scanner_covers_the_range_and_halves_failed_windows() {
+        let mut scanner = LogRangeScanner::new(100, 449, 100);
+        assert_eq!(scanner.window(), Some((100, 199)));
+        scanner.advance();
+        assert_eq!(scanner.window(), Some((200, 299)));
+        assert!(scanner.shrink());
+        assert!(scanner.shrink());
+        assert_eq!(scanner.window(), Some((200, 224)));
+        // Successful windows grow back to the configured size
+        scanner.advance();
+        assert_eq!(scanner.window(), Some((225, 274)));
+        scanner.advance();
+        assert_eq!(scanner.window(), Some((275, 374)));
+        scanner.advance();
+        assert_eq!(scanner.window(), Some((375, 449)));
+        scanner.advance();
+        assert_eq!(scanner.window(), None);
+    }
+
+    #[test]
+    fn descending_log_range_scanner_covers_the_range_newest_first() {
+        let mut scanner = LogRangeScanner::descending(0, 249, 100);
+        assert_eq!(scanner.window(), Some((150, 249)));
+        assert!(scanner.shrink());
+        assert_eq!(scanner.window(), Some((200, 249)));
+        scanner.advance();
+        assert_eq!(scanner.window(), Some((100, 199)));
+        scanner.advance();
+        assert_eq!(scanner.window(), Some((0, 99)));
+        scanner.advance();
+        assert_eq!(scanner.window(), None);
+    }
+
+    #[test]
+    fn log_range_scanner_gives_up_below_one_block() {
+        let mut scanner = LogRangeScanner::new(5, 5, 2);
+        assert!(scanner.shrink());
+        assert_eq!(scanner.window(), Some((5, 5)));
+        assert!(!scanner.shrink());
+        assert_eq!(LogRangeScanner::new(6, 5, 10).window(), None);
+    }
+
+    // resolve_name looks names up by their ENS namehash
+    #[test]
+    fn namehash_matches_known_answer() {
+        assert_eq!(
+            format!("{:?}", ethers::providers::ens::namehash("vitalik.eth")),
+            "0xee6c4522aab0003e8d14cd40a6af439055fd2577951148c14b6cea9a53475835"
+        );
+    }
+}
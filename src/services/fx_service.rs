@@ -0,0 +1,72 @@
+use anyhow::Result;
+use reqwest::StatusCode;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::time::Duration;
+use crate::services::cache::CacheService;
+use crate::utils::amounts;
+
+// FX lookups get a bounded wait so a slow rates API doesn't hold up portfolio requests
+const FX_API_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct FxService {
+    cache: CacheService,
+    http: reqwest::Client,
+}
+
+impl FxService {
+    pub fn new(cache: CacheService) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(FX_API_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self { cache, http }
+    }
+
+    // Units of `currency` (an uppercase ISO 4217 code) per USD, or None if the rates source doesn't quote it
+    pub async fn get_rate(&self, currency: &str) -> Result<Option<Decimal>> {
+        if currency == "USD" {
+            return Ok(Some(Decimal::ONE));
+        }
+
+        // Check cache first
+        if let Some(rate) = self.cache.get_fx_rate(currency).await? {
+            return Ok(Some(rate));
+        }
+
+        // Cache miss - fetch from the FX API
+        let Some(rate) = self.fetch_rate(currency).await? else {
+            return Ok(None);
+        };
+
+        // Store in cache
+        let ttl_seconds = std::env::var("FX_RATE_TTL_SECONDS")
+            .unwrap_or_else(|_| "3600".to_string()) // Reference rates are published daily
+            .parse()
+            .unwrap_or(3600);
+        self.cache.set_fx_rate(currency, rate, ttl_seconds).await?;
+
+        Ok(Some(rate))
+    }
+
+    // Frankfurter-compatible API: GET /latest?from=USD&to=EUR -> {"rates": {"EUR": 0.92}}
+    async fn fetch_rate(&self, currency: &str) -> Result<Option<Decimal>> {
+        let base_url = std::env::var("FX_API_URL")
+            .unwrap_or_else(|_| "https://api.frankfurter.app".to_string());
+        let response = self
+            .http
+            .get(format!("{}/latest", base_url.trim_end_matches('/')))
+            .query(&[("from", "USD"), ("to", currency)])
+            .send()
+            .await?;
+
+        // Unknown currencies are answered with 404 (or 422 by some deployments)
+        if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::UNPROCESSABLE_ENTITY) {
+            return Ok(None);
+        }
+        let body: Value = response.error_for_status()?.json().await?;
+
+        Ok(amounts::parse_decimal(&body["rates"][currency]).filter(|rate| rate.is_sign_positive() && !rate.is_zero()))
+    }
+}
@@ -0,0 +1,327 @@
+use sqlx::{PgPool, Postgres, Row, Transaction};
+use anyhow::Result;
+use rust_decimal::Decimal;
+use crate::types::nft::DiscoveredNft;
+use crate::types::transaction::{EthereumCursor, EthereumTransfer};
+
+#[derive(Clone)]
+pub struct HistoryStore {
+    pool: PgPool,
+}
+
+impl HistoryStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Oldest and newest blocks scanned for the address; everything in between is indexed
+    pub async fn get_index_state(&self, address: &str, chain: &str) -> Result<Option<(u64, u64)>> {
+        let result = sqlx::query(
+            r#"
+            SELECT first_indexed_block, last_indexed_block FROM ethereum_index_state
+            WHERE address = $1 AND chain = $2
+            "#
+        )
+        .bind(address)
+        .bind(chain)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result
+            .map(|row| -> Result<(u64, u64)> {
+                Ok((
+                    row.try_get::<i64, _>("first_indexed_block")? as u64,
+                    row.try_get::<i64, _>("last_indexed_block")? as u64,
+                ))
+            })
+            .transpose()
+    }
+
+    // Takes the address's history lock for the lifetime of the returned transaction, or None if
+    // another worker (possibly on another instance) is indexing it
+    pub async fn try_lock_history(&self, address: &str, chain: &str) -> Result<Option<Transaction<'static, Postgres>>> {
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query("SELECT pg_try_advisory_xact_lock(hashtextextended($1, 0)) AS locked")
+            .bind(format!("ethereum_history:{}:{}", chain, address))
+            .fetch_one(&mut *tx)
+            .await?;
+
+        Ok(row.try_get::<bool, _>("locked")?.then_some(tx))
+    }
+
+    // Stores a scanned block range atomically so a failed scan is retried from the same block. Ranges
+    // are always scanned adjacent to the indexed one, so widening it keeps it contiguous.
+    pub async fn store_transfers(&self, address: &str, chain: &str, transfers: &[EthereumTransfer], from_block: u64, to_block: u64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for transfer in transfers {
+            sqlx::query(
+                r#"
+                INSERT INTO ethereum_transfers (
+                    address, chain, tx_hash, block_number, log_index, timestamp, from_address, to_address,
+                    token_address, token_symbol, decimals, raw_amount, status, fee_wei
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+                ON CONFLICT (address, chain, tx_hash, log_index) DO NOTHING
+                "#
+            )
+            .bind(address)
+            .bind(chain)
+            .bind(&transfer.tx_hash)
+            .bind(transfer.block_number)
+            .bind(transfer.log_index)
+            .bind(transfer.timestamp)
+            .bind(&transfer.from_address)
+            .bind(&transfer.to_address)
+            .bind(&transfer.token_address)
+            .bind(&transfer.token_symbol)
+            .bind(transfer.decimals)
+            .bind(&transfer.raw_amount)
+            .bind(&transfer.status)
+            .bind(&transfer.fee_wei)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO ethereum_index_state (address, chain, first_indexed_block, last_indexed_block)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (address, chain)
+            DO UPDATE SET
+                first_indexed_block = LEAST(ethereum_index_state.first_indexed_block, $3),
+                last_indexed_block = GREATEST(ethereum_index_state.last_indexed_block, $4),
+                updated_at = NOW()
+            "#
+        )
+        .bind(address)
+        .bind(chain)
+        .bind(from_block as i64)
+        .bind(to_block as i64)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Newest first; `before` and `until` are exclusive bounds
+    pub async fn get_transfers(
+        &self,
+        address: &str,
+        chain: &str,
+        limit: usize,
+        before: Option<EthereumCursor>,
+        until: Option<EthereumCursor>,
+    ) -> Result<Vec<EthereumTransfer>> {
+        let transfers = sqlx::query_as::<_, EthereumTransfer>(
+            r#"
+            SELECT tx_hash, block_number, log_index, timestamp, from_address, to_address,
+                   token_address, token_symbol, decimals, raw_amount, status, fee_wei
+            FROM ethereum_transfers
+            WHERE address = $1 AND chain = $2
+              AND ($3::BIGINT IS NULL OR (block_number, log_index) < ($3, $4))
+              AND ($5::BIGINT IS NULL OR (block_number, log_index) > ($5, $6))
+            ORDER BY block_number DESC, log_index DESC
+            LIMIT $7
+            "#
+        )
+        .bind(address)
+        .bind(chain)
+        .bind(before.map(|c| c.block))
+        .bind(before.map(|c| c.log_index))
+        .bind(until.map(|c| c.block))
+        .bind(until.map(|c| c.log_index))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(transfers)
+    }
+
+    pub async fn get_discovery_block(&self, address: &str, chain: &str) -> Result<Option<u64>> {
+        let result = sqlx::query(
+            r#"
+            SELECT last_scanned_block FROM token_discovery_state
+            WHERE address = $1 AND chain = $2
+            "#
+        )
+        .bind(address)
+        .bind(chain)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result
+            .map(|row| row.try_get::<i64, _>("last_scanned_block"))
+            .transpose()?
+            .map(|block| block as u64))
+    }
+
+    pub async fn store_discovered_tokens(&self, address: &str, chain: &str, tokens: &[(String, u64)], last_scanned_block: u64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for (token_address, first_seen_block) in tokens {
+            sqlx::query(
+                r#"
+                INSERT INTO wallet_tokens (address, chain, token_address, first_seen_block)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (address, chain, token_address) DO NOTHING
+                "#
+            )
+            .bind(address)
+            .bind(chain)
+            .bind(token_address)
+            .bind(*first_seen_block as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO token_discovery_state (address, chain, last_scanned_block)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (address, chain)
+            DO UPDATE SET last_scanned_block = $3, updated_at = NOW()
+            "#
+        )
+        .bind(address)
+        .bind(chain)
+        .bind(last_scanned_block as i64)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn get_discovered_tokens(&self, address: &str, chain: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT token_address FROM wallet_tokens
+            WHERE address = $1 AND chain = $2
+            ORDER BY first_seen_block ASC
+            "#
+        )
+        .bind(address)
+        .bind(chain)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| row.try_get::<String, _>("token_address"))
+            .collect::<Result<_, _>>()?)
+    }
+
+    pub async fn get_nft_discovery_block(&self, address: &str, chain: &str) -> Result<Option<u64>> {
+        let result = sqlx::query(
+            r#"
+            SELECT last_scanned_block FROM nft_discovery_state
+            WHERE address = $1 AND chain = $2
+            "#
+        )
+        .bind(address)
+        .bind(chain)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result
+            .map(|row| row.try_get::<i64, _>("last_scanned_block"))
+            .transpose()?
+            .map(|block| block as u64))
+    }
+
+    pub async fn store_discovered_nfts(&self, address: &str, chain: &str, nfts: &[DiscoveredNft], last_scanned_block: u64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for nft in nfts {
+            sqlx::query(
+                r#"
+                INSERT INTO wallet_nfts (address, chain, contract_address, token_id, standard, first_seen_block)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (address, chain, contract_address, token_id) DO NOTHING
+                "#
+            )
+            .bind(address)
+            .bind(chain)
+            .bind(&nft.contract_address)
+            .bind(&nft.token_id)
+            .bind(&nft.standard)
+            .bind(nft.first_seen_block)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO nft_discovery_state (address, chain, last_scanned_block)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (address, chain)
+            DO UPDATE SET last_scanned_block = $3, updated_at = NOW()
+            "#
+        )
+        .bind(address)
+        .bind(chain)
+        .bind(last_scanned_block as i64)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn get_discovered_nfts(&self, address: &str, chain: &str) -> Result<Vec<DiscoveredNft>> {
+        let nfts = sqlx::query_as::<_, DiscoveredNft>(
+            r#"
+            SELECT contract_address, token_id, standard, first_seen_block FROM wallet_nfts
+            WHERE address = $1 AND chain = $2
+            ORDER BY first_seen_block ASC, id ASC
+            "#
+        )
+        .bind(address)
+        .bind(chain)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nfts)
+    }
+
+    pub async fn get_nft_valuation(&self, address: &str, chain: &str) -> Result<Option<Decimal>> {
+        let result = sqlx::query(
+            r#"
+            SELECT value_usd FROM nft_valuations
+            WHERE address = $1 AND chain = $2
+            "#
+        )
+        .bind(address)
+        .bind(chain)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result
+            .map(|row| row.try_get::<Decimal, _>("value_usd"))
+            .transpose()?)
+    }
+
+    pub async fn set_nft_valuation(&self, address: &str, chain: &str, value_usd: Decimal) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO nft_valuations (address, chain, value_usd)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (address, chain)
+            DO UPDATE SET value_usd = EXCLUDED.value_usd, valued_at = NOW()
+            "#
+        )
+        .bind(address)
+        .bind(chain)
+        .bind(value_usd)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
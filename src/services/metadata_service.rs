@@ -1,53 +1,158 @@
 use anyhow::Result;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, Url};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
 use crate::services::cache::CacheService;
+use crate::services::price_provider::CoinGeckoApi;
+use crate::services::token_registry::TokenRegistry;
+
+// Token URIs are chosen by whoever deployed the token, so fetches are limited to public HTTPS hosts,
+// a few redirects, a short timeout and a bounded body
+const NFT_METADATA_TIMEOUT: Duration = Duration::from_secs(5);
+const NFT_METADATA_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const NFT_METADATA_MAX_REDIRECTS: usize = 3;
+const NFT_METADATA_MAX_BYTES: usize = 1024 * 1024;
+
+// Token URIs that fail are retried after an hour rather than on every request
+const NFT_METADATA_RETRY_SECONDS: u64 = 3600;
+
+// Labels for an SPL mint, cached as JSON with the source they were resolved from:
+// "token-2022" (embedded in the mint), "metaplex" (metadata account) or "token-list"
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SolanaTokenMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "logoURI", skip_serializing_if = "Option::is_none")]
+    pub logo_uri: Option<String>,
+    // Left out rather than null so decimals lookups on the cached row keep working
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decimals: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+// Name, symbol and URI read from a mint's Token-2022 extension or its Metaplex metadata account
+pub struct OnchainTokenMetadata {
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub uri: Option<String>,
+    pub source: &'static str,
+}
 
 #[derive(Clone)]
 pub struct MetadataService {
     cache: CacheService,
+    token_registry: TokenRegistry,
+    http: reqwest::Client,
+    coingecko: CoinGeckoApi,
 }
 
 impl MetadataService {
-    pub fn new(cache: CacheService) -> Self {
-        Self { cache }
+    pub fn new(cache: CacheService, token_registry: TokenRegistry, coingecko: CoinGeckoApi) -> Self {
+        // A proxy would resolve hosts itself, bypassing the public address check
+        let http = reqwest::Client::builder()
+            .timeout(NFT_METADATA_TIMEOUT)
+            .connect_timeout(NFT_METADATA_CONNECT_TIMEOUT)
+            .no_proxy()
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(redirect::Policy::custom(|attempt| {
+                if attempt.previous().len() >= NFT_METADATA_MAX_REDIRECTS {
+                    return attempt.error("too many redirects");
+                }
+                match check_token_url(attempt.url()) {
+                    Ok(()) => attempt.follow(),
+                    Err(e) => attempt.error(e.to_string()),
+                }
+            }))
+            .build()
+            .expect("Failed to build token URI client");
+        Self { cache, token_registry, http, coingecko }
     }
 
-    pub async fn get_solana_metadata(&self, mint_address: &str) -> Result<(Option<String>, Option<String>)> {
-        // Check cache first
-        if let Some(cached) = self.cache.get_metadata(mint_address, "solana").await? {
-            let name = cached.get("name").and_then(|v| v.as_str()).map(|s| s.to_string());
-            let logo_uri = cached.get("logoURI").and_then(|v| v.as_str()).map(|s| s.to_string());
-            return Ok((name, logo_uri));
-        }
+    pub async fn get_cached_solana_metadata(&self, mint_addresses: &[String]) -> Result<HashMap<String, SolanaTokenMetadata>> {
+        let cached = self.cache.get_metadata_batch(mint_addresses, "solana").await?;
+        Ok(cached
+            .into_iter()
+            .filter_map(|(mint, metadata)| Some((mint, serde_json::from_value(metadata).ok()?)))
+            .collect())
+    }
+
+    // Resolves a mint's labels from its on-chain metadata, falling back to the configured token lists
+    pub async fn resolve_solana_metadata(&self, mint_address: &str, decimals: Option<u8>, onchain: Option<OnchainTokenMetadata>) -> Result<SolanaTokenMetadata> {
+        let onchain = onchain.filter(|m| m.name.is_some() || m.symbol.is_some());
+        let metadata = match onchain {
+            Some(onchain) => {
+                let logo_uri = match &onchain.uri {
+                    Some(uri) => self.fetch_token_image(mint_address, uri).await,
+                    None => None,
+                };
+                SolanaTokenMetadata {
+                    symbol: onchain.symbol,
+                    name: onchain.name,
+                    logo_uri,
+                    decimals,
+                    source: Some(onchain.source.to_string()),
+                }
+            }
+            None => {
+                let listed = self.token_list_metadata(mint_address);
+                SolanaTokenMetadata {
+                    // The mint account is authoritative for decimals; lists are only used when it couldn't be read
+                    decimals: decimals.or(listed.decimals),
+                    ..listed
+                }
+            }
+        };
 
-        // Fetch from Jupiter token list
-        let (name, logo_uri) = self.fetch_jupiter_metadata(mint_address).await?;
-        
         // Store in cache
-        let metadata = serde_json::json!({
-            "name": name.clone(),
-            "logoURI": logo_uri.clone(),
-        });
-        
         let ttl_seconds = std::env::var("CACHE_TTL_SECONDS")
             .unwrap_or_else(|_| "3600".to_string()) // 1 hour for metadata
             .parse()
             .unwrap_or(3600);
-        self.cache.set_metadata(mint_address, "solana", &metadata, ttl_seconds).await?;
+        self.cache.set_metadata(mint_address, "solana", &serde_json::to_value(&metadata)?, ttl_seconds).await?;
 
-        Ok((name, logo_uri))
+        Ok(metadata)
     }
 
-    pub async fn get_ethereum_metadata(&self, token_address: &str) -> Result<(Option<String>, Option<String>)> {
+    pub async fn get_solana_decimals(&self, mint_addresses: &[String]) -> Result<HashMap<String, u8>> {
+        let details = self.cache.get_token_details(mint_addresses, "solana").await?;
+        Ok(details.into_iter().map(|(mint, (decimals, _))| (mint, decimals)).collect())
+    }
+
+    pub async fn set_solana_decimals(&self, mint_address: &str, decimals: u8) -> Result<()> {
+        self.cache.set_token_details(mint_address, "solana", decimals, None).await
+    }
+
+    pub async fn get_evm_token_details(&self, chain: &str, token_addresses: &[String]) -> Result<HashMap<String, (u8, Option<String>)>> {
+        self.cache.get_token_details(token_addresses, chain).await
+    }
+
+    pub async fn set_evm_token_details(&self, chain: &str, token_address: &str, decimals: u8, symbol: Option<&str>) -> Result<()> {
+        self.cache.set_token_details(token_address, chain, decimals, symbol).await
+    }
+
+    pub async fn get_evm_metadata(&self, chain: &str, token_address: &str, coingecko_id: Option<&str>) -> Result<(Option<String>, Option<String>)> {
         // Check cache first
-        if let Some(cached) = self.cache.get_metadata(token_address, "ethereum").await? {
+        if let Some(cached) = self.cache.get_metadata(token_address, chain).await? {
             let name = cached.get("name").and_then(|v| v.as_str()).map(|s| s.to_string());
             let logo_uri = cached.get("logoURI").and_then(|v| v.as_str()).map(|s| s.to_string());
             return Ok((name, logo_uri));
         }
 
-        // Fetch from CoinGecko
-        let (name, logo_uri) = self.fetch_coingecko_metadata(token_address).await?;
+        // Fetch from CoinGecko, using the id from the chain registry
+        let (name, logo_uri) = match coingecko_id {
+            Some(id) => self.fetch_coingecko_metadata(id).await?,
+            None => (None, None),
+        };
         
         // Store in cache
         let metadata = serde_json::json!({
@@ -59,70 +164,281 @@ impl MetadataService {
             .unwrap_or_else(|_| "3600".to_string()) // 1 hour for metadata
             .parse()
             .unwrap_or(3600);
-        self.cache.set_metadata(token_address, "ethereum", &metadata, ttl_seconds).await?;
+        self.cache.set_metadata(token_address, chain, &metadata, ttl_seconds).await?;
 
         Ok((name, logo_uri))
     }
 
-    async fn fetch_jupiter_metadata(&self, mint_address: &str) -> Result<(Option<String>, Option<String>)> {
-        // Fetch from Jupiter token list
-        let url = std::env::var("JUPITER_TOKEN_LIST_URL")
-            .unwrap_or_else(|_| "https://token.jup.ag/strict".to_string());
-        
+    pub async fn get_cached_nft_metadata(&self, chain: &str, nft_ids: &[String]) -> Result<HashMap<String, (Option<String>, Option<Value>)>> {
+        self.cache.get_nft_metadata(nft_ids, chain).await
+    }
+
+    // Fetches and caches the JSON for (NFT id, token URI) pairs. Fetches run concurrently, bounded
+    // to avoid hammering metadata hosts; failures come back as None.
+    pub async fn fetch_nft_metadata_batch(&self, chain: &str, uris: Vec<(String, String)>, concurrency: usize) -> Vec<(String, String, Option<Value>)> {
+        stream::iter(uris)
+            .map(|(id, uri)| async move {
+                let data = self.fetch_nft_metadata(chain, &id, &uri).await.unwrap_or_else(|e| {
+                    tracing::warn!("Failed to cache NFT metadata for {}: {}", id, e);
+                    None
+                });
+                (id, uri, data)
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    // Fetches the JSON a token URI points to and caches it; None if it could not be fetched or parsed
+    async fn fetch_nft_metadata(&self, chain: &str, nft_id: &str, token_uri: &str) -> Result<Option<Value>> {
+        let metadata = match self.fetch_token_uri(token_uri).await {
+            Ok(metadata) if metadata.is_object() => Some(metadata),
+            Ok(_) => None,
+            Err(e) => {
+                tracing::debug!("Failed to fetch NFT metadata for {} from {}: {}", nft_id, token_uri, e);
+                None
+            }
+        };
+
+        // Store in cache
+        let ttl_seconds = match metadata {
+            Some(_) => std::env::var("NFT_METADATA_TTL_SECONDS")
+                .unwrap_or_else(|_| "86400".to_string()) // 1 day for NFT metadata
+                .parse()
+                .unwrap_or(86400),
+            None => NFT_METADATA_RETRY_SECONDS,
+        };
+        self.cache.set_nft_metadata(nft_id, chain, Some(token_uri), metadata.as_ref(), ttl_seconds).await?;
+
+        Ok(metadata)
+    }
+
+    async fn fetch_token_uri(&self, token_uri: &str) -> Result<Value> {
+        // Fully on-chain collections embed the JSON in the URI itself
+        if let Some(data) = token_uri.strip_prefix("data:") {
+            return parse_data_uri(data);
+        }
+
+        let url = Url::parse(&gateway_url(token_uri))?;
+        check_token_url(&url)?;
+        let mut response = self.http.get(url).send().await?.error_for_status()?;
+        if response.content_length().is_some_and(|len| len > NFT_METADATA_MAX_BYTES as u64) {
+            return Err(anyhow::anyhow!("Token URI body exceeds {} bytes", NFT_METADATA_MAX_BYTES));
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > NFT_METADATA_MAX_BYTES {
+                return Err(anyhow::anyhow!("Token URI body exceeds {} bytes", NFT_METADATA_MAX_BYTES));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    // Image from the JSON an on-chain metadata URI points to
+    async fn fetch_token_image(&self, mint_address: &str, uri: &str) -> Option<String> {
+        match self.fetch_token_uri(uri).await {
+            Ok(json) => nft_field(Some(&json), "image").map(|image| gateway_url(&image)),
+            Err(e) => {
+                tracing::debug!("Failed to fetch token metadata for {} from {}: {}", mint_address, uri, e);
+                None
+            }
+        }
+    }
+
+    fn token_list_metadata(&self, mint_address: &str) -> SolanaTokenMetadata {
+        match self.token_registry.get(mint_address) {
+            Some(token) => SolanaTokenMetadata {
+                symbol: token.symbol,
+                name: token.name,
+                logo_uri: token.logo_uri,
+                decimals: token.decimals,
+                source: Some("token-list".to_string()),
+            },
+            None => SolanaTokenMetadata::default(),
+        }
+    }
+
+    async fn fetch_coingecko_metadata(&self, coingecko_id: &str) -> Result<(Option<String>, Option<String>)> {
+        let url = self.coingecko.url(&format!("coins/{}", coingecko_id));
         let response: Value = reqwest::get(&url).await?.json().await?;
         
-        // Search for token in the list
-        if let Some(tokens) = response.as_array() {
-            for token in tokens {
-                if let Some(addr) = token.get("address").and_then(|v| v.as_str()) {
-                    if addr == mint_address {
-                        let name = token.get("name").and_then(|v| v.as_str()).map(|s| s.to_string());
-                        let logo_uri = token.get("logoURI").and_then(|v| v.as_str()).map(|s| s.to_string());
-                        return Ok((name, logo_uri));
-                    }
-                }
+        // Coin images come in several sizes
+        let name = response.get("name").and_then(|v| v.as_str()).map(|s| s.to_string());
+        let logo_uri = response["image"]["large"].as_str().map(|s| s.to_string());
+
+        Ok((name, logo_uri))
+    }
+}
+
+// Non-empty string field of NFT metadata JSON
+pub fn nft_field(metadata: Option<&Value>, key: &str) -> Option<String> {
+    metadata
+        .and_then(|m| m.get(key))
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+// Token URI as shown in responses; data: URIs repeat the metadata inline, so they are left out
+pub fn display_token_uri(uri: Option<String>) -> Option<String> {
+    uri.filter(|uri| !uri.starts_with("data:"))
+}
+
+// Rewrites ipfs:// and ar:// URIs to HTTP gateways; other URIs are returned unchanged
+pub fn gateway_url(uri: &str) -> String {
+    if let Some(path) = uri.strip_prefix("ipfs://") {
+        let gateway = std::env::var("IPFS_GATEWAY_URL")
+            .unwrap_or_else(|_| "https://ipfs.io/ipfs".to_string());
+        // Some collections use the redundant "ipfs://ipfs/<cid>" form
+        let path = path.strip_prefix("ipfs/").unwrap_or(path);
+        return format!("{}/{}", gateway.trim_end_matches('/'), path);
+    }
+    if let Some(path) = uri.strip_prefix("ar://") {
+        return format!("https://arweave.net/{}", path);
+    }
+    uri.to_string()
+}
+
+// Token URIs may only be fetched over HTTPS, and never from a literal non-public address;
+// hostnames are checked when they are resolved
+fn check_token_url(url: &Url) -> Result<()> {
+    if url.scheme() != "https" {
+        return Err(anyhow::anyhow!("Unsupported token URI scheme: {}", url.scheme()));
+    }
+    let host = url.host_str().ok_or_else(|| anyhow::anyhow!("Token URI has no host"))?;
+    // IPv6 literals keep their brackets in the host string
+    match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) if !is_public_ip(ip) => Err(anyhow::anyhow!("Token URI points at a non-public address: {}", ip)),
+        _ => Ok(()),
+    }
+}
+
+// Resolves hostnames for token URI fetches, refusing any that point at a non-public address.
+// Checking at connect time also covers redirects and DNS answers that change between lookups.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+                return Err(format!("{} resolves to a non-public address: {}", host, addr.ip()).into());
             }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// False for loopback, private, link-local, shared, multicast and other special-purpose ranges
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64)) // 100.64.0.0/10, carrier-grade NAT
         }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || (first & 0xfe00) == 0xfc00 // fc00::/7, unique local
+                    || (first & 0xffc0) == 0xfe80) // fe80::/10, link-local
+            }
+        },
+    }
+}
 
-        Ok((None, None))
-    }
-
-    async fn fetch_coingecko_metadata(&self, token_address: &str) -> Result<(Option<String>, Option<String>)> {
-        // Map token addresses to CoinGecko IDs for metadata
-        let coingecko_id = match token_address.to_lowercase().as_str() {
-            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48" => Some("usd-coin"),
-            "0xdac17f958d2ee523a2206206994597c13d831ec7" => Some("tether"),
-            "0x6b175474e89094c44da98b954eedeac495271d0f" => Some("dai"),
-            "0x2260fac5e5542a773aa44fbcfedf7c193bc2c599" => Some("wrapped-bitcoin"),
-            "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2" => Some("ethereum"),
-            "0x1f9840a85d5af5bf1d1762f925bdaddc4201f984" => Some("uniswap"),
-            "0x514910771af9ca656af840dff83e8264ecf986ca" => Some("chainlink"),
-            "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9" => Some("aave"),
-            _ => None,
-        };
+// Parses the part of a "data:" URI after the scheme: "<media type>[;base64],<payload>"
+fn parse_data_uri(data: &str) -> Result<Value> {
+    let (media_type, payload) = data
+        .split_once(',')
+        .ok_or_else(|| anyhow::anyhow!("Malformed data URI"))?;
 
-        if let Some(id) = coingecko_id {
-            let api_key = std::env::var("COINGECKO_API_KEY").ok();
-            let url = if let Some(key) = api_key {
-                format!("https://api.coingecko.com/api/v3/coins/{}?x_cg_demo_api_key={}", id, key)
-            } else {
-                format!("https://api.coingecko.com/api/v3/coins/{}", id)
-            };
-            
-            let response: Value = reqwest::get(&url).await?.json().await?;
-            
-            let name = response.get("name").and_then(|v| v.as_str()).map(|s| s.to_string());
-            let logo_uri = response.get("image").and_then(|v| v.as_str()).map(|s| s.to_string());
-            
-            return Ok((name, logo_uri));
-        }
+    if media_type.ends_with(";base64") {
+        return Ok(serde_json::from_slice(&BASE64_STANDARD.decode(payload.trim())?)?);
+    }
+
+    // Plain payloads are often raw JSON even though they should be percent-encoded
+    match serde_json::from_str(payload) {
+        Ok(value) => Ok(value),
+        Err(_) => Ok(serde_json::from_slice(&percent_decode(payload))?),
+    }
+}
 
-        Ok((None, None))
+fn percent_decode(input: &str) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
     }
+    decoded
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    fn check(uri: &str) -> Result<()> {
+        check_token_url(&Url::parse(&gateway_url(uri))?)
+    }
 
+    #[test]
+    fn only_fetches_https_and_gateway_uris() {
+        assert!(check("https://example.com/1.json").is_ok());
+        assert!(check("ipfs://QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG/1").is_ok());
+        assert!(check("ar://bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_U").is_ok());
+        assert!(check("http://example.com/1.json").is_err());
+        assert!(check("file:///etc/passwd").is_err());
+        assert!(check("gopher://example.com/").is_err());
+    }
 
+    #[test]
+    fn refuses_literal_non_public_addresses() {
+        assert!(check("https://127.0.0.1/").is_err());
+        assert!(check("https://169.254.169.254/latest/meta-data/").is_err());
+        assert!(check("https://10.0.0.1/").is_err());
+        assert!(check("https://[::1]/").is_err());
+        assert!(check("https://[::ffff:192.168.0.1]/").is_err());
+        assert!(check("https://1.1.1.1/").is_ok());
+    }
 
+    #[test]
+    fn classifies_special_purpose_ranges() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.0.1", "100.64.0.1", "0.0.0.0", "fd00::1", "fe80::1", "::"] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["8.8.8.8", "100.128.0.1", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
 
+    #[tokio::test]
+    async fn resolver_refuses_hosts_that_resolve_to_loopback() {
+        let name: Name = "localhost".parse().unwrap();
+        assert!(PublicResolver.resolve(name).await.is_err());
+    }
+}
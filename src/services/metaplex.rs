@@ -0,0 +1,236 @@
+use serde_json::Value;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+// Metaplex Token Metadata account derivation and decoding, plus DAS asset parsing for compressed NFTs
+
+const TOKEN_METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+// Account key tag of a v1 metadata account
+const METADATA_V1_KEY: u8 = 4;
+
+// Creators are a pubkey followed by a verified flag and a share
+const CREATOR_LEN: usize = 34;
+
+// TokenStandard variants for fungible tokens that still use a metadata account
+const FUNGIBLE_ASSET: u8 = 1;
+const FUNGIBLE: u8 = 2;
+
+pub struct Metadata {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub token_standard: Option<u8>,
+    pub collection: Option<Pubkey>, // Verified collections only
+}
+
+impl Metadata {
+    pub fn is_fungible(&self) -> bool {
+        matches!(self.token_standard, Some(FUNGIBLE_ASSET) | Some(FUNGIBLE))
+    }
+}
+
+// An NFT read from a metadata account or a DAS asset, before off-chain metadata is applied
+pub struct NftInfo {
+    pub mint: String, // Asset id for compressed NFTs
+    pub compressed: bool,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub uri: Option<String>,
+    pub image: Option<String>,
+    pub collection: Option<String>,
+}
+
+pub fn metadata_key(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &TOKEN_METADATA_PROGRAM_ID,
+    )
+    .0
+}
+
+// Decodes a borsh metadata account; fields after is_mutable were added over time and may be missing
+pub fn parse_metadata(data: &[u8]) -> Option<Metadata> {
+    let mut reader = Reader { data, offset: 0 };
+    if reader.u8()? != METADATA_V1_KEY {
+        return None;
+    }
+    reader.skip(64)?; // Update authority and mint
+    let name = reader.string()?;
+    let symbol = reader.string()?;
+    let uri = reader.string()?;
+    reader.skip(2)?; // Seller fee basis points
+    if reader.u8()? == 1 {
+        let creators = reader.u32()? as usize;
+        reader.skip(creators.checked_mul(CREATOR_LEN)?)?;
+    }
+    reader.skip(2)?; // Primary sale happened, is mutable
+
+    let _edition_nonce = reader.option(|r| r.u8());
+    let token_standard = reader.option(|r| r.u8()).flatten();
+    let collection = reader
+        .option(|r| Some((r.u8()? == 1, r.pubkey()?)))
+        .flatten()
+        .filter(|(verified, _)| *verified)
+        .map(|(_, key)| key);
+
+    Some(Metadata { name, symbol, uri, token_standard, collection })
+}
+
+// Reads a compressed NFT from a DAS getAssetsByOwner item; None for uncompressed or burnt assets
+pub fn parse_das_asset(item: &Value) -> Option<NftInfo> {
+    if item["compression"]["compressed"].as_bool() != Some(true) || item["burnt"].as_bool() == Some(true) {
+        return None;
+    }
+
+    let text = |value: &Value| value.as_str().filter(|s| !s.is_empty()).map(|s| s.to_string());
+    let content = &item["content"];
+    let collection = item["grouping"]
+        .as_array()
+        .and_then(|groups| groups.iter().find(|g| g["group_key"] == "collection"))
+        .and_then(|group| text(&group["group_value"]));
+
+    Some(NftInfo {
+        mint: text(&item["id"])?,
+        compressed: true,
+        name: text(&content["metadata"]["name"]),
+        description: text(&content["metadata"]["description"]),
+        uri: text(&content["json_uri"]),
+        image: text(&content["links"]["image"]).or_else(|| content["files"].get(0).and_then(|f| text(&f["uri"]))),
+        collection,
+    })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&[u8]> {
+        let bytes = self.data.get(self.offset..self.offset.checked_add(len)?)?;
+        self.offset += len;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn pubkey(&mut self) -> Option<Pubkey> {
+        Some(Pubkey::new_from_array(self.bytes(32)?.try_into().ok()?))
+    }
+
+    // Names, symbols and URIs are stored padded with null bytes to a fixed length
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?;
+        Some(String::from_utf8_lossy(bytes).trim_end_matches('\0').trim().to_string())
+    }
+
+    // Outer None when the data ends before the field; Some(None) for an absent borsh option
+    fn option<T>(&mut self, read: impl FnOnce(&mut Self) -> Option<T>) -> Option<Option<T>> {
+        match self.u8()? {
+            0 => Some(None),
+            _ => read(self).map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Size the Token Metadata program allocates for a metadata account; unused space stays zeroed
+    const METADATA_ACCOUNT_LEN: usize = 679;
+
+    // Strings are written padded with null bytes to their maximum length, as the program stores them
+    fn padded(out: &mut Vec<u8>, value: &str, max_len: usize) {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(max_len, 0);
+        out.extend_from_slice(&(max_len as u32).to_le_bytes());
+        out.extend_from_slice(&bytes);
+    }
+
+    // A v1 metadata account with two creators, laid out field by field up to the collection
+    fn metadata_account(token_standard: Option<u8>, collection: Option<(bool, Pubkey)>) -> Vec<u8> {
+        let mut data = vec![METADATA_V1_KEY];
+        data.extend_from_slice(Pubkey::new_unique().as_ref()); // Update authority
+        data.extend_from_slice(Pubkey::new_unique().as_ref()); // Mint
+        padded(&mut data, "Example #1234", 32);
+        padded(&mut data, "EXMPL", 10);
+        padded(&mut data, "https://arweave.net/3wXyF1wvK6ARJ_9ue-O58CMuXrz5nyHEiPFQ6z5q02E", 200);
+        data.extend_from_slice(&420u16.to_le_bytes()); // Seller fee basis points
+        data.push(1);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        for (share, verified) in [(100u8, 1u8), (0, 0)] {
+            data.extend_from_slice(Pubkey::new_unique().as_ref());
+            data.push(verified);
+            data.push(share);
+        }
+        data.extend_from_slice(&[1, 1]); // Primary sale happened, is mutable
+        data.extend_from_slice(&[1, 254]); // Edition nonce
+        match token_standard {
+            Some(standard) => data.extend_from_slice(&[1, standard]),
+            None => data.push(0),
+        }
+        match collection {
+            Some((verified, key)) => {
+                data.extend_from_slice(&[1, verified as u8]);
+                data.extend_from_slice(key.as_ref());
+            }
+            None => data.push(0),
+        }
+        data.resize(METADATA_ACCOUNT_LEN, 0);
+        data
+    }
+
+    #[test]
+    fn parses_a_full_nft_metadata_account() {
+        let collection = Pubkey::new_unique();
+        let metadata = parse_metadata(&metadata_account(Some(4), Some((true, collection)))).unwrap();
+
+        assert_eq!(metadata.name, "Example #1234");
+        assert_eq!(metadata.symbol, "EXMPL");
+        assert_eq!(metadata.uri, "https://arweave.net/3wXyF1wvK6ARJ_9ue-O58CMuXrz5nyHEiPFQ6z5q02E");
+        assert_eq!(metadata.token_standard, Some(4));
+        assert_eq!(metadata.collection, Some(collection));
+        assert!(!metadata.is_fungible());
+    }
+
+    #[test]
+    fn ignores_unverified_collections_and_flags_fungible_standards() {
+        let metadata = parse_metadata(&metadata_account(Some(FUNGIBLE), Some((false, Pubkey::new_unique())))).unwrap();
+        assert_eq!(metadata.collection, None);
+        assert!(metadata.is_fungible());
+    }
+
+    #[test]
+    fn accepts_accounts_written_before_the_optional_fields_existed() {
+        let mut data = metadata_account(None, None);
+        // Older accounts end right after is_mutable
+        let end = 1 + 64 + (4 + 32) + (4 + 10) + (4 + 200) + 2 + 1 + 4 + 2 * CREATOR_LEN + 2;
+        data.truncate(end);
+
+        let metadata = parse_metadata(&data).unwrap();
+        assert_eq!(metadata.name, "Example #1234");
+        assert_eq!(metadata.token_standard, None);
+        assert_eq!(metadata.collection, None);
+    }
+
+    #[test]
+    fn rejects_other_accounts_and_truncated_data() {
+        let mut data = metadata_account(None, None);
+        assert!(parse_metadata(&data[..100]).is_none());
+        data[0] = 6; // Master edition key
+        assert!(parse_metadata(&data).is_none());
+    }
+}
@@ -1,6 +1,22 @@
+pub mod chain_client;
 pub mod solana_client;
+pub mod solana_tx_parser;
 pub mod ethereum_client;
+pub mod bitcoin_client;
 pub mod price_service;
+pub mod price_provider;
 pub mod cache;
 pub mod metadata_service;
+pub mod token_registry;
+pub mod name_service;
+pub mod sns;
+pub mod metaplex;
+pub mod rpc_counter;
+pub mod history_store;
+pub mod price_history;
+pub mod fx_service;
 
+#[cfg(test)]
+pub mod rpc_stub;
+#[cfg(test)]
+pub mod esplora_stub;
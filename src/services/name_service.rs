@@ -0,0 +1,62 @@
+use anyhow::Result;
+use crate::services::cache::CacheService;
+use crate::services::chain_client::ChainClient;
+
+// Resolves ENS/SNS names through the chain clients, caching both directions in Postgres
+#[derive(Clone)]
+pub struct NameService {
+    cache: CacheService,
+}
+
+impl NameService {
+    pub fn new(cache: CacheService) -> Self {
+        Self { cache }
+    }
+
+    // Name -> address, or None if the name is not registered
+    pub async fn resolve(&self, client: &dyn ChainClient, name: &str) -> Result<Option<String>> {
+        let Some(naming_system) = client.naming_system() else {
+            return Ok(None);
+        };
+
+        // Check cache first
+        if let Some(cached) = self.cache.get_name_lookup(name, naming_system, "forward").await? {
+            return Ok(cached);
+        }
+
+        // Cache miss - resolve on-chain
+        let address = client.resolve_name(name).await?;
+
+        // Store in cache
+        self.cache.set_name_lookup(name, naming_system, "forward", address.as_deref(), ttl_seconds()).await?;
+
+        Ok(address)
+    }
+
+    // Address -> primary name, or None if the owner has not set one
+    pub async fn lookup(&self, client: &dyn ChainClient, address: &str) -> Result<Option<String>> {
+        let Some(naming_system) = client.naming_system() else {
+            return Ok(None);
+        };
+
+        // Check cache first
+        if let Some(cached) = self.cache.get_name_lookup(address, naming_system, "reverse").await? {
+            return Ok(cached);
+        }
+
+        // Cache miss - look up on-chain
+        let name = client.lookup_name(address).await?;
+
+        // Store in cache
+        self.cache.set_name_lookup(address, naming_system, "reverse", name.as_deref(), ttl_seconds()).await?;
+
+        Ok(name)
+    }
+}
+
+fn ttl_seconds() -> u64 {
+    std::env::var("NAME_CACHE_TTL_SECONDS")
+        .unwrap_or_else(|_| "3600".to_string()) // 1 hour for names
+        .parse()
+        .unwrap_or(3600)
+}
@@ -0,0 +1,348 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::{PgPool, Row};
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+use crate::services::price_provider::TokenRef;
+use crate::services::price_service::PriceService;
+use crate::types::price::{Candle, PriceRange, Resolution};
+
+// Time-bucketed USD prices for charts. A background collector samples tracked wallets' tokens that have a
+// CoinGecko id, plus any listed in PRICE_HISTORY_WATCHLIST; ranges the collector never saw are backfilled
+// from the price providers' historical endpoints.
+
+#[derive(Clone)]
+pub struct PriceHistory {
+    pool: PgPool,
+    price_service: PriceService,
+}
+
+impl PriceHistory {
+    pub fn new(pool: PgPool, price_service: PriceService) -> Self {
+        Self { pool, price_service }
+    }
+
+    pub fn spawn_collector(&self) {
+        let interval_seconds = std::env::var("PRICE_HISTORY_INTERVAL_SECONDS")
+            .unwrap_or_else(|_| "300".to_string()) // One sample per 5-minute bucket
+            .parse()
+            .unwrap_or(300);
+
+        let history = self.clone();
+        let watchlist = self.watchlist();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds));
+            // The first tick completes immediately; give the first sample a full interval like the rest
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match history.collect(&watchlist).await {
+                    Ok(count) => tracing::debug!("Recorded prices for {} tokens", count),
+                    Err(e) => tracing::warn!("Failed to record price history: {}", e),
+                }
+            }
+        });
+    }
+
+    // Appends the current price of every collected token to its 5m, 1h and 1d buckets; returns the number of tokens priced
+    pub async fn collect(&self, watchlist: &[TokenRef]) -> Result<usize> {
+        let mut seen = HashSet::new();
+        // Held tokens without a CoinGecko id are mostly spam and long-tail mints nobody charts
+        let held = self
+            .get_held_tokens()
+            .await?
+            .into_iter()
+            .filter_map(|(chain, token_id, decimals)| match token_id {
+                Some(token_id) => self.price_service.token_ref(&chain, &token_id, decimals),
+                None => self.price_service.native_token(&chain),
+            })
+            .filter(|token| token.coingecko_id.is_some());
+        let tokens: Vec<TokenRef> = watchlist
+            .iter()
+            .cloned()
+            .chain(held)
+            .filter(|token| seen.insert((token.chain.clone(), token.id.clone())))
+            .collect();
+        let quotes = self.price_service.get_prices(&tokens).await?;
+
+        let now = chrono::Utc::now().timestamp();
+        let mut candles = Vec::new();
+        for (token, quote) in tokens.iter().zip(quotes) {
+            let Some(price) = quote.price_usd else { continue };
+            for resolution in Resolution::ALL {
+                let candle = Candle {
+                    timestamp: resolution.bucket_start(now),
+                    open: price,
+                    high: price,
+                    low: price,
+                    close: price,
+                };
+                candles.push((token, resolution, candle));
+            }
+        }
+        self.store_candles(&candles, true).await?;
+        self.prune(now).await?;
+
+        Ok(candles.len() / Resolution::ALL.len())
+    }
+
+    // Candles covering the range, backfilling from the providers when stored history doesn't reach back far enough
+    pub async fn get_candles(&self, token: &TokenRef, range: PriceRange) -> Result<Vec<Candle>> {
+        let resolution = range.resolution();
+        let now = chrono::Utc::now().timestamp();
+        let start = range
+            .days()
+            .map(|days| resolution.bucket_start(now - days as i64 * 86400))
+            .unwrap_or(0);
+
+        // A failed backfill still serves whatever history is stored
+        if self.needs_backfill(token, resolution, start, now).await? {
+            if let Err(e) = self.backfill(token, range, start, now).await {
+                tracing::warn!("Failed to backfill {} price history for {}: {}", token.chain, token.id, e);
+            }
+        }
+
+        let candles = sqlx::query_as::<_, Candle>(
+            r#"
+            SELECT EXTRACT(EPOCH FROM bucket_start)::BIGINT AS timestamp, open, high, low, close
+            FROM price_history
+            WHERE chain = $1 AND token_id = $2 AND resolution = $3 AND bucket_start >= $4
+            ORDER BY bucket_start ASC
+            "#
+        )
+        .bind(&token.chain)
+        .bind(&token.id)
+        .bind(resolution.as_str())
+        .bind(to_datetime(start))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(candles)
+    }
+
+    async fn needs_backfill(&self, token: &TokenRef, resolution: Resolution, start: i64, now: i64) -> Result<bool> {
+        let row = sqlx::query(
+            r#"
+            SELECT f.covered_from, f.backfilled_at,
+                   (SELECT MAX(h.bucket_start) FROM price_history h
+                    WHERE h.chain = f.chain AND h.token_id = f.token_id AND h.resolution = f.resolution) AS latest
+            FROM price_history_backfills f
+            WHERE f.chain = $1 AND f.token_id = $2 AND f.resolution = $3
+            "#
+        )
+        .bind(&token.chain)
+        .bind(&token.id)
+        .bind(resolution.as_str())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(true);
+        };
+        let covered_from = row.try_get::<DateTime<Utc>, _>("covered_from")?.timestamp();
+        let backfilled_at = row.try_get::<DateTime<Utc>, _>("backfilled_at")?.timestamp();
+        let latest = row.try_get::<Option<DateTime<Utc>>, _>("latest")?.map(|latest| latest.timestamp());
+        if start < covered_from {
+            return Ok(true);
+        }
+
+        // Tokens no tracked wallet holds aren't collected, so their history is refetched once it falls a bucket behind
+        let stale = latest.is_none_or(|latest| latest < resolution.bucket_start(now) - resolution.seconds());
+        Ok(stale && backfilled_at < now - resolution.seconds())
+    }
+
+    async fn backfill(&self, token: &TokenRef, range: PriceRange, start: i64, now: i64) -> Result<()> {
+        let resolution = range.resolution();
+
+        let samples = self.price_service.get_price_history(token, range.days()).await?;
+        let candles: Vec<(&TokenRef, Resolution, Candle)> = aggregate_candles(resolution, samples)
+            .into_iter()
+            .map(|candle| (token, resolution, candle))
+            .collect();
+        self.store_candles(&candles, false).await?;
+        tracing::debug!("Backfilled {} {} candles for {} on {}", candles.len(), resolution.as_str(), token.id, token.chain);
+
+        sqlx::query(
+            r#"
+            INSERT INTO price_history_backfills (chain, token_id, resolution, covered_from, backfilled_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (chain, token_id, resolution)
+            DO UPDATE SET covered_from = LEAST(price_history_backfills.covered_from, EXCLUDED.covered_from),
+                          backfilled_at = EXCLUDED.backfilled_at
+            "#
+        )
+        .bind(&token.chain)
+        .bind(&token.id)
+        .bind(resolution.as_str())
+        .bind(to_datetime(start))
+        .bind(to_datetime(now))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Collected samples are merged into existing buckets; backfilled candles only fill buckets the collector hasn't
+    async fn store_candles(&self, candles: &[(&TokenRef, Resolution, Candle)], merge: bool) -> Result<()> {
+        if candles.is_empty() {
+            return Ok(());
+        }
+
+        let chains: Vec<&str> = candles.iter().map(|(t, _, _)| t.chain.as_str()).collect();
+        let token_ids: Vec<&str> = candles.iter().map(|(t, _, _)| t.id.as_str()).collect();
+        let resolutions: Vec<&str> = candles.iter().map(|(_, r, _)| r.as_str()).collect();
+        let bucket_starts: Vec<DateTime<Utc>> = candles.iter().map(|(_, _, c)| to_datetime(c.timestamp)).collect();
+        let opens: Vec<Decimal> = candles.iter().map(|(_, _, c)| c.open).collect();
+        let highs: Vec<Decimal> = candles.iter().map(|(_, _, c)| c.high).collect();
+        let lows: Vec<Decimal> = candles.iter().map(|(_, _, c)| c.low).collect();
+        let closes: Vec<Decimal> = candles.iter().map(|(_, _, c)| c.close).collect();
+
+        let on_conflict = if merge {
+            r#"
+            DO UPDATE SET high = GREATEST(price_history.high, EXCLUDED.high),
+                          low = LEAST(price_history.low, EXCLUDED.low),
+                          close = EXCLUDED.close,
+                          updated_at = NOW()
+            "#
+        } else {
+            "DO NOTHING"
+        };
+        let query = format!(
+            r#"
+            INSERT INTO price_history (chain, token_id, resolution, bucket_start, open, high, low, close)
+            SELECT * FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::VARCHAR[], $4::TIMESTAMPTZ[], $5::NUMERIC[], $6::NUMERIC[], $7::NUMERIC[], $8::NUMERIC[])
+            ON CONFLICT (chain, token_id, resolution, bucket_start) {}
+            "#,
+            on_conflict
+        );
+
+        sqlx::query(&query)
+            .bind(&chains)
+            .bind(&token_ids)
+            .bind(&resolutions)
+            .bind(&bucket_starts)
+            .bind(&opens)
+            .bind(&highs)
+            .bind(&lows)
+            .bind(&closes)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn prune(&self, now: i64) -> Result<()> {
+        for resolution in Resolution::ALL {
+            let Some(retention_seconds) = resolution.retention_seconds() else { continue };
+            sqlx::query("DELETE FROM price_history WHERE resolution = $1 AND bucket_start < $2")
+                .bind(resolution.as_str())
+                .bind(to_datetime(now - retention_seconds))
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Tokens named in PRICE_HISTORY_WATCHLIST as "<chain>:<token>", e.g. "solana:<mint>,ethereum:<address>"
+    fn watchlist(&self) -> Vec<TokenRef> {
+        let watchlist = std::env::var("PRICE_HISTORY_WATCHLIST").unwrap_or_default();
+        watchlist
+            .split(',')
+            .filter_map(|entry| entry.trim().split_once(':'))
+            .filter_map(|(chain, token_id)| {
+                let token = self.price_service.token_ref(chain, token_id, None);
+                if token.is_none() {
+                    tracing::warn!("Ignoring unsupported price history watchlist entry {}:{}", chain, token_id);
+                }
+                token
+            })
+            .collect()
+    }
+
+    // (chain, token, decimals) for every token in the last portfolio of each tracked wallet, with a None token for
+    // the chain's native asset. Wallets registered as "evm" are tracked on every EVM chain.
+    async fn get_held_tokens(&self) -> Result<Vec<(String, Option<String>, Option<u8>)>> {
+        let rows = sqlx::query(
+            r#"
+            WITH tracked AS (
+                SELECT b.chain, b.data
+                FROM cached_balances b
+                WHERE EXISTS (
+                    SELECT 1 FROM user_wallets w
+                    WHERE w.address = b.address AND (w.chain = b.chain OR w.chain = 'evm')
+                )
+            )
+            SELECT DISTINCT chain, NULL::VARCHAR AS token_id, NULL::SMALLINT AS decimals FROM tracked
+            UNION
+            SELECT DISTINCT tracked.chain, t.token ->> 'mint_or_address', (t.token ->> 'decimals')::SMALLINT
+            FROM tracked, jsonb_array_elements(tracked.data -> 'tokens') AS t(token)
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut held = Vec::new();
+        for row in rows {
+            let decimals: Option<i16> = row.try_get("decimals")?;
+            held.push((row.try_get("chain")?, row.try_get("token_id")?, decimals.map(|d| d as u8)));
+        }
+        Ok(held)
+    }
+}
+
+// OHLC candles for every bucket with at least one sample, in time order; buckets without samples are left out
+pub fn aggregate_candles(resolution: Resolution, mut samples: Vec<(i64, Decimal)>) -> Vec<Candle> {
+    let mut buckets: BTreeMap<i64, Candle> = BTreeMap::new();
+    samples.sort_by_key(|(timestamp, _)| *timestamp);
+    for (timestamp, price) in samples {
+        let bucket_start = resolution.bucket_start(timestamp);
+        buckets
+            .entry(bucket_start)
+            .and_modify(|candle| {
+                candle.high = candle.high.max(price);
+                candle.low = candle.low.min(price);
+                candle.close = price;
+            })
+            .or_insert(Candle { timestamp: bucket_start, open: price, high: price, low: price, close: price });
+    }
+    buckets.into_values().collect()
+}
+
+fn to_datetime(timestamp: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(timestamp, 0).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_on_a_boundary_open_the_next_bucket() {
+        let candles = aggregate_candles(Resolution::FiveMinutes, vec![(0, Decimal::from(1)), (299, Decimal::from(2)), (300, Decimal::from(3))]);
+        let starts: Vec<i64> = candles.iter().map(|c| c.timestamp).collect();
+        assert_eq!(starts, vec![0, 300]);
+        assert_eq!(candles[0].close, Decimal::from(2));
+        assert_eq!(candles[1].open, Decimal::from(3));
+    }
+
+    #[test]
+    fn aggregates_ohlc_in_time_order() {
+        // Providers don't guarantee ordering, so open and close follow the timestamps rather than the input
+        let candles = aggregate_candles(Resolution::Hour, vec![(3500, Decimal::from(4)), (3600 * 10, Decimal::from(9)), (100, Decimal::from(2)), (1800, Decimal::from(7)), (2000, Decimal::from(1))]);
+        assert_eq!(candles.len(), 2);
+        let first = &candles[0];
+        assert_eq!((first.timestamp, first.open, first.high, first.low, first.close), (0, Decimal::from(2), Decimal::from(7), Decimal::from(1), Decimal::from(4)));
+        let second = &candles[1];
+        assert_eq!((second.timestamp, second.open, second.high, second.low, second.close), (36000, Decimal::from(9), Decimal::from(9), Decimal::from(9), Decimal::from(9)));
+    }
+
+    #[test]
+    fn empty_buckets_are_left_out() {
+        assert!(aggregate_candles(Resolution::Day, Vec::new()).is_empty());
+
+        let candles = aggregate_candles(Resolution::Day, vec![(86400 + 5, Decimal::from(1)), (86400 * 4 + 5, Decimal::from(2))]);
+        let starts: Vec<i64> = candles.iter().map(|c| c.timestamp).collect();
+        assert_eq!(starts, vec![86400, 86400 * 4]);
+    }
+}
@@ -0,0 +1,764 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use ethers::abi::{AbiDecode, AbiEncode};
+use ethers::contract::abigen;
+use ethers::contract::multicall_contract::Call3;
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address as EthAddress, U256};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::config::{Config, DexConfig, EvmChainConfig};
+use crate::services::ethereum_client;
+use crate::utils::amounts;
+
+abigen!(
+    UniswapV2Router,
+    r#"[
+        function factory() external view returns (address)
+    ]"#
+);
+
+abigen!(
+    UniswapV2Factory,
+    r#"[
+        function getPair(address tokenA, address tokenB) external view returns (address pair)
+    ]"#
+);
+
+abigen!(
+    UniswapV2Pair,
+    r#"[
+        function token0() external view returns (address)
+        function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast)
+    ]"#
+);
+
+// Ids per price request; Jupiter rejects more than 100, and CoinGecko and DefiLlama URLs get unwieldy past that
+const JUPITER_PRICE_BATCH_SIZE: usize = 100;
+const COINGECKO_PRICE_BATCH_SIZE: usize = 100;
+const DEFILLAMA_PRICE_BATCH_SIZE: usize = 100;
+
+// Every wrapped native token the DEX provider prices through (WETH, WMATIC, WBNB) has 18 decimals
+const WRAPPED_NATIVE_DECIMALS: u8 = 18;
+
+// A token as price providers see it
+#[derive(Debug, Clone)]
+pub struct TokenRef {
+    pub chain: String,
+    pub id: String, // Cache key: mint, contract address, or symbol for native assets
+    pub address: Option<String>, // On-chain address; the wrapped token for native assets where one exists
+    pub coingecko_id: Option<String>,
+    pub decimals: Option<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PriceQuote {
+    pub price_usd: Option<Decimal>, // None when no source prices the token, as opposed to a price of zero
+    pub price_change_24h: Option<f64>,
+    pub confidence: Option<String>,
+    pub source: Option<String>, // Name of the provider that supplied the price
+}
+
+// A source of USD prices. Tokens a provider has no price for are left out of its result;
+// an error means the provider itself failed and the next one in the chain's priority list is tried.
+#[async_trait]
+pub trait PriceProvider: Send + Sync {
+    // Name used in PRICE_PROVIDERS and recorded as the source of cached prices
+    fn name(&self) -> &'static str;
+
+    // Whether the provider looks the token up at all. Only tokens a provider actually queried are treated as
+    // unpriced when it answers without them.
+    fn can_price(&self, chain: &str, token: &TokenRef) -> bool;
+
+    async fn fetch_prices(&self, chain: &str, tokens: &[TokenRef]) -> Result<HashMap<String, PriceQuote>>;
+
+    // Providers with historical data override this and fetch_history
+    fn has_history(&self) -> bool {
+        false
+    }
+
+    // (unix seconds, USD price) samples covering the last `days` days, or the full history for None.
+    // Providers without historical data return nothing.
+    async fn fetch_history(&self, _chain: &str, _token: &TokenRef, _days: Option<u32>) -> Result<Vec<(i64, Decimal)>> {
+        Ok(Vec::new())
+    }
+}
+
+// Sends the batches of one price lookup concurrently, bounded so a large portfolio doesn't trip
+// rate limits; responses come back in request order
+async fn send_batched(requests: Vec<reqwest::RequestBuilder>) -> Result<Vec<Value>> {
+    let concurrency: usize = std::env::var("PRICE_BATCH_CONCURRENCY")
+        .unwrap_or_else(|_| "4".to_string())
+        .parse()
+        .unwrap_or(4);
+
+    stream::iter(requests)
+        .map(|request| async move {
+            let response: Value = request.send().await?.error_for_status()?.json().await?;
+            Ok::<_, anyhow::Error>(response)
+        })
+        .buffered(concurrency.max(1))
+        .try_collect()
+        .await
+}
+
+// The configured CoinGecko API, shared by price lookups and token metadata
+#[derive(Debug, Clone)]
+pub struct CoinGeckoApi {
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl CoinGeckoApi {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            base_url: config.coingecko_api_url.trim_end_matches('/').to_string(),
+            api_key: config.coingecko_api_key.clone(),
+        }
+    }
+
+    // Endpoint URL, with the demo API key when one is configured
+    pub fn url(&self, path: &str) -> String {
+        let url = format!("{}/{}", self.base_url, path);
+        match &self.api_key {
+            Some(key) => {
+                let separator = if url.contains('?') { '&' } else { '?' };
+                format!("{}{}x_cg_demo_api_key={}", url, separator, key)
+            }
+            None => url,
+        }
+    }
+}
+
+// Prices tokens by CoinGecko id on any chain
+pub struct CoinGeckoProvider {
+    http: reqwest::Client,
+    api: CoinGeckoApi,
+}
+
+impl CoinGeckoProvider {
+    pub fn new(http: reqwest::Client, api: CoinGeckoApi) -> Self {
+        Self { http, api }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for CoinGeckoProvider {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    fn can_price(&self, _chain: &str, token: &TokenRef) -> bool {
+        token.coingecko_id.is_some()
+    }
+
+    fn has_history(&self) -> bool {
+        true
+    }
+
+    async fn fetch_prices(&self, _chain: &str, tokens: &[TokenRef]) -> Result<HashMap<String, PriceQuote>> {
+        let mut ids: Vec<&str> = Vec::new();
+        for id in tokens.iter().filter_map(|t| t.coingecko_id.as_deref()) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let requests = ids
+            .chunks(COINGECKO_PRICE_BATCH_SIZE)
+            .map(|chunk| {
+                self.http.get(self.api.url(&format!(
+                    "simple/price?ids={}&vs_currencies=usd&include_24hr_change=true",
+                    chunk.join(",")
+                )))
+            })
+            .collect();
+        let responses = send_batched(requests).await?;
+        let entries: HashMap<&str, &Value> = responses
+            .iter()
+            .filter_map(|response| response.as_object())
+            .flatten()
+            .map(|(id, entry)| (id.as_str(), entry))
+            .collect();
+
+        let mut prices = HashMap::new();
+        for token in tokens {
+            let Some(entry) = token.coingecko_id.as_deref().and_then(|id| entries.get(id)) else { continue };
+            if let Some(price) = amounts::parse_decimal(&entry["usd"]) {
+                prices.insert(token.id.clone(), PriceQuote {
+                    price_usd: Some(price),
+                    price_change_24h: entry["usd_24h_change"].as_f64(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(prices)
+    }
+
+    // market_chart returns 5-minute samples for one day, hourly up to 90 days and daily beyond that
+    async fn fetch_history(&self, _chain: &str, token: &TokenRef, days: Option<u32>) -> Result<Vec<(i64, Decimal)>> {
+        let Some(id) = token.coingecko_id.as_deref() else {
+            return Ok(Vec::new());
+        };
+        let days = days.map(|d| d.to_string()).unwrap_or_else(|| "max".to_string());
+        let url = self.api.url(&format!("coins/{}/market_chart?vs_currency=usd&days={}", id, days));
+        let response: Value = self.http.get(&url).send().await?.error_for_status()?.json().await?;
+
+        // Each sample is [unix milliseconds, price]
+        let samples = response["prices"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|sample| Some(((sample[0].as_f64()? / 1000.0) as i64, amounts::parse_decimal(&sample[1])?)))
+            .collect();
+
+        Ok(samples)
+    }
+}
+
+// Prices SPL mints through the Jupiter price API
+pub struct JupiterProvider {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl JupiterProvider {
+    pub fn new(http: reqwest::Client, base_url: String, api_key: Option<String>) -> Self {
+        Self { http, base_url, api_key }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for JupiterProvider {
+    fn name(&self) -> &'static str {
+        "jupiter"
+    }
+
+    fn can_price(&self, chain: &str, token: &TokenRef) -> bool {
+        chain == "solana" && token.address.is_some()
+    }
+
+    async fn fetch_prices(&self, chain: &str, tokens: &[TokenRef]) -> Result<HashMap<String, PriceQuote>> {
+        let mut prices = HashMap::new();
+        if chain != "solana" {
+            return Ok(prices);
+        }
+
+        let priced: Vec<&TokenRef> = tokens.iter().filter(|t| t.address.is_some()).collect();
+        let chunks: Vec<&[&TokenRef]> = priced.chunks(JUPITER_PRICE_BATCH_SIZE).collect();
+        let requests = chunks
+            .iter()
+            .map(|chunk| {
+                let mints: Vec<&str> = chunk.iter().filter_map(|t| t.address.as_deref()).collect();
+                let request = self
+                    .http
+                    .get(&self.base_url)
+                    .query(&[("ids", mints.join(",")), ("showExtraInfo", "true".to_string())]);
+                match &self.api_key {
+                    Some(api_key) => request.header("x-api-key", api_key),
+                    None => request,
+                }
+            })
+            .collect();
+        let responses = send_batched(requests).await?;
+
+        for (chunk, response) in chunks.into_iter().zip(responses) {
+            // Each requested mint maps to null or {"price": "<decimal string>", "extraInfo": {"confidenceLevel": ...}}
+            for token in chunk {
+                let Some(mint) = token.address.as_deref() else { continue };
+                let entry = &response["data"][mint];
+                if let Some(price) = amounts::parse_decimal(&entry["price"]) {
+                    prices.insert(token.id.clone(), PriceQuote {
+                        price_usd: Some(price),
+                        confidence: entry["extraInfo"]["confidenceLevel"].as_str().map(|c| c.to_string()),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+
+        Ok(prices)
+    }
+}
+
+// Prices tokens by "<chain>:<address>", or "coingecko:<id>" for assets without an address,
+// through a DefiLlama-compatible coins API
+pub struct DefiLlamaProvider {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl DefiLlamaProvider {
+    pub fn new(http: reqwest::Client, base_url: &str) -> Self {
+        Self { http, base_url: base_url.trim_end_matches('/').to_string() }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for DefiLlamaProvider {
+    fn name(&self) -> &'static str {
+        "defillama"
+    }
+
+    fn can_price(&self, _chain: &str, token: &TokenRef) -> bool {
+        token.address.is_some() || token.coingecko_id.is_some()
+    }
+
+    async fn fetch_prices(&self, chain: &str, tokens: &[TokenRef]) -> Result<HashMap<String, PriceQuote>> {
+        // Coin keys come back lowercased, so they are matched case-insensitively
+        let mut keys: Vec<(String, &TokenRef)> = Vec::new();
+        for token in tokens {
+            let key = match (&token.address, &token.coingecko_id) {
+                (Some(address), _) => format!("{}:{}", chain, address),
+                (None, Some(id)) => format!("coingecko:{}", id),
+                (None, None) => continue,
+            };
+            keys.push((key, token));
+        }
+        if keys.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let coins: Vec<&str> = keys.iter().map(|(key, _)| key.as_str()).collect();
+        let requests = coins
+            .chunks(DEFILLAMA_PRICE_BATCH_SIZE)
+            .map(|chunk| self.http.get(format!("{}/prices/current/{}", self.base_url, chunk.join(","))))
+            .collect();
+        let responses = send_batched(requests).await?;
+
+        let coins: HashMap<String, &Value> = responses
+            .iter()
+            .filter_map(|response| response["coins"].as_object())
+            .flatten()
+            .map(|(key, coin)| (key.to_lowercase(), coin))
+            .collect();
+
+        let mut prices = HashMap::new();
+        for (key, token) in keys {
+            let Some(coin) = coins.get(&key.to_lowercase()) else { continue };
+            if let Some(price) = amounts::parse_decimal(&coin["price"]) {
+                prices.insert(token.id.clone(), PriceQuote {
+                    price_usd: Some(price),
+                    confidence: coin["confidence"].as_f64().map(|c| c.to_string()),
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(prices)
+    }
+}
+
+// Prices tokens from the reserves of their Uniswap V2-compatible pairs with the chain's USD stablecoin,
+// or with the wrapped native token valued through its own stablecoin pair. Pairs whose USD-side reserve
+// is below DEX_MIN_RESERVE_USD are ignored, since thin pools are trivial to push to any price.
+pub struct DexProvider {
+    chains: HashMap<String, (Arc<Provider<Http>>, EvmChainConfig, DexConfig)>,
+    factories: Mutex<HashMap<String, EthAddress>>,
+}
+
+impl DexProvider {
+    pub fn new(chains: &[EvmChainConfig]) -> Self {
+        let mut dex_chains = HashMap::new();
+        for chain in chains {
+            let Some(dex) = &chain.dex else { continue };
+            match Provider::<Http>::try_from(chain.rpc_url.as_str()) {
+                Ok(provider) => {
+                    dex_chains.insert(chain.name.clone(), (Arc::new(provider), chain.clone(), dex.clone()));
+                }
+                Err(e) => tracing::warn!("DEX pricing disabled on {}: {}", chain.name, e),
+            }
+        }
+        Self { chains: dex_chains, factories: Mutex::new(HashMap::new()) }
+    }
+
+    // The router's factory never changes, so it is looked up once per chain
+    async fn factory(&self, provider: &Arc<Provider<Http>>, chain: &str, router: EthAddress) -> Result<EthAddress> {
+        if let Some(factory) = self.factories.lock().unwrap_or_else(|e| e.into_inner()).get(chain) {
+            return Ok(*factory);
+        }
+        let factory = UniswapV2Router::new(router, provider.clone()).factory().call().await?;
+        self.factories.lock().unwrap_or_else(|e| e.into_inner()).insert(chain.to_string(), factory);
+        Ok(factory)
+    }
+}
+
+#[async_trait]
+impl PriceProvider for DexProvider {
+    fn name(&self) -> &'static str {
+        "dex"
+    }
+
+    fn can_price(&self, chain: &str, token: &TokenRef) -> bool {
+        self.chains.contains_key(chain)
+            && token.decimals.is_some()
+            && token.address.as_deref().is_some_and(|a| a.parse::<EthAddress>().is_ok())
+    }
+
+    async fn fetch_prices(&self, chain: &str, tokens: &[TokenRef]) -> Result<HashMap<String, PriceQuote>> {
+        let mut prices = HashMap::new();
+        let Some((provider, chain_config, dex)) = self.chains.get(chain) else {
+            return Ok(prices);
+        };
+        let targets: Vec<(&TokenRef, EthAddress, u8)> = tokens
+            .iter()
+            .filter_map(|t| Some((t, t.address.as_deref()?.parse().ok()?, t.decimals?)))
+            .collect();
+        if targets.is_empty() {
+            return Ok(prices);
+        }
+
+        let min_reserve_usd: Decimal = std::env::var("DEX_MIN_RESERVE_USD")
+            .unwrap_or_else(|_| "50000".to_string())
+            .parse()
+            .unwrap_or(Decimal::from(50000));
+        let wrapped_native: EthAddress = dex.wrapped_native.parse()?;
+        let usd_token: EthAddress = dex.usd_token.parse()?;
+        let factory = self.factory(provider, chain, dex.router.parse()?).await?;
+
+        // One aggregate3 round finds every pair, a second reads their reserves
+        let mut wanted = vec![(wrapped_native, usd_token)];
+        for (_, address, _) in &targets {
+            wanted.push((*address, usd_token));
+            wanted.push((*address, wrapped_native));
+        }
+        let pair_calls = wanted
+            .iter()
+            .map(|(a, b)| Call3 {
+                target: factory,
+                allow_failure: true,
+                call_data: GetPairCall { token_a: *a, token_b: *b }.encode().into(),
+            })
+            .collect();
+        let pair_addresses: Vec<Option<EthAddress>> = ethereum_client::aggregate(chain_config, provider, pair_calls)
+            .await?
+            .into_iter()
+            .map(|data| data.and_then(|d| GetPairReturn::decode(d).ok()).map(|r| r.pair).filter(|pair| !pair.is_zero()))
+            .collect();
+
+        let pairs: Vec<EthAddress> = pair_addresses.iter().flatten().copied().collect();
+        let reserve_calls = pairs
+            .iter()
+            .flat_map(|pair| {
+                [
+                    Call3 { target: *pair, allow_failure: true, call_data: Token0Call.encode().into() },
+                    Call3 { target: *pair, allow_failure: true, call_data: GetReservesCall.encode().into() },
+                ]
+            })
+            .collect();
+        let results = ethereum_client::aggregate(chain_config, provider, reserve_calls).await?;
+        let mut reserves: HashMap<EthAddress, (EthAddress, U256, U256)> = HashMap::new();
+        for (pair, result) in pairs.iter().zip(results.chunks(2)) {
+            let token0 = result[0].as_ref().and_then(|d| Token0Return::decode(d).ok()).map(|r| r.0);
+            let pair_reserves = result[1].as_ref().and_then(|d| GetReservesReturn::decode(d).ok());
+            if let (Some(token0), Some(pair_reserves)) = (token0, pair_reserves) {
+                reserves.insert(*pair, (token0, U256::from(pair_reserves.reserve_0), U256::from(pair_reserves.reserve_1)));
+            }
+        }
+
+        // (token reserve, quote reserve) of the pair for (token, quote), as whole units. Reserves too
+        // large for a Decimal only occur in pairs of worthless tokens, which are skipped.
+        let pair_depth = |index: usize, token: EthAddress, token_decimals: u8, quote_decimals: u8| {
+            let pair = pair_addresses.get(index).copied().flatten()?;
+            let (token0, reserve0, reserve1) = reserves.get(&pair)?;
+            let (token_reserve, quote_reserve) = if *token0 == token { (reserve0, reserve1) } else { (reserve1, reserve0) };
+            let token_reserve = amounts::to_decimal(&amounts::format_units(&token_reserve.to_string(), token_decimals))?;
+            let quote_reserve = amounts::to_decimal(&amounts::format_units(&quote_reserve.to_string(), quote_decimals))?;
+            (!token_reserve.is_zero()).then_some((token_reserve, quote_reserve))
+        };
+
+        // The wrapped native price comes from its own stablecoin pair and must clear the same bar
+        let native_price = pair_depth(0, wrapped_native, WRAPPED_NATIVE_DECIMALS, dex.usd_decimals)
+            .filter(|(_, usd_reserve)| *usd_reserve >= min_reserve_usd)
+            .and_then(|(native_reserve, usd_reserve)| usd_reserve.checked_div(native_reserve));
+
+        for (i, (token, address, decimals)) in targets.iter().enumerate() {
+            let price = if *address == usd_token {
+                Some(Decimal::ONE)
+            } else if *address == wrapped_native {
+                native_price
+            } else {
+                // Of the pairs deep enough to trust, the one with the larger USD-side reserve sets the price
+                let direct = pair_depth(1 + 2 * i, *address, *decimals, dex.usd_decimals)
+                    .and_then(|(token_reserve, usd_reserve)| Some((usd_reserve.checked_div(token_reserve)?, usd_reserve)));
+                let routed = native_price.and_then(|native_price| {
+                    let (token_reserve, native_reserve) = pair_depth(2 + 2 * i, *address, *decimals, WRAPPED_NATIVE_DECIMALS)?;
+                    Some((
+                        native_reserve.checked_div(token_reserve)?.checked_mul(native_price)?,
+                        native_reserve.checked_mul(native_price)?,
+                    ))
+                });
+                [direct, routed]
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, depth_usd)| *depth_usd >= min_reserve_usd)
+                    .max_by(|a, b| a.1.cmp(&b.1))
+                    .map(|(price, _)| price.normalize())
+            };
+            if let Some(price) = price {
+                prices.insert(token.id.clone(), PriceQuote {
+                    price_usd: Some(price),
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(prices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::rpc_stub;
+    use ethers::contract::multicall_contract::{Aggregate3Call, Aggregate3Return, Result as MulticallResult};
+    use ethers::types::Bytes;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::str::FromStr;
+
+    const MULTICALL: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+    const ROUTER: &str = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D";
+    const FACTORY: &str = "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f";
+    const WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+    const USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+    const DEEP: &str = "0x1111111111111111111111111111111111111111";
+    const THIN: &str = "0x2222222222222222222222222222222222222222";
+
+    fn address(s: &str) -> EthAddress {
+        s.parse().unwrap()
+    }
+
+    // Pairs as (token a, token b, reserve a, reserve b), in raw units
+    fn pairs() -> Vec<(EthAddress, EthAddress, u128, u128)> {
+        let e18 = 10u128.pow(18);
+        vec![
+            // 4,000 WETH against 10M USDC: 2,500 USD per WETH
+            (address(WETH), address(USDC), 4_000 * e18, 10_000_000 * 10u128.pow(6)),
+            // 1M DEEP against 100k USDC (0.10) and against 100 WETH (0.25, with 250k USD behind it)
+            (address(DEEP), address(USDC), 1_000_000 * e18, 100_000 * 10u128.pow(6)),
+            (address(DEEP), address(WETH), 1_000_000 * e18, 100 * e18),
+            // 10 USDC of liquidity is below the minimum reserve
+            (address(THIN), address(USDC), 1_000 * e18, 10 * 10u128.pow(6)),
+        ]
+    }
+
+    fn pair_address(index: usize) -> EthAddress {
+        EthAddress::from_low_u64_be(0xbeef0000 + index as u64)
+    }
+
+    fn answer_call(target: EthAddress, data: &[u8]) -> Option<Bytes> {
+        if target == address(ROUTER) && data == FactoryCall.encode().as_slice() {
+            return Some(FactoryReturn(address(FACTORY)).encode().into());
+        }
+        if target == address(FACTORY) {
+            let call = GetPairCall::decode(data).ok()?;
+            let pair = pairs()
+                .iter()
+                .position(|(a, b, ..)| (*a, *b) == (call.token_a, call.token_b) || (*b, *a) == (call.token_a, call.token_b))
+                .map(pair_address)
+                .unwrap_or_default();
+            return Some(GetPairReturn { pair }.encode().into());
+        }
+        let (_, (a, b, reserve_a, reserve_b)) = pairs().into_iter().enumerate().find(|(i, _)| pair_address(*i) == target)?;
+        // Pairs sort their tokens by address
+        let (token0, reserve0, reserve1) = if a < b { (a, reserve_a, reserve_b) } else { (b, reserve_b, reserve_a) };
+        if data == Token0Call.encode().as_slice() {
+            return Some(Token0Return(token0).encode().into());
+        }
+        if data == GetReservesCall.encode().as_slice() {
+            return Some(GetReservesReturn { reserve_0: reserve0, reserve_1: reserve1, block_timestamp_last: 0 }.encode().into());
+        }
+        None
+    }
+
+    async fn dex_provider(eth_calls: Arc<AtomicUsize>) -> DexProvider {
+        let url = rpc_stub::spawn(move |method, params| {
+            match method {
+                "eth_chainId" => Some(json!("0x1")),
+                "eth_call" => {
+                    eth_calls.fetch_add(1, Ordering::SeqCst);
+                    let tx = &params[0];
+                    let target = address(tx["to"].as_str()?);
+                    let data: Bytes = tx.get("input").or(tx.get("data"))?.as_str()?.parse().ok()?;
+                    let output = if target == address(MULTICALL) {
+                        let calls = Aggregate3Call::decode(&data).ok()?.calls;
+                        let results = calls
+                            .into_iter()
+                            .map(|call| match answer_call(call.target, &call.call_data) {
+                                Some(return_data) => MulticallResult { success: true, return_data },
+                                None => MulticallResult { success: false, return_data: Bytes::new() },
+                            })
+                            .collect();
+                        Aggregate3Return { return_data: results }.encode().into()
+                    } else {
+                        answer_call(target, &data)?
+                    };
+                    Some(json!(output))
+                }
+                _ => None,
+            }
+        })
+        .await;
+
+        DexProvider::new(&[EvmChainConfig {
+            chain_id: 1,
+            name: "ethereum".to_string(),
+            rpc_url: url,
+            native_symbol: "ETH".to_string(),
+            native_price_id: None,
+            tokens: Vec::new(),
+            discovery_start_block: 0,
+            discovery_lookback_blocks: None,
+            native_history_blocks: None,
+            multicall_address: MULTICALL.to_string(),
+            opensea_chain: None,
+            dex: Some(DexConfig {
+                router: ROUTER.to_string(),
+                wrapped_native: WETH.to_string(),
+                usd_token: USDC.to_string(),
+                usd_decimals: 6,
+            }),
+        }])
+    }
+
+    fn token(address: &str) -> TokenRef {
+        TokenRef {
+            chain: "ethereum".to_string(),
+            id: address.to_lowercase(),
+            address: Some(address.to_string()),
+            coingecko_id: None,
+            decimals: Some(18),
+        }
+    }
+
+    #[tokio::test]
+    async fn prices_from_the_deepest_trusted_pair_in_two_batches() {
+        let eth_calls = Arc::new(AtomicUsize::new(0));
+        let dex = dex_provider(eth_calls.clone()).await;
+        let tokens: Vec<TokenRef> = [USDC, WETH, DEEP, THIN].into_iter().map(token).collect();
+
+        let prices = dex.fetch_prices("ethereum", &tokens).await.unwrap();
+        let price = |address: &str| prices.get(&address.to_lowercase()).and_then(|q| q.price_usd);
+        assert_eq!(price(USDC), Some(Decimal::ONE));
+        assert_eq!(price(WETH), Some(Decimal::from(2500)));
+        assert_eq!(price(DEEP), Some(Decimal::new(25, 2)));
+        assert_eq!(price(THIN), None);
+        // factory(), then one aggregate3 for the pairs and one for their reserves
+        assert_eq!(eth_calls.load(Ordering::SeqCst), 3);
+
+        // The factory is only looked up once
+        dex.fetch_prices("ethereum", &tokens).await.unwrap();
+        assert_eq!(eth_calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn skips_chains_and_tokens_it_cannot_price() {
+        let dex = dex_provider(Arc::new(AtomicUsize::new(0))).await;
+        let mut unpriceable = token(DEEP);
+        unpriceable.decimals = None;
+        assert!(!dex.can_price("ethereum", &unpriceable));
+        assert!(!dex.can_price("polygon", &token(DEEP)));
+        assert!(dex.can_price("ethereum", &token(DEEP)));
+        assert!(dex.fetch_prices("polygon", &[token(DEEP)]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn splits_defillama_lookups_into_batches_of_one_hundred() {
+        use axum::{extract::Path, routing::get, Json, Router};
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let app = Router::new().route(
+            "/prices/current/:coins",
+            get(move |Path(coins): Path<String>| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let coins: serde_json::Map<String, Value> =
+                    coins.split(',').map(|coin| (coin.to_string(), json!({ "price": 1.0 }))).collect();
+                async move { Json(json!({ "coins": coins })) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let tokens: Vec<TokenRef> = (0..250)
+            .map(|i| TokenRef {
+                chain: "ethereum".to_string(),
+                id: format!("0x{:040x}", i),
+                address: Some(format!("0x{:040x}", i)),
+                coingecko_id: None,
+                decimals: Some(18),
+            })
+            .collect();
+        let prices = DefiLlamaProvider::new(reqwest::Client::new(), &url)
+            .fetch_prices("ethereum", &tokens)
+            .await
+            .unwrap();
+
+        assert_eq!(prices.len(), 250);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn reads_jupiter_string_prices_and_confidence_in_batches() {
+        use axum::{extract::Query, routing::get, Json, Router};
+
+        // The first mint is unknown to Jupiter and comes back null; the rest are priced as strings
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let seen = batches.clone();
+        let app = Router::new().route(
+            "/",
+            get(move |Query(params): Query<HashMap<String, String>>| {
+                let mints: Vec<String> = params["ids"].split(',').map(|m| m.to_string()).collect();
+                assert_eq!(params.get("showExtraInfo").map(String::as_str), Some("true"));
+                seen.lock().unwrap().push(mints.len());
+                let data: serde_json::Map<String, Value> = mints
+                    .into_iter()
+                    .map(|mint| {
+                        let entry = match mint.as_str() {
+                            "mint0" => Value::Null,
+                            "mint1" => json!({ "price": "0.000000000123456789", "extraInfo": { "confidenceLevel": "low" } }),
+                            _ => json!({ "price": "1.5", "extraInfo": { "confidenceLevel": "high" } }),
+                        };
+                        (mint, entry)
+                    })
+                    .collect();
+                async move { Json(json!({ "data": data })) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let tokens: Vec<TokenRef> = (0..150)
+            .map(|i| TokenRef {
+                chain: "solana".to_string(),
+                id: format!("mint{}", i),
+                address: Some(format!("mint{}", i)),
+                coingecko_id: None,
+                decimals: Some(6),
+            })
+            .collect();
+        let prices = JupiterProvider::new(reqwest::Client::new(), url, None)
+            .fetch_prices("solana", &tokens)
+            .await
+            .unwrap();
+
+        assert_eq!(*batches.lock().unwrap(), vec![JUPITER_PRICE_BATCH_SIZE, 150 - JUPITER_PRICE_BATCH_SIZE]);
+        assert_eq!(prices.len(), 149);
+        assert!(!prices.contains_key("mint0"));
+        assert_eq!(prices["mint1"].price_usd, Some(Decimal::from_str("0.000000000123456789").unwrap()));
+        assert_eq!(prices["mint1"].confidence.as_deref(), Some("low"));
+        assert_eq!(prices["mint149"].price_usd, Some(Decimal::new(15, 1)));
+        assert_eq!(prices["mint149"].confidence.as_deref(), Some("high"));
+    }
+}
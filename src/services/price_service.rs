@@ -1,137 +1,384 @@
 use anyhow::Result;
+use ethers::types::Address as EthAddress;
+use ethers::utils::to_checksum;
+use rust_decimal::Decimal;
 use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::config::{Config, EvmChainConfig};
 use crate::services::cache::CacheService;
+use crate::services::price_provider::{
+    CoinGeckoApi, CoinGeckoProvider, DefiLlamaProvider, DexProvider, JupiterProvider, PriceProvider, PriceQuote, TokenRef,
+};
+use crate::services::token_registry::TokenRegistry;
+use crate::utils::{amounts, helpers};
+
+// Wrapped SOL mint, which price APIs use for native SOL
+const WRAPPED_SOL_MINT: &str = "So11111111111111111111111111111111111111112";
+
+// Upstream price APIs get a bounded wait so one slow provider can fall through to the next
+const PRICE_PROVIDER_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Clone)]
 pub struct PriceService {
     cache: CacheService,
+    token_registry: TokenRegistry,
+    config: Config,
+    providers: Arc<Vec<Arc<dyn PriceProvider>>>,
+    rate_limited_until: Arc<Mutex<HashMap<&'static str, Instant>>>,
 }
 
 impl PriceService {
-    pub fn new(cache: CacheService) -> Self {
-        Self { cache }
+    pub fn new(cache: CacheService, token_registry: TokenRegistry, config: Config) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(PRICE_PROVIDER_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        let providers: Vec<Arc<dyn PriceProvider>> = vec![
+            Arc::new(CoinGeckoProvider::new(http.clone(), CoinGeckoApi::new(&config))),
+            Arc::new(JupiterProvider::new(http.clone(), config.jupiter_price_api_url.clone(), config.jupiter_api_key.clone())),
+            Arc::new(DefiLlamaProvider::new(http, &config.defillama_api_url)),
+            Arc::new(DexProvider::new(&config.evm_chains)),
+        ];
+        for (chain, names) in &config.price_providers {
+            for name in names.iter().filter(|n| !providers.iter().any(|p| p.name() == n.as_str())) {
+                tracing::warn!("Unknown price provider '{}' configured for {}", name, chain);
+            }
+        }
+
+        Self {
+            cache,
+            token_registry,
+            config,
+            providers: Arc::new(providers),
+            rate_limited_until: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn get_solana_price(&self, token_id: &str) -> Result<Decimal> {
+        let quote = self.get_price(&self.solana_token(token_id)).await?;
+        Ok(quote.price_usd.unwrap_or_default())
     }
 
-    pub async fn get_solana_price(&self, token_id: &str) -> Result<f64> {
+    pub async fn get_bitcoin_price_with_change(&self) -> Result<(Decimal, Option<f64>)> {
+        let quote = self.get_price(&bitcoin_token()).await?;
+        Ok((quote.price_usd.unwrap_or_default(), quote.price_change_24h))
+    }
+
+    pub async fn get_price(&self, token: &TokenRef) -> Result<PriceQuote> {
+        let mut quotes = self.get_prices(std::slice::from_ref(token)).await?;
+        Ok(quotes.pop().unwrap_or_default())
+    }
+
+    // Quotes in the order given. Cache misses are fetched with one request per provider for each chain
+    // and written back in a single upsert.
+    pub async fn get_prices(&self, tokens: &[TokenRef]) -> Result<Vec<PriceQuote>> {
         // Check cache first
-        if let Some((price, _)) = self.cache.get_price_with_change(token_id, "solana").await? {
-            return Ok(price);
+        let mut quotes = self.cache.get_price_quotes(tokens).await?;
+        let mut missing: Vec<&TokenRef> = Vec::new();
+        for token in tokens {
+            let cached = quotes.contains_key(&(token.chain.clone(), token.id.clone()));
+            if !cached && !missing.iter().any(|m| m.chain == token.chain && m.id == token.id) {
+                missing.push(token);
+            }
         }
 
-        // Cache miss - fetch from Jupiter API
-        let (price, change) = self.fetch_jupiter_price(token_id).await?;
-        
-        // Store in cache
-        let ttl_seconds = std::env::var("CACHE_TTL_SECONDS")
-            .unwrap_or_else(|_| "30".to_string())
-            .parse()
-            .unwrap_or(30);
-        self.cache.set_price_with_change(token_id, "solana", price, change, ttl_seconds).await?;
+        if !missing.is_empty() {
+            // Cache miss - fetch from the providers, one chain at a time
+            let mut chains: Vec<&str> = Vec::new();
+            for token in &missing {
+                if !chains.contains(&token.chain.as_str()) {
+                    chains.push(&token.chain);
+                }
+            }
+
+            let mut fetched: Vec<(&TokenRef, PriceQuote)> = Vec::new();
+            for chain in chains {
+                let chain_tokens: Vec<&TokenRef> = missing.iter().copied().filter(|t| t.chain == chain).collect();
+                let (mut prices, answered) = self.fetch_quotes(chain, &chain_tokens).await;
+
+                // Tokens are only cached as unpriced when a provider that looked them up answered
+                for token in chain_tokens {
+                    match prices.remove(&token.id) {
+                        Some(quote) => fetched.push((token, quote)),
+                        None if answered.contains(&token.id) => fetched.push((token, PriceQuote::default())),
+                        None => {}
+                    }
+                }
+            }
 
-        Ok(price)
+            // Store in cache
+            let ttl_seconds = std::env::var("CACHE_TTL_SECONDS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30);
+            let entries: Vec<(&TokenRef, &PriceQuote)> = fetched.iter().map(|(token, quote)| (*token, quote)).collect();
+            if let Err(e) = self.cache.set_price_quotes(&entries, ttl_seconds).await {
+                tracing::warn!("Failed to cache {} prices: {}", entries.len(), e);
+            }
+            for (token, quote) in fetched {
+                quotes.insert((token.chain.clone(), token.id.clone()), quote);
+            }
+        }
+
+        Ok(tokens
+            .iter()
+            .map(|token| quotes.get(&(token.chain.clone(), token.id.clone())).cloned().unwrap_or_default())
+            .collect())
     }
 
-    pub async fn get_solana_price_with_change(&self, token_id: &str) -> Result<(f64, Option<f64>)> {
-        // Check cache first
-        if let Some(cached) = self.cache.get_price_with_change(token_id, "solana").await? {
-            return Ok(cached);
+    // Historical (unix seconds, USD price) samples from the first provider in the chain's priority list that has any.
+    // Fails when no provider answered, so a rate-limited backfill isn't mistaken for a token without history.
+    pub async fn get_price_history(&self, token: &TokenRef, days: Option<u32>) -> Result<Vec<(i64, Decimal)>> {
+        let mut answered = false;
+        for name in self.config.price_providers(&token.chain) {
+            let Some(provider) = self.providers.iter().find(|p| p.name() == name) else { continue };
+            if !provider.has_history() || !provider.can_price(&token.chain, token) || self.is_rate_limited(provider.name()) {
+                continue;
+            }
+
+            match provider.fetch_history(&token.chain, token, days).await {
+                Ok(samples) if !samples.is_empty() => return Ok(samples),
+                Ok(_) => answered = true,
+                Err(e) => {
+                    if is_rate_limit_error(&e) {
+                        self.set_rate_limited(provider.name());
+                    }
+                    tracing::warn!("Price provider {} failed to return history for {} on {}: {}", name, token.id, token.chain, e);
+                }
+            }
         }
 
-        // Cache miss - fetch from Jupiter API
-        let (price, change) = self.fetch_jupiter_price(token_id).await?;
-        
-        // Store in cache
-        let ttl_seconds = std::env::var("CACHE_TTL_SECONDS")
-            .unwrap_or_else(|_| "30".to_string())
+        if !answered {
+            return Err(anyhow::anyhow!("No price provider returned history for {} on {}", token.id, token.chain));
+        }
+        Ok(Vec::new())
+    }
+
+    // Each provider is asked only for the tokens earlier ones couldn't price; also returns the ids of tokens
+    // that some provider looked up and answered for
+    async fn fetch_quotes(&self, chain: &str, tokens: &[&TokenRef]) -> (HashMap<String, PriceQuote>, HashSet<String>) {
+        let mut quotes: HashMap<String, PriceQuote> = HashMap::new();
+        let mut answered = HashSet::new();
+        let mut remaining: Vec<TokenRef> = tokens.iter().map(|t| (*t).clone()).collect();
+
+        for name in self.config.price_providers(chain) {
+            if remaining.is_empty() {
+                break;
+            }
+            let Some(provider) = self.providers.iter().find(|p| p.name() == name) else { continue };
+            if self.is_rate_limited(provider.name()) {
+                tracing::debug!("Skipping rate-limited price provider {}", name);
+                continue;
+            }
+
+            match provider.fetch_prices(chain, &remaining).await {
+                Ok(prices) => {
+                    answered.extend(remaining.iter().filter(|t| provider.can_price(chain, t)).map(|t| t.id.clone()));
+                    for (id, mut quote) in prices {
+                        quote.source = Some(provider.name().to_string());
+                        quotes.insert(id, quote);
+                    }
+                    remaining.retain(|t| !quotes.contains_key(&t.id));
+                }
+                Err(e) => {
+                    if is_rate_limit_error(&e) {
+                        self.set_rate_limited(provider.name());
+                    }
+                    tracing::warn!("Price provider {} failed for {} tokens on {}: {}", name, remaining.len(), chain, e);
+                }
+            }
+        }
+
+        (quotes, answered)
+    }
+
+    fn is_rate_limited(&self, provider: &str) -> bool {
+        let rate_limited_until = self.rate_limited_until.lock().unwrap_or_else(|e| e.into_inner());
+        rate_limited_until.get(provider).is_some_and(|until| *until > Instant::now())
+    }
+
+    // A provider that answers 429 is skipped for a while rather than retried on every lookup
+    fn set_rate_limited(&self, provider: &'static str) {
+        let cooldown_seconds = std::env::var("PRICE_PROVIDER_COOLDOWN_SECONDS")
+            .unwrap_or_else(|_| "60".to_string())
             .parse()
-            .unwrap_or(30);
-        self.cache.set_price_with_change(token_id, "solana", price, change, ttl_seconds).await?;
+            .unwrap_or(60);
+        let mut rate_limited_until = self.rate_limited_until.lock().unwrap_or_else(|e| e.into_inner());
+        rate_limited_until.insert(provider, Instant::now() + Duration::from_secs(cooldown_seconds));
+    }
 
-        Ok((price, change))
+    // SOL is keyed by symbol and priced as wrapped SOL; listed mints carry their CoinGecko id
+    pub fn solana_token(&self, token_id: &str) -> TokenRef {
+        if token_id == "SOL" || token_id == WRAPPED_SOL_MINT {
+            return TokenRef {
+                chain: "solana".to_string(),
+                id: token_id.to_string(),
+                address: Some(WRAPPED_SOL_MINT.to_string()),
+                coingecko_id: Some("solana".to_string()),
+                decimals: Some(9),
+            };
+        }
+        let listed = self.token_registry.get(token_id);
+        TokenRef {
+            chain: "solana".to_string(),
+            id: token_id.to_string(),
+            address: Some(token_id.to_string()),
+            coingecko_id: listed.as_ref().and_then(|t| t.coingecko_id.clone()),
+            decimals: listed.and_then(|t| t.decimals),
+        }
     }
 
-    pub async fn get_ethereum_price(&self, token_id: &str) -> Result<f64> {
-        // Check cache first
-        if let Some((price, _)) = self.cache.get_price_with_change(token_id, "ethereum").await? {
-            return Ok(price);
+    // The price lookup for a token as identified in API paths and portfolios: a mint, contract address or native symbol.
+    // None for unsupported chains and malformed addresses.
+    pub fn token_ref(&self, chain: &str, token_id: &str, decimals: Option<u8>) -> Option<TokenRef> {
+        let native = self.native_token(chain)?;
+        if token_id.eq_ignore_ascii_case(&native.id) {
+            return Some(native);
+        }
+        match chain {
+            "solana" => token_id.parse::<Pubkey>().is_ok().then(|| self.solana_token(token_id)),
+            "bitcoin" => None,
+            _ => {
+                let evm_chain = self.config.evm_chains.iter().find(|c| c.name == chain)?;
+                let token_addr = token_id.parse::<EthAddress>().ok()?;
+                Some(evm_token(evm_chain, &token_addr, decimals))
+            }
         }
+    }
 
-        // Cache miss - fetch from CoinGecko
-        let (price, change) = self.fetch_coingecko_price(token_id).await?;
-        
-        // Store in cache
-        let ttl_seconds = std::env::var("CACHE_TTL_SECONDS")
-            .unwrap_or_else(|_| "30".to_string())
-            .parse()
-            .unwrap_or(30);
-        self.cache.set_price_with_change(token_id, "ethereum", price, change, ttl_seconds).await?;
+    pub fn native_token(&self, chain: &str) -> Option<TokenRef> {
+        match chain {
+            "solana" => Some(self.solana_token("SOL")),
+            "bitcoin" => Some(bitcoin_token()),
+            _ => self.config.evm_chains.iter().find(|c| c.name == chain).map(evm_native_token),
+        }
+    }
 
-        Ok(price)
+    // NFT valuation needs an OpenSea API key; without one NFTs are listed but not valued
+    pub fn nft_floor_prices_enabled(&self) -> bool {
+        std::env::var("OPENSEA_API_KEY").is_ok()
     }
 
-    pub async fn get_ethereum_price_with_change(&self, token_id: &str) -> Result<(f64, Option<f64>)> {
-        // Check cache first
-        if let Some(cached) = self.cache.get_price_with_change(token_id, "ethereum").await? {
-            return Ok(cached);
+    // USD floor price of the collection at `contract_address`, or None if it has no listed floor
+    pub async fn get_nft_floor_price(&self, chain: &EvmChainConfig, contract_address: &str) -> Result<Option<Decimal>> {
+        let (Some(opensea_chain), Ok(api_key)) = (chain.opensea_chain.as_deref(), std::env::var("OPENSEA_API_KEY")) else {
+            return Ok(None);
+        };
+        let cache_key = format!("nft-floor:{}", contract_address.to_lowercase());
+
+        // Check cache first; a zero price caches a collection without a floor
+        if let Some((price, _)) = self.cache.get_price_with_change(&cache_key, &chain.name).await? {
+            return Ok(Some(price).filter(|p| !p.is_zero()));
         }
 
-        // Cache miss - fetch from CoinGecko
-        let (price, change) = self.fetch_coingecko_price(token_id).await?;
-        
+        // Cache miss - fetch from OpenSea and convert from the currency the floor is listed in
+        let price = match self.fetch_opensea_floor(&api_key, opensea_chain, contract_address).await? {
+            Some((floor, symbol)) => {
+                let currency = if symbol.eq_ignore_ascii_case(&chain.native_symbol) {
+                    evm_native_token(chain)
+                } else if symbol.eq_ignore_ascii_case("ETH") || symbol.eq_ignore_ascii_case("WETH") {
+                    TokenRef {
+                        chain: chain.name.clone(),
+                        id: "ETH".to_string(),
+                        address: None,
+                        coingecko_id: Some("ethereum".to_string()),
+                        decimals: None,
+                    }
+                } else {
+                    TokenRef { chain: chain.name.clone(), id: symbol.to_uppercase(), address: None, coingecko_id: None, decimals: None }
+                };
+                let currency_price = self.get_price(&currency).await?.price_usd.unwrap_or_default();
+                floor.checked_mul(currency_price).unwrap_or_default().normalize()
+            }
+            None => Decimal::ZERO,
+        };
+
         // Store in cache
-        let ttl_seconds = std::env::var("CACHE_TTL_SECONDS")
-            .unwrap_or_else(|_| "30".to_string())
+        let ttl_seconds = std::env::var("NFT_FLOOR_PRICE_TTL_SECONDS")
+            .unwrap_or_else(|_| "600".to_string()) // 10 minutes for floor prices
             .parse()
-            .unwrap_or(30);
-        self.cache.set_price_with_change(token_id, "ethereum", price, change, ttl_seconds).await?;
+            .unwrap_or(600);
+        self.cache.set_price_with_change(&cache_key, &chain.name, price, None, ttl_seconds).await?;
 
-        Ok((price, change))
+        Ok(Some(price).filter(|p| !p.is_zero()))
     }
 
-    async fn fetch_jupiter_price(&self, token_id: &str) -> Result<(f64, Option<f64>)> {
-        // For SOL, use CoinGecko
-        if token_id == "SOL" || token_id == "So11111111111111111111111111111111111111112" {
-            return self.fetch_coingecko_price("solana").await;
-        }
+    // Returns the floor price and the symbol of the currency it is listed in
+    async fn fetch_opensea_floor(&self, api_key: &str, opensea_chain: &str, contract_address: &str) -> Result<Option<(Decimal, String)>> {
+        let base_url = std::env::var("OPENSEA_API_URL")
+            .unwrap_or_else(|_| "https://api.opensea.io/api/v2".to_string());
+        let client = reqwest::Client::builder()
+            .timeout(PRICE_PROVIDER_TIMEOUT)
+            .build()?;
 
-        // TODO: Implement Jupiter price API call for other tokens
-        // For now, return placeholder
-        Ok((0.0, None))
-    }
-
-    async fn fetch_coingecko_price(&self, token_id: &str) -> Result<(f64, Option<f64>)> {
-        // Map common token addresses to CoinGecko IDs
-        let coingecko_id = match token_id.to_lowercase().as_str() {
-            "eth" | "ethereum" => "ethereum",
-            "sol" | "solana" => "solana",
-            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48" => "usd-coin",
-            "0xdac17f958d2ee523a2206206994597c13d831ec7" => "tether",
-            "0x6b175474e89094c44da98b954eedeac495271d0f" => "dai",
-            "0x2260fac5e5542a773aa44fbcfedf7c193bc2c599" => "wrapped-bitcoin",
-            "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2" => "ethereum",
-            "0x1f9840a85d5af5bf1d1762f925bdaddc4201f984" => "uniswap",
-            "0x514910771af9ca656af840dff83e8264ecf986ca" => "chainlink",
-            "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9" => "aave",
-            _ => return Ok((0.0, None)), // Unknown token
+        // Floor prices are kept per collection, so the contract is first mapped to its collection slug
+        let response = client
+            .get(format!("{}/chain/{}/contract/{}", base_url, opensea_chain, contract_address))
+            .header("x-api-key", api_key)
+            .send()
+            .await?;
+        // Contracts OpenSea doesn't index have no floor; other errors are retried on the next lookup
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let contract: Value = response.error_for_status()?.json().await?;
+        let Some(slug) = contract["collection"].as_str().filter(|s| !s.is_empty()) else {
+            return Ok(None);
         };
 
-        let api_key = std::env::var("COINGECKO_API_KEY").ok();
-        let url = if let Some(key) = api_key {
-            format!("https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies=usd&include_24hr_change=true&x_cg_demo_api_key={}", coingecko_id, key)
-        } else {
-            format!("https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies=usd&include_24hr_change=true", coingecko_id)
-        };
-        
-        let response: Value = reqwest::get(&url).await?.json().await?;
-        
-        let price = response[coingecko_id]["usd"]
-            .as_f64()
-            .unwrap_or(0.0);
-        
-        let change_24h = response[coingecko_id]["usd_24h_change"]
-            .as_f64();
-
-        Ok((price, change_24h))
+        let stats: Value = client
+            .get(format!("{}/collections/{}/stats", base_url, slug))
+            .header("x-api-key", api_key)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let floor = amounts::parse_decimal(&stats["total"]["floor_price"]).filter(|p| p.is_sign_positive() && !p.is_zero());
+        let symbol = stats["total"]["floor_price_symbol"].as_str().unwrap_or("ETH");
+
+        Ok(floor.map(|floor| (floor, symbol.to_string())))
+    }
+}
+
+// The chain's native currency, keyed by symbol and priced through its wrapped token where there is one
+pub fn evm_native_token(chain: &EvmChainConfig) -> TokenRef {
+    TokenRef {
+        chain: chain.name.clone(),
+        id: chain.native_symbol.clone(),
+        address: chain.wrapped_native().map(|a| a.to_string()),
+        coingecko_id: chain.native_price_id.clone(),
+        decimals: Some(18),
     }
 }
 
+// An ERC-20 token, keyed by its lowercase address like everything else stored per EVM token
+pub fn evm_token(chain: &EvmChainConfig, token_addr: &EthAddress, decimals: Option<u8>) -> TokenRef {
+    let token_address = to_checksum(token_addr, None);
+    TokenRef {
+        chain: chain.name.clone(),
+        id: helpers::evm_address_key(token_addr),
+        coingecko_id: chain.price_id(&token_address).map(|id| id.to_string()),
+        address: Some(token_address),
+        decimals,
+    }
+}
+
+fn bitcoin_token() -> TokenRef {
+    TokenRef {
+        chain: "bitcoin".to_string(),
+        id: "BTC".to_string(),
+        address: None,
+        coingecko_id: Some("bitcoin".to_string()),
+        decimals: Some(8),
+    }
+}
+
+fn is_rate_limit_error(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .is_some_and(|status| status == reqwest::StatusCode::TOO_MANY_REQUESTS)
+}
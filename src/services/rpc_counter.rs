@@ -0,0 +1,49 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use solana_client::client_error::Result as ClientResult;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Counts the Solana RPC requests made on behalf of one portfolio request. The client is shared,
+// so each count is scoped to the future passed to `count` rather than kept on the sender.
+tokio::task_local! {
+    static RPC_CALLS: Arc<AtomicUsize>;
+}
+
+pub struct CountingSender {
+    inner: RpcClient,
+}
+
+impl CountingSender {
+    pub fn new(inner: RpcClient) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl RpcSender for CountingSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        // Calls outside a counted scope, such as transaction history lookups, are not tallied
+        let _ = RPC_CALLS.try_with(|calls| calls.fetch_add(1, Ordering::Relaxed));
+        self.inner.send(request, params).await
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+// Runs `future` and returns its output with the number of RPC requests it made
+pub async fn count<F: Future>(future: F) -> (F::Output, usize) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let output = RPC_CALLS.scope(calls.clone(), future).await;
+    (output, calls.load(Ordering::Relaxed))
+}
@@ -0,0 +1,32 @@
+use axum::{extract::State, routing::post, Json, Router};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+// In-process JSON-RPC server for tests. Each call is answered with handler(method, params);
+// None becomes a JSON-RPC error. Batch requests are answered element by element.
+
+type Handler = Arc<dyn Fn(&str, &Value) -> Option<Value> + Send + Sync>;
+
+pub async fn spawn(handler: impl Fn(&str, &Value) -> Option<Value> + Send + Sync + 'static) -> String {
+    let handler: Handler = Arc::new(handler);
+    let app = Router::new().route("/", post(answer)).with_state(handler);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    url
+}
+
+async fn answer(State(handler): State<Handler>, Json(request): Json<Value>) -> Json<Value> {
+    match request.as_array() {
+        Some(batch) => Json(batch.iter().map(|call| answer_call(&handler, call)).collect()),
+        None => Json(answer_call(&handler, &request)),
+    }
+}
+
+fn answer_call(handler: &Handler, call: &Value) -> Value {
+    let method = call["method"].as_str().unwrap_or_default();
+    match handler(method, &call["params"]) {
+        Some(result) => json!({ "jsonrpc": "2.0", "id": call["id"], "result": result }),
+        None => json!({ "jsonrpc": "2.0", "id": call["id"], "error": { "code": -32601, "message": format!("unsupported {}", method) } }),
+    }
+}
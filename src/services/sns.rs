@@ -0,0 +1,98 @@
+use solana_sdk::hash::hashv;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+// Solana Name Service account derivation and decoding, following the Bonfida SDK
+
+const NAME_PROGRAM_ID: Pubkey = pubkey!("namesLPneVptA9Z5rqUDD9tMTWEJwofgaYwp8cawRkX");
+const SOL_TLD_AUTHORITY: Pubkey = pubkey!("58PwtjSDuFHuUkYjH9BYnnQKHfwo9reZhC2zMJv9JPkx");
+const REVERSE_LOOKUP_CLASS: Pubkey = pubkey!("33m47vH6Eav6jr5Ry86XjhRft2jRBLDnDgPSHoquXi2Z");
+const FAVOURITE_DOMAIN_PROGRAM_ID: Pubkey = pubkey!("85iDfUvr3HJyLM2LcYBx8yRHwyyc7ZuKQbHwFMbgQCZ5");
+
+const HASH_PREFIX: &str = "SPL Name Service";
+
+// Name registry accounts start with the parent name, owner and class, followed by the record data
+const NAME_HEADER_LEN: usize = 96;
+
+// Name account for "bonfida.sol" or the subdomain "dex.bonfida.sol"
+pub fn domain_key(domain: &str) -> Option<Pubkey> {
+    let labels: Vec<&str> = domain.strip_suffix(".sol")?.split('.').collect();
+    if labels.iter().any(|label| label.is_empty()) {
+        return None;
+    }
+
+    match labels.as_slice() {
+        [name] => Some(name_account_key(name, None, &SOL_TLD_AUTHORITY)),
+        [sub, name] => {
+            let parent = name_account_key(name, None, &SOL_TLD_AUTHORITY);
+            // Subdomain names are prefixed with a zero byte
+            Some(name_account_key(&format!("\0{}", sub), None, &parent))
+        }
+        _ => None,
+    }
+}
+
+// Account holding the domain a wallet has chosen as its primary name
+pub fn favourite_domain_key(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"favourite_domain", owner.as_ref()], &FAVOURITE_DOMAIN_PROGRAM_ID).0
+}
+
+// Account mapping a name account back to its human-readable name
+pub fn reverse_key(name_account: &Pubkey) -> Pubkey {
+    name_account_key(&name_account.to_string(), Some(&REVERSE_LOOKUP_CLASS), &Pubkey::default())
+}
+
+pub fn owner(name_account_data: &[u8]) -> Option<Pubkey> {
+    let bytes: [u8; 32] = name_account_data.get(32..64)?.try_into().ok()?;
+    Some(Pubkey::new_from_array(bytes))
+}
+
+// Only second-level .sol domains are resolved back to names
+pub fn is_sol_domain(name_account_data: &[u8]) -> bool {
+    name_account_data.get(..32) == Some(SOL_TLD_AUTHORITY.as_ref())
+}
+
+// Favourite domain accounts are a one-byte tag followed by the name account
+pub fn parse_favourite_domain(data: &[u8]) -> Option<Pubkey> {
+    let bytes: [u8; 32] = data.get(1..33)?.try_into().ok()?;
+    Some(Pubkey::new_from_array(bytes))
+}
+
+// Reverse records hold a borsh string (u32 length prefix) after the header
+pub fn parse_reverse(data: &[u8]) -> Option<String> {
+    let record = data.get(NAME_HEADER_LEN..)?;
+    let len = u32::from_le_bytes(record.get(..4)?.try_into().ok()?) as usize;
+    let name = std::str::from_utf8(record.get(4..4 + len)?).ok()?;
+    Some(name.to_string()).filter(|n| !n.is_empty())
+}
+
+fn name_account_key(name: &str, class: Option<&Pubkey>, parent: &Pubkey) -> Pubkey {
+    let hashed_name = hashv(&[HASH_PREFIX.as_bytes(), name.as_bytes()]).to_bytes();
+    let class = class.copied().unwrap_or_default();
+    Pubkey::find_program_address(&[&hashed_name, class.as_ref(), parent.as_ref()], &NAME_PROGRAM_ID).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_known_domain_key() {
+        assert_eq!(domain_key("bonfida.sol"), Some(pubkey!("Crf8hzfthWGbGbLTVCiqRqV5MVnbpHB1L9KQMd6gsinb")));
+    }
+
+    #[test]
+    fn subdomains_hang_off_the_parent_domain() {
+        let sub = domain_key("dex.bonfida.sol").unwrap();
+        assert_ne!(Some(sub), domain_key("bonfida.sol"));
+        assert_eq!(sub, name_account_key("\0dex", None, &domain_key("bonfida.sol").unwrap()));
+    }
+
+    #[test]
+    fn rejects_non_sol_and_empty_labels() {
+        assert_eq!(domain_key("bonfida.eth"), None);
+        assert_eq!(domain_key(".sol"), None);
+        assert_eq!(domain_key("dex..sol"), None);
+        assert_eq!(domain_key("a.b.bonfida.sol"), None);
+    }
+}
@@ -1,20 +1,73 @@
 use anyhow::Result;
-use solana_client::rpc_client::RpcClient;
+use async_trait::async_trait;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClientConfig};
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
 use solana_client::rpc_request::TokenAccountsFilter;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_account_decoder::{UiAccountData, UiAccountEncoding};
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::native_token::lamports_to_sol;
+use solana_sdk::signature::Signature;
+use solana_sdk::commitment_config::CommitmentConfig;
 use solana_transaction_status::UiTransactionEncoding;
-use solana_program_pack::Pack;
-use spl_token::state::Account as TokenAccount;
+use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use spl_token_2022::extension::interest_bearing_mint::InterestBearingConfig;
+use spl_token_2022::extension::metadata_pointer::MetadataPointer;
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use spl_token_2022::state::{Account as TokenAccount, Mint as MintState};
+use spl_token_metadata_interface::state::TokenMetadata;
+use solana_stake_interface::state::StakeStateV2;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use futures::stream::{self, StreamExt};
+use serde_json::Value;
+use crate::types::nft::{Nft, NftCollection, NftPortfolio};
 use crate::types::portfolio::PortfolioResponse;
-use crate::types::token::Token;
+use crate::types::token::{Token, TokenExtensions};
+use crate::types::staking::StakePosition;
+use crate::types::transaction::{Transaction, TransactionPage};
+use crate::services::chain_client::ChainClient;
+use crate::services::{metaplex, rpc_counter, sns};
+use crate::services::solana_tx_parser::parse_transaction;
+use crate::services::price_provider::PriceQuote;
 use crate::services::price_service::PriceService;
-use crate::services::metadata_service::MetadataService;
+use crate::services::metadata_service::{self, MetadataService, OnchainTokenMetadata, SolanaTokenMetadata};
+use crate::utils::{amounts, helpers};
 use crate::config::Config;
 
+// Native SOL uses 9 decimals (lamports)
+pub const SOL_DECIMALS: u8 = 9;
+
+// getMultipleAccounts accepts at most 100 pubkeys per request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+// Largest page getAssetsByOwner returns
+const DAS_PAGE_SIZE: usize = 1000;
+
+// Byte offsets of the authorized staker and withdrawer in a stake account
+const STAKER_OFFSET: usize = 12;
+const WITHDRAWER_OFFSET: usize = 44;
+
+struct TokenBalance {
+    mint: String,
+    raw_amount: u64,
+    token_program: Pubkey,
+}
+
+#[derive(Clone, Default)]
+struct Token2022Mint {
+    decimals: u8,
+    interest: Option<InterestBearingConfig>,
+    extensions: TokenExtensions,
+}
+
 #[derive(Clone)]
 pub struct SolanaClient {
-    rpc_url: String,
+    rpc_client: Arc<RpcClient>,
     price_service: PriceService,
     metadata_service: MetadataService,
     config: Config,
@@ -23,8 +76,19 @@ pub struct SolanaClient {
 #[allow(deprecated)]
 impl SolanaClient {
     pub fn new(rpc_url: String, price_service: PriceService, metadata_service: MetadataService, config: Config) -> Self {
-        Self {
+        let commitment = CommitmentConfig::from_str(&config.solana_commitment).unwrap_or_else(|_| {
+            tracing::warn!("Unknown SOLANA_COMMITMENT '{}', using confirmed", config.solana_commitment);
+            CommitmentConfig::confirmed()
+        });
+        let http_client = RpcClient::new_with_timeout_and_commitment(
             rpc_url,
+            Duration::from_secs(config.solana_rpc_timeout_seconds),
+            commitment,
+        );
+        let rpc_client = RpcClient::new_sender(rpc_counter::CountingSender::new(http_client), RpcClientConfig::with_commitment(commitment));
+
+        Self {
+            rpc_client: Arc::new(rpc_client),
             price_service,
             metadata_service,
             config,
@@ -32,126 +96,929 @@ impl SolanaClient {
     }
 
     pub async fn fetch_portfolio(&self, address: &str) -> Result<PortfolioResponse> {
+        // At most 3 + 2 stake queries + 2 epoch lookups + the NFT lookups + ceil(Token-2022 mints / 100)
+        // + ceil(uncached mints / 50) + ceil(unresolved mints / 100) RPC calls per portfolio
+        let (portfolio, rpc_calls) = rpc_counter::count(self.build_portfolio(address)).await;
+        tracing::debug!("Solana portfolio for {} used {} RPC calls", address, rpc_calls);
+        portfolio
+    }
+
+    async fn build_portfolio(&self, address: &str) -> Result<PortfolioResponse> {
         let pubkey = address.parse::<Pubkey>()?;
-        let rpc_client = RpcClient::new(self.rpc_url.clone());
+        let rpc_client = &self.rpc_client;
         
         // Fetch SOL balance
-        let lamports = rpc_client.get_balance(&pubkey)?;
-        #[allow(deprecated)]
-        let sol_balance = lamports_to_sol(lamports);
-        
-        // Get SOL price
-        let (sol_price, sol_price_change) = self.price_service.get_solana_price_with_change("SOL").await.unwrap_or((0.0, None));
-        let sol_value = sol_balance * sol_price;
+        let lamports = rpc_client.get_balance(&pubkey).await?;
+        let sol_balance = amounts::to_f64(&lamports.to_string(), SOL_DECIMALS);
 
-        // Fetch SPL token balances
-        let mut tokens: Vec<Token> = Vec::new();
-        
-        let token_program_id = spl_token::ID;
-        let token_accounts = rpc_client.get_token_accounts_by_owner(
-            &pubkey,
-            TokenAccountsFilter::ProgramId(token_program_id),
-        )?;
-
-        for account in token_accounts {
-            // Parse the pubkey from string
-            let account_pubkey: Pubkey = account.pubkey.parse()?;
-            // Get account data - get_account returns Result<Account>
-            if let Ok(account_info) = rpc_client.get_account(&account_pubkey) {
-                // Account data is in account_info.data which is Vec<u8>
-                // Try to unpack the token account using spl-token's unpack
-                if let Ok(token_account) = TokenAccount::unpack(&account_info.data) {
-                    let mint = token_account.mint.to_string();
-                    // Decimals are stored in the mint account, not the token account
-                    // For now, use a default of 9 (common for most tokens) or fetch from metadata
-                    // In production, you'd fetch the mint account to get the actual decimals
-                    let decimals = 9u8; // Default, should fetch from mint account
-                    let amount = token_account.amount as f64 / 10_f64.powi(decimals as i32);
-                    
-                    if amount > 0.0 {
-                        // Get token price
-                        let (price, price_change) = self.price_service.get_solana_price_with_change(&mint).await.unwrap_or((0.0, None));
-                        let value = amount * price;
-                        
-                        // Get metadata
-                        let (name, logo_uri) = self.metadata_service.get_solana_metadata(&mint).await.unwrap_or((None, None));
-                        
-                        // Get symbol from metadata or use mint address
-                        let symbol = if let Some(ref n) = name {
-                            n.split_whitespace().next().unwrap_or(&mint[..8]).to_string()
-                        } else {
-                            mint.chars().take(8).collect()
-                        };
+        // Fetch SPL token balances from both the legacy Token program and Token-2022
+        let (mut balances, mut decimals_by_mint) = self.get_token_balances(&pubkey).await?;
 
-                        tokens.push(Token {
-                            symbol,
-                            mint_or_address: mint,
-                            amount,
-                            decimals,
-                            price_usd: price,
-                            value_usd: value,
-                            name,
-                            logo_uri,
-                            price_change_24h: price_change,
-                        });
-                    }
-                }
+        // NFTs are listed in their own section rather than as tokens
+        let nfts = self.get_nft_collections(&pubkey, &balances, &decimals_by_mint, false).await?;
+        balances.retain(|b| !nfts.iter().any(|c| c.nfts.iter().any(|n| n.token_id == b.mint)));
+
+        // Token-2022 mints are read in full for their extensions, which also carries decimals
+        let token_2022_mints: Vec<String> = balances
+            .iter()
+            .filter(|b| b.token_program == spl_token_2022::ID)
+            .map(|b| b.mint.clone())
+            .collect();
+        let mut token_2022_info = HashMap::new();
+        if !token_2022_mints.is_empty() {
+            let info = self.get_token_2022_mints(&token_2022_mints).await?;
+            for (mint, mint_info) in &info {
+                decimals_by_mint.insert(mint.clone(), mint_info.decimals);
             }
+            token_2022_info = info;
         }
 
+        // Names and symbols come from the mint and its metadata account, which also carry decimals
+        let mints: Vec<String> = balances.iter().map(|b| b.mint.clone()).collect();
+        let metadata_by_mint = self.get_token_metadata(&mints).await?;
+        for (mint, metadata) in &metadata_by_mint {
+            if let Some(decimals) = metadata.decimals {
+                decimals_by_mint.entry(mint.clone()).or_insert(decimals);
+            }
+        }
+
+        // Only binary-encoded accounts lack decimals; those mints are looked up in batches
+        let unresolved: Vec<String> = balances
+            .iter()
+            .filter(|b| !decimals_by_mint.contains_key(&b.mint))
+            .map(|b| b.mint.clone())
+            .collect();
+        if !unresolved.is_empty() {
+            let resolved = self.get_mint_decimals(&unresolved).await?;
+            decimals_by_mint.extend(resolved);
+        }
+
+        // SOL and all mints are priced in one batch rather than per token
+        let mut price_refs = vec![self.price_service.solana_token("SOL")];
+        price_refs.extend(mints.iter().map(|mint| self.price_service.solana_token(mint)));
+        let mut quotes = self.price_service.get_prices(&price_refs).await.unwrap_or_else(|e| {
+            tracing::warn!("Failed to fetch token prices for {}: {}", address, e);
+            vec![PriceQuote::default(); price_refs.len()]
+        });
+        let prices: HashMap<String, PriceQuote> = mints.into_iter().zip(quotes.split_off(1)).collect();
+        let sol_price = quotes.pop().and_then(|quote| quote.price_usd).unwrap_or_default();
+        let sol_value = amounts::usd_value(&lamports.to_string(), SOL_DECIMALS, sol_price);
+
+        let tokens: Vec<Token> = balances
+            .into_iter()
+            .filter_map(|balance| {
+                // Skip tokens whose mint account could not be read rather than guessing decimals
+                let decimals = decimals_by_mint.get(&balance.mint).copied()?;
+                let mint_info = token_2022_info.get(&balance.mint).cloned();
+                let metadata = metadata_by_mint.get(&balance.mint).cloned().unwrap_or_default();
+                let price = prices.get(&balance.mint).cloned().unwrap_or_default();
+                Some(build_token(balance, decimals, mint_info, metadata, price))
+            })
+            .collect();
+
+        // Stake accounts are optional; some RPC providers restrict getProgramAccounts
+        let staking = match self.fetch_stake_positions(&pubkey, sol_price).await {
+            Ok(staking) => staking,
+            Err(e) => {
+                tracing::warn!("Failed to fetch stake accounts for {}: {}", address, e);
+                Vec::new()
+            }
+        };
+
         let total_tokens_count = tokens.len() + if sol_balance > 0.0 { 1 } else { 0 };
+        let total_value_usd = amounts::sum(
+            std::iter::once(sol_value)
+                .chain(tokens.iter().map(|t| t.value_usd))
+                .chain(staking.iter().map(|s| s.value_usd)),
+        );
         let last_updated = chrono::Utc::now().to_rfc3339();
 
         Ok(PortfolioResponse {
             chain: "solana".to_string(),
+            chain_id: None,
             address: address.to_string(),
+            name: None,
             native_balance: sol_balance,
+            native_raw_balance: lamports.to_string(),
+            native_decimals: SOL_DECIMALS,
             native_price_usd: sol_price,
             native_value_usd: sol_value,
             tokens,
+            staking,
+            nfts,
             total_tokens_count: Some(total_tokens_count),
+            nft_value_usd: None,
+            total_value_usd: Some(total_value_usd),
             last_updated: Some(last_updated),
+            ..Default::default()
+        })
+    }
+
+    pub async fn fetch_nfts(&self, address: &str) -> Result<NftPortfolio> {
+        let pubkey = address.parse::<Pubkey>()?;
+        let (balances, decimals_by_mint) = self.get_token_balances(&pubkey).await?;
+        let collections = self.get_nft_collections(&pubkey, &balances, &decimals_by_mint, true).await?;
+
+        Ok(NftPortfolio {
+            chain: "solana".to_string(),
+            chain_id: None,
+            address: address.to_string(),
+            total_nfts_count: collections.iter().map(|c| c.nfts.len()).sum(),
+            collections,
+            total_value_usd: None,
+            last_updated: chrono::Utc::now().to_rfc3339(),
+            ..Default::default()
         })
     }
 
-    pub async fn fetch_transactions(&self, address: &str, limit: usize) -> Result<Vec<crate::types::transaction::Transaction>> {
+    // Non-zero balances from both token programs, with decimals for accounts returned as jsonParsed
+    async fn get_token_balances(&self, owner: &Pubkey) -> Result<(Vec<TokenBalance>, HashMap<String, u8>)> {
+        let mut balances: Vec<TokenBalance> = Vec::new();
+        let mut decimals_by_mint: HashMap<String, u8> = HashMap::new();
+        for token_program in [spl_token::ID, spl_token_2022::ID] {
+            let token_accounts = self.rpc_client.get_token_accounts_by_owner(
+                owner,
+                TokenAccountsFilter::ProgramId(token_program),
+            ).await?;
+
+            // Use the account data returned by the owner query directly instead of re-fetching each account
+            for account in token_accounts {
+                if let Some((mint, raw_amount, decimals)) = parse_token_account(&account.account.data) {
+                    if raw_amount > 0 {
+                        if let Some(decimals) = decimals {
+                            decimals_by_mint.insert(mint.clone(), decimals);
+                        }
+                        balances.push(TokenBalance { mint, raw_amount, token_program });
+                    }
+                }
+            }
+        }
+
+        Ok((balances, decimals_by_mint))
+    }
+
+    // NFTs grouped by verified collection: single-unit balances of 0-decimal, supply-1 mints plus compressed NFTs
+    async fn get_nft_collections(
+        &self,
+        owner: &Pubkey,
+        balances: &[TokenBalance],
+        decimals_by_mint: &HashMap<String, u8>,
+        fetch_offchain: bool,
+    ) -> Result<Vec<NftCollection>> {
+        let candidates: Vec<Pubkey> = balances
+            .iter()
+            .filter(|b| b.raw_amount == 1 && decimals_by_mint.get(&b.mint).is_none_or(|d| *d == 0))
+            .filter_map(|b| b.mint.parse().ok())
+            .collect();
+        let mut nfts = self.get_token_nfts(&candidates).await?;
+
+        // Compressed NFTs live in merkle trees rather than token accounts, so they need a DAS-compatible API
+        match self.get_compressed_nfts(owner).await {
+            Ok(compressed) => nfts.extend(compressed),
+            Err(e) => tracing::warn!("Failed to fetch compressed NFTs for {}: {}", owner, e),
+        }
+        if nfts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let offchain = self.get_offchain_metadata(&nfts, fetch_offchain).await;
+
+        let mut collection_keys: Vec<Pubkey> = Vec::new();
+        for key in nfts.iter().filter_map(|n| n.collection.as_deref()?.parse::<Pubkey>().ok()) {
+            if !collection_keys.contains(&key) {
+                collection_keys.push(key);
+            }
+        }
+        let collection_names = self.get_collection_names(&collection_keys).await?;
+
+        let mut collections: Vec<NftCollection> = Vec::new();
+        for nft in nfts {
+            // NFTs outside a verified collection are listed as a collection of their own
+            let address = nft.collection.clone().unwrap_or_else(|| nft.mint.clone());
+            let metadata = offchain.get(&nft.mint);
+            let nft = build_nft(nft, metadata);
+            match collections.iter_mut().find(|c| c.address == address) {
+                Some(collection) => collection.nfts.push(nft),
+                None => collections.push(NftCollection {
+                    name: collection_names.get(&address).cloned(),
+                    address,
+                    floor_price_usd: None,
+                    value_usd: None,
+                    nfts: vec![nft],
+                    ..Default::default()
+                }),
+            }
+        }
+
+        Ok(collections)
+    }
+
+    // Reads each candidate mint with its metadata PDA, keeping those with a supply of one
+    async fn get_token_nfts(&self, mints: &[Pubkey]) -> Result<Vec<metaplex::NftInfo>> {
+        let mut nfts = Vec::new();
+        for chunk in mints.chunks(MAX_MULTIPLE_ACCOUNTS / 2) {
+            let keys: Vec<Pubkey> = chunk.iter().flat_map(|mint| [*mint, metaplex::metadata_key(mint)]).collect();
+            let accounts = self.rpc_client.get_multiple_accounts(&keys).await?;
+
+            for (mint, pair) in chunk.iter().zip(accounts.chunks(2)) {
+                let Some(mint_account) = &pair[0] else { continue };
+                let Ok(state) = StateWithExtensions::<MintState>::unpack(&mint_account.data) else { continue };
+                if state.base.decimals != 0 || state.base.supply != 1 {
+                    continue;
+                }
+                let metadata = pair[1].as_ref().and_then(|a| metaplex::parse_metadata(&a.data));
+                if metadata.as_ref().is_some_and(|m| m.is_fungible()) {
+                    continue;
+                }
+
+                // Token-2022 NFTs may embed their metadata in the mint instead of a metadata account
+                let (name, uri, collection) = match metadata {
+                    Some(metadata) => (metadata.name, metadata.uri, metadata.collection),
+                    None => match state.get_variable_len_extension::<TokenMetadata>() {
+                        Ok(embedded) => (embedded.name, embedded.uri, None),
+                        Err(_) => (String::new(), String::new(), None),
+                    },
+                };
+
+                nfts.push(metaplex::NftInfo {
+                    mint: mint.to_string(),
+                    compressed: false,
+                    name: Some(name).filter(|n| !n.is_empty()),
+                    description: None,
+                    uri: Some(uri).filter(|u| !u.is_empty()),
+                    image: None,
+                    collection: collection.map(|c| c.to_string()),
+                });
+            }
+        }
+
+        Ok(nfts)
+    }
+
+    async fn get_compressed_nfts(&self, owner: &Pubkey) -> Result<Vec<metaplex::NftInfo>> {
+        let Some(das_url) = self.config.solana_das_url.as_deref() else {
+            return Ok(Vec::new());
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(self.config.solana_rpc_timeout_seconds))
+            .build()?;
+
+        let mut nfts = Vec::new();
+        let mut page = 1;
+        loop {
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": "blockfolio",
+                "method": "getAssetsByOwner",
+                "params": { "ownerAddress": owner.to_string(), "page": page, "limit": DAS_PAGE_SIZE },
+            });
+            let response: Value = client.post(das_url).json(&request).send().await?.error_for_status()?.json().await?;
+            if let Some(error) = response.get("error") {
+                return Err(anyhow::anyhow!("getAssetsByOwner failed: {}", error));
+            }
+
+            // Uncompressed assets are also listed but are already read from token accounts
+            let items = response["result"]["items"].as_array().cloned().unwrap_or_default();
+            nfts.extend(items.iter().filter_map(metaplex::parse_das_asset));
+            if items.len() < DAS_PAGE_SIZE {
+                break;
+            }
+            page += 1;
+        }
+
+        Ok(nfts)
+    }
+
+    // Off-chain JSON by mint for NFTs that still lack an image, from the cache or their metadata URI.
+    // Without fetch_offchain, uncached URIs are fetched in the background so portfolios don't wait on metadata hosts.
+    async fn get_offchain_metadata(&self, nfts: &[metaplex::NftInfo], fetch_offchain: bool) -> HashMap<String, Value> {
+        let wanted: Vec<(String, String)> = nfts
+            .iter()
+            .filter(|n| n.image.is_none())
+            .filter_map(|n| Some((n.mint.clone(), n.uri.clone()?)))
+            .collect();
+        let mints: Vec<String> = wanted.iter().map(|(mint, _)| mint.clone()).collect();
+        let cached = self.metadata_service.get_cached_nft_metadata("solana", &mints).await.unwrap_or_default();
+
+        let mut metadata = HashMap::new();
+        let mut missing = Vec::new();
+        for (mint, uri) in wanted {
+            match cached.get(&mint) {
+                Some((_, data)) => {
+                    if let Some(data) = data {
+                        metadata.insert(mint, data.clone());
+                    }
+                }
+                None => missing.push((mint, uri)),
+            }
+        }
+
+        if !fetch_offchain {
+            if !missing.is_empty() {
+                let metadata_service = self.metadata_service.clone();
+                let concurrency = self.config.token_lookup_concurrency;
+                tokio::spawn(async move {
+                    metadata_service.fetch_nft_metadata_batch("solana", missing, concurrency).await;
+                });
+            }
+            return metadata;
+        }
+
+        let fetched = self
+            .metadata_service
+            .fetch_nft_metadata_batch("solana", missing, self.config.token_lookup_concurrency)
+            .await;
+        metadata.extend(fetched.into_iter().filter_map(|(mint, _, data)| Some((mint, data?))));
+
+        metadata
+    }
+
+    // Collection names come from the metadata account of each collection's own mint
+    async fn get_collection_names(&self, collections: &[Pubkey]) -> Result<HashMap<String, String>> {
+        let mut names = HashMap::new();
+        for chunk in collections.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let keys: Vec<Pubkey> = chunk.iter().map(metaplex::metadata_key).collect();
+            let accounts = self.rpc_client.get_multiple_accounts(&keys).await?;
+            for (collection, account) in chunk.iter().zip(accounts) {
+                let Some(metadata) = account.and_then(|a| metaplex::parse_metadata(&a.data)) else { continue };
+                if !metadata.name.is_empty() {
+                    names.insert(collection.to_string(), metadata.name);
+                }
+            }
+        }
+
+        Ok(names)
+    }
+
+    async fn fetch_stake_positions(&self, owner: &Pubkey, sol_price: Decimal) -> Result<Vec<StakePosition>> {
+        // Meta starts after the 4-byte state tag and 8-byte rent reserve: staker at 12, withdrawer at 44
+        let mut stake_accounts = Vec::new();
+        for offset in [STAKER_OFFSET, WITHDRAWER_OFFSET] {
+            let config = RpcProgramAccountsConfig {
+                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(offset, &owner.to_bytes()))]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            for (stake_pubkey, account) in self.rpc_client.get_program_accounts_with_config(&solana_stake_interface::program::ID, config).await? {
+                if !stake_accounts.iter().any(|(existing, _)| *existing == stake_pubkey) {
+                    stake_accounts.push((stake_pubkey, account));
+                }
+            }
+        }
+
+        if stake_accounts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let current_epoch = self.rpc_client.get_epoch_info().await?.epoch;
+
+        let mut positions = Vec::new();
+        for (stake_pubkey, account) in stake_accounts {
+            let Ok(state) = bincode::deserialize::<StakeStateV2>(&account.data) else { continue };
+            let (meta, delegation) = match state {
+                StakeStateV2::Initialized(meta) => (meta, None),
+                StakeStateV2::Stake(meta, stake, _) => (meta, Some(stake.delegation)),
+                _ => continue,
+            };
+
+            let activation_state = match delegation {
+                None => "inactive",
+                Some(d) if d.deactivation_epoch != u64::MAX => {
+                    if current_epoch > d.deactivation_epoch { "inactive" } else { "deactivating" }
+                }
+                Some(d) if d.activation_epoch >= current_epoch => "activating",
+                Some(_) => "active",
+            };
+
+            let lamports = account.lamports.to_string();
+            positions.push(StakePosition {
+                stake_account: stake_pubkey.to_string(),
+                validator_vote_account: delegation.map(|d| d.voter_pubkey.to_string()),
+                activation_state: activation_state.to_string(),
+                delegated_amount: amounts::to_f64(&delegation.map(|d| d.stake).unwrap_or(0).to_string(), SOL_DECIMALS),
+                rent_reserve: amounts::to_f64(&meta.rent_exempt_reserve.to_string(), SOL_DECIMALS),
+                total_balance: amounts::to_f64(&lamports, SOL_DECIMALS),
+                value_usd: amounts::usd_value(&lamports, SOL_DECIMALS, sol_price),
+                ..Default::default()
+            });
+        }
+
+        Ok(positions)
+    }
+
+    // Labels for each mint, resolved from Token-2022 embedded metadata, then the Metaplex metadata account, then token lists
+    async fn get_token_metadata(&self, mints: &[String]) -> Result<HashMap<String, SolanaTokenMetadata>> {
+        let mut metadata = self.metadata_service.get_cached_solana_metadata(mints).await.unwrap_or_default();
+
+        let mut missing: Vec<Pubkey> = Vec::new();
+        for mint in mints {
+            if !metadata.contains_key(mint) {
+                let pubkey: Pubkey = mint.parse()?;
+                if !missing.contains(&pubkey) {
+                    missing.push(pubkey);
+                }
+            }
+        }
+
+        // Each mint is read together with its metadata PDA
+        let mut onchain = Vec::new();
+        for chunk in missing.chunks(MAX_MULTIPLE_ACCOUNTS / 2) {
+            let keys: Vec<Pubkey> = chunk.iter().flat_map(|mint| [*mint, metaplex::metadata_key(mint)]).collect();
+            let accounts = self.rpc_client.get_multiple_accounts(&keys).await?;
+
+            for (mint, pair) in chunk.iter().zip(accounts.chunks(2)) {
+                let state = pair[0].as_ref().and_then(|a| StateWithExtensions::<MintState>::unpack(&a.data).ok());
+                let decimals = state.as_ref().map(|s| s.base.decimals);
+                let embedded = state
+                    .as_ref()
+                    .and_then(|s| s.get_variable_len_extension::<TokenMetadata>().ok())
+                    .map(|m| OnchainTokenMetadata {
+                        name: Some(m.name).filter(|n| !n.is_empty()),
+                        symbol: Some(m.symbol).filter(|s| !s.is_empty()),
+                        uri: Some(m.uri).filter(|u| !u.is_empty()),
+                        source: "token-2022",
+                    })
+                    .filter(|m| m.name.is_some() || m.symbol.is_some());
+                let token_metadata = embedded.or_else(|| {
+                    let m = metaplex::parse_metadata(&pair[1].as_ref()?.data)?;
+                    Some(OnchainTokenMetadata {
+                        name: Some(m.name).filter(|n| !n.is_empty()),
+                        symbol: Some(m.symbol).filter(|s| !s.is_empty()),
+                        uri: Some(m.uri).filter(|u| !u.is_empty()),
+                        source: "metaplex",
+                    })
+                });
+                onchain.push((mint.to_string(), decimals, token_metadata));
+            }
+        }
+
+        // Off-chain JSON and token list lookups run concurrently
+        let concurrency = self.config.token_lookup_concurrency.max(1);
+        let resolved: Vec<(String, SolanaTokenMetadata)> = stream::iter(onchain)
+            .map(|(mint, decimals, token_metadata)| async move {
+                match self.metadata_service.resolve_solana_metadata(&mint, decimals, token_metadata).await {
+                    Ok(resolved) => (mint, resolved),
+                    Err(e) => {
+                        tracing::warn!("Failed to resolve metadata for {}: {}", mint, e);
+                        (mint, SolanaTokenMetadata { decimals, ..Default::default() })
+                    }
+                }
+            })
+            .buffered(concurrency)
+            .collect()
+            .await;
+        metadata.extend(resolved);
+
+        Ok(metadata)
+    }
+
+    async fn get_token_2022_mints(&self, mints: &[String]) -> Result<HashMap<String, Token2022Mint>> {
+        let mut pubkeys: Vec<Pubkey> = Vec::new();
+        for mint in mints {
+            let pubkey: Pubkey = mint.parse()?;
+            if !pubkeys.contains(&pubkey) {
+                pubkeys.push(pubkey);
+            }
+        }
+
+        let mut info_by_mint = HashMap::new();
+        let mut epoch = None;
+        for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let accounts = self.rpc_client.get_multiple_accounts(chunk).await?;
+            for (pubkey, account) in chunk.iter().zip(accounts) {
+                let Some(account) = account else { continue };
+                let Ok(state) = StateWithExtensions::<MintState>::unpack(&account.data) else { continue };
+
+                let mut info = Token2022Mint {
+                    decimals: state.base.decimals,
+                    ..Default::default()
+                };
+
+                if let Ok(fee_config) = state.get_extension::<TransferFeeConfig>() {
+                    // The active fee depends on the epoch, which is only fetched when needed
+                    let current_epoch = match epoch {
+                        Some(current_epoch) => current_epoch,
+                        None => {
+                            let current_epoch = self.rpc_client.get_epoch_info().await?.epoch;
+                            epoch = Some(current_epoch);
+                            current_epoch
+                        }
+                    };
+                    let fee = fee_config.get_epoch_fee(current_epoch);
+                    info.extensions.transfer_fee_basis_points = Some(u16::from(fee.transfer_fee_basis_points));
+                    info.extensions.transfer_fee_maximum = Some(u64::from(fee.maximum_fee).to_string());
+                }
+
+                if let Ok(interest) = state.get_extension::<InterestBearingConfig>() {
+                    info.extensions.interest_rate_bps = Some(i16::from(interest.current_rate));
+                    info.interest = Some(*interest);
+                }
+
+                if let Ok(pointer) = state.get_extension::<MetadataPointer>() {
+                    info.extensions.metadata_address = Option::<Pubkey>::from(pointer.metadata_address).map(|a| a.to_string());
+                }
+
+                if let Ok(metadata) = state.get_variable_len_extension::<TokenMetadata>() {
+                    info.extensions.metadata_uri = Some(metadata.uri).filter(|u| !u.is_empty());
+                }
+
+                info_by_mint.insert(pubkey.to_string(), info);
+            }
+        }
+
+        Ok(info_by_mint)
+    }
+
+    async fn get_mint_decimals(&self, mints: &[String]) -> Result<HashMap<String, u8>> {
+        let mut decimals = self.metadata_service.get_solana_decimals(mints).await.unwrap_or_default();
+
+        let mut missing: Vec<Pubkey> = Vec::new();
+        for mint in mints {
+            if !decimals.contains_key(mint) {
+                let pubkey: Pubkey = mint.parse()?;
+                if !missing.contains(&pubkey) {
+                    missing.push(pubkey);
+                }
+            }
+        }
+
+        // Mint accounts hold the decimals; fetch the uncached ones in batches
+        for chunk in missing.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let accounts = self.rpc_client.get_multiple_accounts(chunk).await?;
+            for (pubkey, account) in chunk.iter().zip(accounts) {
+                let Some(account) = account else { continue };
+                // Token-2022 mints carry extensions after the base layout, which this parser accepts too
+                if let Ok(mint) = StateWithExtensions::<MintState>::unpack(&account.data) {
+                    let mint_address = pubkey.to_string();
+                    if let Err(e) = self.metadata_service.set_solana_decimals(&mint_address, mint.base.decimals).await {
+                        tracing::warn!("Failed to cache decimals for {}: {}", mint_address, e);
+                    }
+                    decimals.insert(mint_address, mint.base.decimals);
+                }
+            }
+        }
+
+        Ok(decimals)
+    }
+
+    // Owner of a .sol domain's name account
+    pub async fn resolve_domain(&self, domain: &str) -> Result<Option<String>> {
+        let Some(key) = sns::domain_key(domain) else {
+            return Ok(None);
+        };
+        let account = self.rpc_client.get_account_with_commitment(&key, self.rpc_client.commitment()).await?.value;
+        Ok(account.and_then(|a| sns::owner(&a.data)).map(|owner| owner.to_string()))
+    }
+
+    // The wallet's favourite domain, if it still owns it
+    pub async fn primary_domain(&self, address: &str) -> Result<Option<String>> {
+        let owner = address.parse::<Pubkey>()?;
+        let commitment = self.rpc_client.commitment();
+
+        let favourite = self.rpc_client.get_account_with_commitment(&sns::favourite_domain_key(&owner), commitment).await?.value;
+        let Some(name_key) = favourite.and_then(|a| sns::parse_favourite_domain(&a.data)) else {
+            return Ok(None);
+        };
+        let name_account = self.rpc_client.get_account_with_commitment(&name_key, commitment).await?.value;
+        if !name_account.is_some_and(|a| sns::owner(&a.data) == Some(owner) && sns::is_sol_domain(&a.data)) {
+            return Ok(None);
+        }
+
+        let reverse = self.rpc_client.get_account_with_commitment(&sns::reverse_key(&name_key), commitment).await?.value;
+        Ok(reverse.and_then(|a| sns::parse_reverse(&a.data)).map(|name| format!("{}.sol", name)))
+    }
+
+    pub async fn fetch_transactions(&self, address: &str, limit: usize, before: Option<Signature>, until: Option<Signature>) -> Result<TransactionPage> {
         let pubkey = address.parse::<Pubkey>()?;
-        let rpc_client = RpcClient::new(self.rpc_url.clone());
         
-        // Get recent signatures - API takes only pubkey, returns all signatures
-        let signatures = rpc_client.get_signatures_for_address(&pubkey)?;
+        // Only request one page of signatures, starting after the cursor
+        let config = GetConfirmedSignaturesForAddress2Config {
+            before,
+            until,
+            limit: Some(limit),
+            commitment: None,
+        };
+        let signatures = self.rpc_client.get_signatures_for_address_with_config(&pubkey, config).await?;
+
+        // A full page means there may be older signatures to fetch
+        let next_cursor = if signatures.len() == limit {
+            signatures.last().map(|s| s.signature.clone())
+        } else {
+            None
+        };
         
-        let mut transactions = Vec::new();
+        let concurrency = self.config.token_lookup_concurrency.max(1);
+        let transactions: Vec<Option<Transaction>> = stream::iter(signatures)
+            .map(|sig_info| async move {
+                match self.fetch_transaction(address, &sig_info).await {
+                    Ok(tx) => Some(tx),
+                    Err(e) => {
+                        tracing::warn!("Failed to fetch transaction {}: {}", sig_info.signature, e);
+                        None
+                    }
+                }
+            })
+            .buffered(concurrency)
+            .collect()
+            .await;
         
-        for sig_info in signatures.iter().take(limit) {
-            // Parse signature string to Signature type
-            let signature = sig_info.signature.parse::<solana_sdk::signature::Signature>()?;
-            // get_transaction requires encoding parameter
-            if let Ok(_tx) = rpc_client.get_transaction(&signature, UiTransactionEncoding::Json) {
-                let timestamp = sig_info.block_time.unwrap_or(0);
-                let status = if sig_info.err.is_none() { "success" } else { "failed" };
-                
-                // Determine transaction type and amount
-                // This is simplified - in reality, you'd parse the transaction details
-                let transaction_type = "transfer".to_string();
-                let amount = 0.0; // Would need to parse transaction to get actual amount
-                let token_symbol = "SOL".to_string();
-                
-                transactions.push(crate::types::transaction::Transaction {
-                    hash: sig_info.signature.to_string(),
-                    timestamp,
-                    transaction_type,
-                    amount,
-                    token_symbol,
-                    chain: "solana".to_string(),
-                    status: status.to_string(),
-                    from: address.to_string(),
-                    to: address.to_string(), // Would need to parse from transaction
-                });
+        Ok(TransactionPage {
+            transactions: transactions.into_iter().flatten().collect(),
+            next_cursor,
+        })
+    }
+
+    async fn fetch_transaction(&self, address: &str, sig_info: &RpcConfirmedTransactionStatusWithSignature) -> Result<Transaction> {
+        // Parse signature string to Signature type
+        let signature = sig_info.signature.parse::<Signature>()?;
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::JsonParsed),
+            commitment: None,
+            max_supported_transaction_version: Some(0),
+        };
+        let tx = self.rpc_client.get_transaction_with_config(&signature, config).await?;
+        let parsed = parse_transaction(&serde_json::to_value(&tx)?, address);
+
+        let timestamp = sig_info.block_time.or(tx.block_time).unwrap_or(0);
+        let status = if sig_info.err.is_none() { "success" } else { "failed" };
+
+        let (raw_amount, decimals, token_symbol, token_mint) = match &parsed.transfer {
+            Some(transfer) => {
+                let raw_amount = transfer.raw_amount.to_string();
+                match &transfer.mint {
+                    Some(mint) => {
+                        let metadata = match self.get_token_metadata(std::slice::from_ref(mint)).await {
+                            Ok(mut metadata) => metadata.remove(mint).unwrap_or_default(),
+                            Err(_) => SolanaTokenMetadata::default(),
+                        };
+                        let symbol = metadata.symbol.unwrap_or_else(|| fallback_symbol(metadata.name.as_deref(), mint));
+                        (raw_amount, transfer.decimals, symbol, Some(mint.clone()))
+                    }
+                    None => (raw_amount, transfer.decimals, "SOL".to_string(), None),
+                }
             }
+            None => ("0".to_string(), SOL_DECIMALS, "SOL".to_string(), None),
+        };
+
+        // Unknown sides of the transfer are the queried address itself
+        let (from, to) = match parsed.transfer {
+            Some(transfer) => (
+                transfer.from.unwrap_or_else(|| address.to_string()),
+                transfer.to.unwrap_or_else(|| address.to_string()),
+            ),
+            None => (address.to_string(), address.to_string()),
+        };
+
+        Ok(Transaction {
+            hash: sig_info.signature.to_string(),
+            timestamp,
+            transaction_type: parsed.direction,
+            amount: amounts::to_f64(&raw_amount, decimals),
+            raw_amount,
+            decimals,
+            token_symbol,
+            chain: "solana".to_string(),
+            status: status.to_string(),
+            from,
+            to,
+            token_mint,
+            counterparty: parsed.counterparty,
+            fee: parsed.fee_lamports.map(|fee| amounts::to_f64(&fee.to_string(), SOL_DECIMALS)),
+        })
+    }
+}
+
+#[async_trait]
+impl ChainClient for SolanaClient {
+    fn chain(&self) -> &str {
+        "solana"
+    }
+
+    fn is_valid_address(&self, address: &str) -> bool {
+        helpers::is_valid_solana_address(address)
+    }
+
+    // Base58 is case-sensitive, so addresses are already canonical
+    fn normalize_address(&self, address: &str) -> String {
+        address.to_string()
+    }
+
+    fn is_valid_cursor(&self, cursor: &str) -> bool {
+        cursor.parse::<Signature>().is_ok()
+    }
+
+    fn naming_system(&self) -> Option<&'static str> {
+        Some("sns")
+    }
+
+    fn is_name(&self, input: &str) -> bool {
+        input.to_lowercase().ends_with(".sol")
+    }
+
+    async fn resolve_name(&self, name: &str) -> Result<Option<String>> {
+        self.resolve_domain(name).await
+    }
+
+    async fn lookup_name(&self, address: &str) -> Result<Option<String>> {
+        self.primary_domain(address).await
+    }
+
+    async fn fetch_portfolio(&self, address: &str) -> Result<PortfolioResponse> {
+        SolanaClient::fetch_portfolio(self, address).await
+    }
+
+    async fn fetch_transactions(&self, address: &str, limit: usize, before: Option<&str>, until: Option<&str>) -> Result<TransactionPage> {
+        let before = before.map(Signature::from_str).transpose()?;
+        let until = until.map(Signature::from_str).transpose()?;
+        SolanaClient::fetch_transactions(self, address, limit, before, until).await
+    }
+
+    fn supports_nfts(&self) -> bool {
+        true
+    }
+
+    async fn fetch_nfts(&self, address: &str) -> Result<NftPortfolio> {
+        SolanaClient::fetch_nfts(self, address).await
+    }
+}
+
+// Used when no source has a symbol: the first word of the token name, or the start of the mint when no name is known
+fn fallback_symbol(name: Option<&str>, mint: &str) -> String {
+    match name {
+        Some(n) => n.split_whitespace().next().unwrap_or(&mint[..8]).to_string(),
+        None => mint.chars().take(8).collect(),
+    }
+}
+
+fn build_token(balance: TokenBalance, decimals: u8, mint_info: Option<Token2022Mint>, metadata: SolanaTokenMetadata, price: PriceQuote) -> Token {
+    let TokenBalance { mint, raw_amount, token_program } = balance;
+
+    // Interest-bearing mints report a UI amount that includes accrued interest
+    let ui_amount = mint_info
+        .as_ref()
+        .and_then(|info| info.interest)
+        .and_then(|interest| interest.amount_to_ui_amount(raw_amount, decimals, chrono::Utc::now().timestamp()))
+        .unwrap_or_else(|| amounts::format_units(&raw_amount.to_string(), decimals));
+    let amount = ui_amount.parse().unwrap_or(0.0);
+
+    let value = amounts::value_of(&ui_amount, price.price_usd.unwrap_or_default());
+
+    let SolanaTokenMetadata { symbol, name, logo_uri, .. } = metadata;
+    let symbol = symbol.unwrap_or_else(|| fallback_symbol(name.as_deref(), &mint));
+
+    Token {
+        symbol,
+        mint_or_address: mint,
+        amount,
+        raw_amount: raw_amount.to_string(),
+        decimals,
+        price_usd: price.price_usd,
+        value_usd: value,
+        price_confidence: price.confidence,
+        name,
+        logo_uri,
+        price_change_24h: price.price_change_24h,
+        token_program: Some(token_program_label(&token_program).to_string()),
+        extensions: mint_info.map(|info| info.extensions),
+        ..Default::default()
+    }
+}
+
+fn build_nft(nft: metaplex::NftInfo, metadata: Option<&Value>) -> Nft {
+    // On-chain and DAS fields take precedence over the off-chain JSON
+    let image = nft.image.or_else(|| metadata_service::nft_field(metadata, "image"));
+
+    Nft {
+        token_id: nft.mint,
+        standard: if nft.compressed { "compressed" } else { "spl" }.to_string(),
+        amount: "1".to_string(),
+        name: nft.name.or_else(|| metadata_service::nft_field(metadata, "name")),
+        description: nft.description.or_else(|| metadata_service::nft_field(metadata, "description")),
+        image: image.map(|image| metadata_service::gateway_url(&image)),
+        token_uri: metadata_service::display_token_uri(nft.uri),
+    }
+}
+
+// Returns (mint, raw amount, decimals) from either a jsonParsed or a binary token account
+fn parse_token_account(data: &UiAccountData) -> Option<(String, u64, Option<u8>)> {
+    match data {
+        UiAccountData::Json(parsed) => {
+            let info = parsed.parsed.get("info")?;
+            let mint = info.get("mint")?.as_str()?.to_string();
+            let token_amount = info.get("tokenAmount")?;
+            let raw_amount = token_amount.get("amount")?.as_str()?.parse().ok()?;
+            let decimals = token_amount.get("decimals").and_then(|v| v.as_u64()).map(|d| d as u8);
+            Some((mint, raw_amount, decimals))
+        }
+        _ => {
+            let bytes = data.decode()?;
+            let token_account = StateWithExtensions::<TokenAccount>::unpack(&bytes).ok()?;
+            Some((token_account.base.mint.to_string(), token_account.base.amount, None))
         }
-        
-        Ok(transactions)
     }
 }
 
+fn token_program_label(program_id: &Pubkey) -> &'static str {
+    if *program_id == spl_token_2022::ID {
+        "token-2022"
+    } else {
+        "spl-token"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::services::cache::CacheService;
+    use crate::services::price_provider::CoinGeckoApi;
+    use crate::services::rpc_stub;
+    use crate::services::token_registry::TokenRegistry;
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use serde_json::json;
+    use spl_token::solana_program::program_pack::Pack;
+
+    // Services are backed by a database that never answers, so every cache lookup misses
+    fn client(rpc_url: String) -> SolanaClient {
+        let cache = CacheService::new(database::unreachable_pool());
+        let config = Config { solana_rpc_url: rpc_url.clone(), ..Config::for_tests() };
+        let token_registry = TokenRegistry::new(cache.clone());
+        let price_service = PriceService::new(cache.clone(), token_registry.clone(), config.clone());
+        let metadata_service = MetadataService::new(cache, token_registry, CoinGeckoApi::new(&config));
+        SolanaClient::new(rpc_url, price_service, metadata_service, config)
+    }
+
+    fn encoded_account(data: Vec<u8>, owner: &Pubkey) -> Value {
+        json!({
+            "data": [BASE64_STANDARD.encode(&data), "base64"],
+            "executable": false,
+            "lamports": 2_039_280,
+            "owner": owner.to_string(),
+            "rentEpoch": 0,
+            "space": data.len(),
+        })
+    }
+
+    #[tokio::test]
+    async fn portfolio_rpc_calls_stay_within_the_batched_bound() {
+        let owner = Pubkey::new_unique();
+        let mints: Vec<Pubkey> = (0..120).map(|_| Pubkey::new_unique()).collect();
+        let token_accounts: Vec<Value> = mints
+            .iter()
+            .map(|mint| {
+                let mut data = vec![0u8; spl_token::state::Account::LEN];
+                let account = spl_token::state::Account {
+                    mint: *mint,
+                    owner,
+                    amount: 1_000,
+                    state: spl_token::state::AccountState::Initialized,
+                    ..Default::default()
+                };
+                account.pack_into_slice(&mut data);
+                json!({ "pubkey": Pubkey::new_unique().to_string(), "account": encoded_account(data, &spl_token::ID) })
+            })
+            .collect();
+        let mut mint_data = vec![0u8; spl_token::state::Mint::LEN];
+        let mint = spl_token::state::Mint { decimals: 6, supply: 1_000_000, is_initialized: true, ..Default::default() };
+        mint.pack_into_slice(&mut mint_data);
+        let mint_keys: Vec<String> = mints.iter().map(|m| m.to_string()).collect();
+
+        let url = rpc_stub::spawn(move |method, params| {
+            let context = json!({ "slot": 1 });
+            match method {
+                "getBalance" => Some(json!({ "context": context, "value": 1_000_000_000u64 })),
+                "getTokenAccountsByOwner" => {
+                    let legacy = params[1]["programId"] == spl_token::ID.to_string();
+                    let value = if legacy { token_accounts.clone() } else { Vec::new() };
+                    Some(json!({ "context": context, "value": value }))
+                }
+                "getMultipleAccounts" => {
+                    let value: Vec<Value> = params[0]
+                        .as_array()?
+                        .iter()
+                        .map(|key| match mint_keys.iter().any(|m| key == m) {
+                            true => encoded_account(mint_data.clone(), &spl_token::ID),
+                            false => Value::Null,
+                        })
+                        .collect();
+                    Some(json!({ "context": context, "value": value }))
+                }
+                "getProgramAccounts" => Some(json!([])),
+                _ => None,
+            }
+        })
+        .await;
+
+        let solana = client(url);
+        let (portfolio, rpc_calls) = rpc_counter::count(solana.build_portfolio(&owner.to_string())).await;
+        let portfolio = portfolio.unwrap();
+
+        assert_eq!(portfolio.tokens.len(), 120);
+        assert!(portfolio.tokens.iter().all(|t| t.decimals == 6));
+        // Balance, two owner queries, ceil(120 / 50) mint and metadata batches and two stake queries
+        assert_eq!(rpc_calls, 1 + 2 + 3 + 2);
+    }
+}
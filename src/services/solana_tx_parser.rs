@@ -0,0 +1,370 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use crate::services::solana_client::SOL_DECIMALS;
+
+// A value movement between two wallets extracted from a jsonParsed transaction
+#[derive(Debug, Clone)]
+pub struct ParsedTransfer {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub raw_amount: u64,
+    pub decimals: u8,
+    pub mint: Option<String>, // None for native SOL
+}
+
+#[derive(Debug, Clone)]
+pub struct ParsedTransaction {
+    pub direction: String, // "send", "receive", "self" or "other"
+    pub transfer: Option<ParsedTransfer>,
+    pub counterparty: Option<String>,
+    pub fee_lamports: Option<u64>,
+}
+
+struct TokenAccountInfo {
+    owner: Option<String>,
+    mint: String,
+    decimals: u8,
+}
+
+// Decodes a `getTransaction` response fetched with the jsonParsed encoding, from the point of view of `address`
+pub fn parse_transaction(tx: &Value, address: &str) -> ParsedTransaction {
+    let message = &tx["transaction"]["message"];
+    let meta = &tx["meta"];
+
+    let account_keys: Vec<String> = message["accountKeys"]
+        .as_array()
+        .map(|keys| {
+            keys.iter()
+                .filter_map(|k| k.get("pubkey").or(Some(k)).and_then(|v| v.as_str()).map(|s| s.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // Only the fee payer (first account key) pays the fee
+    let fee_lamports = meta["fee"].as_u64().filter(|_| account_keys.first().map(|k| k == address).unwrap_or(false));
+
+    let token_accounts = token_accounts(meta, &account_keys);
+
+    // Outer instructions first, then inner instructions in execution order
+    let mut instructions: Vec<&Value> = message["instructions"].as_array().map(|v| v.iter().collect()).unwrap_or_default();
+    if let Some(inner) = meta["innerInstructions"].as_array() {
+        for group in inner {
+            if let Some(list) = group["instructions"].as_array() {
+                instructions.extend(list.iter());
+            }
+        }
+    }
+
+    let transfers: Vec<ParsedTransfer> = instructions
+        .into_iter()
+        .filter_map(|ix| parse_transfer_instruction(ix, &token_accounts))
+        .collect();
+
+    let relevant = transfers.iter().find(|t| t.from.as_deref() == Some(address) || t.to.as_deref() == Some(address));
+    if let Some(transfer) = relevant {
+        let is_sender = transfer.from.as_deref() == Some(address);
+        let is_receiver = transfer.to.as_deref() == Some(address);
+        let (direction, counterparty) = match (is_sender, is_receiver) {
+            (true, true) => ("self", None),
+            (true, false) => ("send", transfer.to.clone()),
+            _ => ("receive", transfer.from.clone()),
+        };
+        return ParsedTransaction {
+            direction: direction.to_string(),
+            transfer: Some(transfer.clone()),
+            counterparty,
+            fee_lamports,
+        };
+    }
+
+    // No direct transfer (e.g. a swap through a program): fall back to balance deltas
+    if let Some((transfer, delta)) = largest_balance_delta(meta, &account_keys, address, fee_lamports.unwrap_or(0)) {
+        // Pick a counterparty from any transfer of the same asset, if one exists
+        let counterparty = transfers
+            .iter()
+            .find(|t| t.mint == transfer.mint)
+            .and_then(|t| if delta < 0 { t.to.clone() } else { t.from.clone() })
+            .filter(|c| c != address);
+        return ParsedTransaction {
+            direction: if delta < 0 { "send" } else { "receive" }.to_string(),
+            transfer: Some(transfer),
+            counterparty,
+            fee_lamports,
+        };
+    }
+
+    ParsedTransaction {
+        direction: "other".to_string(),
+        transfer: None,
+        counterparty: None,
+        fee_lamports,
+    }
+}
+
+// Maps token account addresses to their owner, mint and decimals using the pre/post token balances
+fn token_accounts(meta: &Value, account_keys: &[String]) -> HashMap<String, TokenAccountInfo> {
+    let mut accounts = HashMap::new();
+    for key in ["preTokenBalances", "postTokenBalances"] {
+        let Some(balances) = meta[key].as_array() else { continue };
+        for balance in balances {
+            let Some(index) = balance["accountIndex"].as_u64() else { continue };
+            let Some(account) = account_keys.get(index as usize) else { continue };
+            let Some(mint) = balance["mint"].as_str() else { continue };
+            accounts.entry(account.clone()).or_insert_with(|| TokenAccountInfo {
+                owner: balance["owner"].as_str().map(|s| s.to_string()),
+                mint: mint.to_string(),
+                decimals: balance["uiTokenAmount"]["decimals"].as_u64().unwrap_or(0) as u8,
+            });
+        }
+    }
+    accounts
+}
+
+fn parse_transfer_instruction(ix: &Value, token_accounts: &HashMap<String, TokenAccountInfo>) -> Option<ParsedTransfer> {
+    let program = ix["program"].as_str()?;
+    let parsed = &ix["parsed"];
+    let kind = parsed["type"].as_str()?;
+    let info = &parsed["info"];
+
+    match (program, kind) {
+        ("system", "transfer") | ("system", "transferWithSeed") => Some(ParsedTransfer {
+            from: info["source"].as_str().map(|s| s.to_string()),
+            to: info["destination"].as_str().map(|s| s.to_string()),
+            raw_amount: info["lamports"].as_u64()?,
+            decimals: SOL_DECIMALS,
+            mint: None,
+        }),
+        ("spl-token", "transfer") | ("spl-token", "transferChecked") => {
+            let source = info["source"].as_str()?;
+            let destination = info["destination"].as_str()?;
+            let source_info = token_accounts.get(source);
+            let destination_info = token_accounts.get(destination);
+
+            // transferChecked carries mint and decimals; plain transfer relies on the token balances
+            let (raw_amount, decimals, mint) = if kind == "transferChecked" {
+                let token_amount = &info["tokenAmount"];
+                (
+                    token_amount["amount"].as_str()?.parse().ok()?,
+                    token_amount["decimals"].as_u64()? as u8,
+                    info["mint"].as_str()?.to_string(),
+                )
+            } else {
+                let account = source_info.or(destination_info)?;
+                (info["amount"].as_str()?.parse().ok()?, account.decimals, account.mint.clone())
+            };
+
+            // Transfers move between token accounts; report the wallets that own them
+            let authority = info["authority"].as_str().or(info["multisigAuthority"].as_str()).map(|s| s.to_string());
+            Some(ParsedTransfer {
+                from: source_info.and_then(|a| a.owner.clone()).or(authority),
+                to: destination_info.and_then(|a| a.owner.clone()),
+                raw_amount,
+                decimals,
+                mint: Some(mint),
+            })
+        }
+        _ => None,
+    }
+}
+
+// Returns the asset whose balance changed the most for `address`, with the sign of the change
+fn largest_balance_delta(
+    meta: &Value,
+    account_keys: &[String],
+    address: &str,
+    fee_lamports: u64,
+) -> Option<(ParsedTransfer, i128)> {
+    let mut deltas: HashMap<String, (i128, u8)> = HashMap::new();
+    for (key, sign) in [("preTokenBalances", -1i128), ("postTokenBalances", 1i128)] {
+        let Some(balances) = meta[key].as_array() else { continue };
+        for balance in balances {
+            if balance["owner"].as_str() != Some(address) {
+                continue;
+            }
+            let Some(mint) = balance["mint"].as_str() else { continue };
+            let amount: i128 = balance["uiTokenAmount"]["amount"].as_str().and_then(|a| a.parse().ok()).unwrap_or(0);
+            let decimals = balance["uiTokenAmount"]["decimals"].as_u64().unwrap_or(0) as u8;
+            let entry = deltas.entry(mint.to_string()).or_insert((0, decimals));
+            entry.0 += sign * amount;
+        }
+    }
+
+    // Token deltas take priority over SOL, whose change is usually just fees and rent
+    let token_delta = deltas
+        .into_iter()
+        .filter(|(_, (delta, _))| *delta != 0)
+        .max_by_key(|(_, (delta, _))| delta.abs());
+    if let Some((mint, (delta, decimals))) = token_delta {
+        return Some((
+            ParsedTransfer {
+                from: None,
+                to: None,
+                raw_amount: delta.unsigned_abs() as u64,
+                decimals,
+                mint: Some(mint),
+            },
+            delta,
+        ));
+    }
+
+    let index = account_keys.iter().position(|k| k == address)?;
+    let pre = meta["preBalances"].get(index)?.as_u64()? as i128;
+    let post = meta["postBalances"].get(index)?.as_u64()? as i128;
+    let delta = post - pre + fee_lamports as i128;
+    if delta == 0 {
+        return None;
+    }
+
+    Some((
+        ParsedTransfer {
+            from: None,
+            to: None,
+            raw_amount: delta.unsigned_abs() as u64,
+            decimals: SOL_DECIMALS,
+            mint: None,
+        },
+        delta,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ALICE: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+    const BOB: &str = "HN7cABqLq46Es1jh92dQQisAq662SmxELLLsHHe4YWrH";
+    const ALICE_TOKEN_ACCOUNT: &str = "3emsAVdmGKERbHjmGfQ6oZ1e35dkf5iYcS6U4CPKFVaa";
+    const BOB_TOKEN_ACCOUNT: &str = "8Zb5iYdVHkUjq8fmWQZRUBWvX3TfLfhLo2prr5srbGEr";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn system_transfer(from: &str, to: &str, lamports: u64) -> Value {
+        json!({
+            "meta": {
+                "fee": 5000,
+                "preBalances": [2_000_000_000u64, 0, 1],
+                "postBalances": [2_000_000_000u64 - lamports - 5000, lamports, 1],
+                "preTokenBalances": [],
+                "postTokenBalances": [],
+                "innerInstructions": []
+            },
+            "transaction": {
+                "message": {
+                    "accountKeys": [
+                        { "pubkey": from, "signer": true, "writable": true },
+                        { "pubkey": to, "signer": false, "writable": true },
+                        { "pubkey": "11111111111111111111111111111111", "signer": false, "writable": false }
+                    ],
+                    "instructions": [{
+                        "program": "system",
+                        "programId": "11111111111111111111111111111111",
+                        "parsed": {
+                            "type": "transfer",
+                            "info": { "source": from, "destination": to, "lamports": lamports }
+                        }
+                    }]
+                }
+            }
+        })
+    }
+
+    fn token_balance(index: u64, owner: &str, amount: &str) -> Value {
+        json!({
+            "accountIndex": index,
+            "mint": USDC,
+            "owner": owner,
+            "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            "uiTokenAmount": { "amount": amount, "decimals": 6 }
+        })
+    }
+
+    fn spl_transfer_checked() -> Value {
+        json!({
+            "meta": {
+                "fee": 5000,
+                "preBalances": [1_000_000_000u64, 2039280, 2039280, 1],
+                "postBalances": [999_995_000u64, 2039280, 2039280, 1],
+                "preTokenBalances": [token_balance(1, ALICE, "5000000"), token_balance(2, BOB, "0")],
+                "postTokenBalances": [token_balance(1, ALICE, "3500000"), token_balance(2, BOB, "1500000")],
+                "innerInstructions": []
+            },
+            "transaction": {
+                "message": {
+                    "accountKeys": [
+                        { "pubkey": ALICE, "signer": true, "writable": true },
+                        { "pubkey": ALICE_TOKEN_ACCOUNT, "signer": false, "writable": true },
+                        { "pubkey": BOB_TOKEN_ACCOUNT, "signer": false, "writable": true },
+                        { "pubkey": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "signer": false, "writable": false }
+                    ],
+                    "instructions": [{
+                        "program": "spl-token",
+                        "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                        "parsed": {
+                            "type": "transferChecked",
+                            "info": {
+                                "source": ALICE_TOKEN_ACCOUNT,
+                                "destination": BOB_TOKEN_ACCOUNT,
+                                "authority": ALICE,
+                                "mint": USDC,
+                                "tokenAmount": { "amount": "1500000", "decimals": 6, "uiAmountString": "1.5" }
+                            }
+                        }
+                    }]
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn system_transfer_is_a_send_for_the_payer() {
+        let parsed = parse_transaction(&system_transfer(ALICE, BOB, 250_000_000), ALICE);
+        assert_eq!(parsed.direction, "send");
+        assert_eq!(parsed.counterparty.as_deref(), Some(BOB));
+        assert_eq!(parsed.fee_lamports, Some(5000));
+        let transfer = parsed.transfer.unwrap();
+        assert_eq!(transfer.raw_amount, 250_000_000);
+        assert_eq!(transfer.decimals, SOL_DECIMALS);
+        assert_eq!(transfer.mint, None);
+    }
+
+    #[test]
+    fn system_transfer_is_a_receive_for_the_recipient() {
+        let parsed = parse_transaction(&system_transfer(ALICE, BOB, 250_000_000), BOB);
+        assert_eq!(parsed.direction, "receive");
+        assert_eq!(parsed.counterparty.as_deref(), Some(ALICE));
+        // Only the fee payer is charged the fee
+        assert_eq!(parsed.fee_lamports, None);
+    }
+
+    #[test]
+    fn transfer_checked_reports_owning_wallets() {
+        let sent = parse_transaction(&spl_transfer_checked(), ALICE);
+        assert_eq!(sent.direction, "send");
+        assert_eq!(sent.counterparty.as_deref(), Some(BOB));
+        let transfer = sent.transfer.unwrap();
+        assert_eq!(transfer.from.as_deref(), Some(ALICE));
+        assert_eq!(transfer.to.as_deref(), Some(BOB));
+        assert_eq!(transfer.raw_amount, 1_500_000);
+        assert_eq!(transfer.decimals, 6);
+        assert_eq!(transfer.mint.as_deref(), Some(USDC));
+
+        let received = parse_transaction(&spl_transfer_checked(), BOB);
+        assert_eq!(received.direction, "receive");
+        assert_eq!(received.counterparty.as_deref(), Some(ALICE));
+    }
+
+    #[test]
+    fn self_transfer_has_no_counterparty() {
+        let parsed = parse_transaction(&system_transfer(ALICE, ALICE, 1_000), ALICE);
+        assert_eq!(parsed.direction, "self");
+        assert_eq!(parsed.counterparty, None);
+        assert_eq!(parsed.transfer.unwrap().raw_amount, 1_000);
+    }
+
+    #[test]
+    fn unrelated_transaction_is_other() {
+        let parsed = parse_transaction(&system_transfer(BOB, USDC, 1_000), ALICE);
+        assert_eq!(parsed.direction, "other");
+        assert!(parsed.transfer.is_none());
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::Result;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use crate::services::cache::CacheService;
+
+// In-memory index of the configured Solana token lists, refreshed in the background.
+// Lists are revalidated with their ETag/Last-Modified and snapshotted to Postgres for cold starts.
+
+// Full lists run to several megabytes, so downloads get a longer timeout than single lookups
+const TOKEN_LIST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenListEntry {
+    pub address: String,
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    pub decimals: Option<u8>,
+    pub logo_uri: Option<String>,
+    pub coingecko_id: Option<String>,
+}
+
+// A downloaded list as stored in token_list_snapshots; tokens is a JSON array of TokenListEntry
+#[derive(Debug, Clone, FromRow)]
+pub struct TokenListSnapshot {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub tokens: Value,
+}
+
+#[derive(Default)]
+struct TokenList {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    tokens: Vec<TokenListEntry>,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    lists: HashMap<String, TokenList>,
+    by_mint: HashMap<String, TokenListEntry>,
+}
+
+#[derive(Clone)]
+pub struct TokenRegistry {
+    cache: CacheService,
+    http: reqwest::Client,
+    urls: Arc<Vec<String>>,
+    state: Arc<RwLock<RegistryState>>,
+}
+
+impl TokenRegistry {
+    pub fn new(cache: CacheService) -> Self {
+        // Lists are searched in the order configured; the first one containing a mint wins
+        let urls = std::env::var("SOLANA_TOKEN_LIST_URLS")
+            .or_else(|_| std::env::var("JUPITER_TOKEN_LIST_URL"))
+            .unwrap_or_else(|_| "https://token.jup.ag/strict".to_string());
+        let urls: Vec<String> = urls
+            .split(',')
+            .map(str::trim)
+            .filter(|u| !u.is_empty())
+            .map(|u| u.to_string())
+            .collect();
+
+        let http = reqwest::Client::builder()
+            .timeout(TOKEN_LIST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            cache,
+            http,
+            urls: Arc::new(urls),
+            state: Arc::new(RwLock::new(RegistryState::default())),
+        }
+    }
+
+    pub fn get(&self, mint_address: &str) -> Option<TokenListEntry> {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        state.by_mint.get(mint_address).cloned()
+    }
+
+    // Loads the lists saved by a previous run; false when there were none to load
+    pub async fn load_snapshot(&self) -> Result<bool> {
+        let snapshots = self.cache.get_token_list_snapshots(&self.urls).await?;
+        if snapshots.is_empty() {
+            return Ok(false);
+        }
+
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        for snapshot in snapshots {
+            let tokens = match serde_json::from_value(snapshot.tokens) {
+                Ok(tokens) => tokens,
+                Err(e) => {
+                    tracing::warn!("Ignoring unreadable token list snapshot for {}: {}", snapshot.url, e);
+                    continue;
+                }
+            };
+            state.lists.insert(snapshot.url, TokenList {
+                etag: snapshot.etag,
+                last_modified: snapshot.last_modified,
+                tokens,
+            });
+        }
+        self.rebuild_index(&mut state);
+        tracing::info!("Loaded {} tokens from token list snapshots", state.by_mint.len());
+
+        Ok(true)
+    }
+
+    // Revalidates every list; a list that fails to download keeps its previous contents
+    pub async fn refresh(&self) {
+        for url in self.urls.iter() {
+            if let Err(e) = self.refresh_list(url).await {
+                tracing::warn!("Failed to refresh token list {}: {}", url, e);
+            }
+        }
+    }
+
+    // `downloaded` says whether startup already fetched the lists; a snapshot is revalidated right away
+    pub fn spawn_refresh(&self, downloaded: bool) {
+        let refresh_seconds = std::env::var("TOKEN_LIST_REFRESH_SECONDS")
+            .unwrap_or_else(|_| "3600".to_string()) // Hourly; unchanged lists cost a 304
+            .parse()
+            .unwrap_or(3600);
+
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(refresh_seconds));
+            // The first tick completes immediately, and startup may have just downloaded the lists
+            if downloaded {
+                interval.tick().await;
+            }
+            loop {
+                interval.tick().await;
+                registry.refresh().await;
+            }
+        });
+    }
+
+    async fn refresh_list(&self, url: &str) -> Result<()> {
+        let (etag, last_modified) = {
+            let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+            state
+                .lists
+                .get(url)
+                .map(|list| (list.etag.clone(), list.last_modified.clone()))
+                .unwrap_or_default()
+        };
+
+        let mut request = self.http.get(url);
+        if let Some(etag) = &etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            tracing::debug!("Token list {} is unchanged", url);
+            return Ok(());
+        }
+        let response = response.error_for_status()?;
+
+        let header = |name| response.headers().get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string());
+        let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+        let body: Value = response.json().await?;
+        let tokens = parse_token_list(&body);
+
+        // The snapshot only speeds up the next start, so failing to save it is not fatal
+        let snapshot = TokenListSnapshot {
+            url: url.to_string(),
+            etag: etag.clone(),
+            last_modified: last_modified.clone(),
+            tokens: serde_json::to_value(&tokens)?,
+        };
+        if let Err(e) = self.cache.set_token_list_snapshot(&snapshot).await {
+            tracing::warn!("Failed to save token list snapshot for {}: {}", url, e);
+        }
+
+        let changed = {
+            let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+            tracing::info!("Loaded {} tokens from {}", tokens.len(), url);
+            let previous = state.lists.insert(url.to_string(), TokenList { etag, last_modified, tokens });
+            let before = state.by_mint.clone();
+            self.rebuild_index(&mut state);
+            // Nothing was cached from a list that wasn't loaded yet
+            match previous {
+                Some(_) => changed_mints(&before, &state.by_mint),
+                None => Vec::new(),
+            }
+        };
+
+        // Labels cached from the old entries would otherwise be served until they expire
+        if !changed.is_empty() {
+            tracing::debug!("{} mints changed in {}", changed.len(), url);
+            if let Err(e) = self.cache.expire_metadata(&changed, "solana").await {
+                tracing::warn!("Failed to invalidate metadata for mints changed in {}: {}", url, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rebuild_index(&self, state: &mut RegistryState) {
+        let mut by_mint = HashMap::new();
+        for list in self.urls.iter().filter_map(|url| state.lists.get(url)) {
+            for token in &list.tokens {
+                by_mint.entry(token.address.clone()).or_insert_with(|| token.clone());
+            }
+        }
+        state.by_mint = by_mint;
+    }
+}
+
+// Mints added, removed or relabelled between two indexes
+fn changed_mints(before: &HashMap<String, TokenListEntry>, after: &HashMap<String, TokenListEntry>) -> Vec<String> {
+    let removed = before.keys().filter(|mint| !after.contains_key(*mint));
+    let updated = after.iter().filter(|(mint, token)| before.get(*mint) != Some(token)).map(|(mint, _)| mint);
+    removed.chain(updated).cloned().collect()
+}
+
+// Jupiter serves a bare array; Solana token-list style files wrap it in "tokens"
+fn parse_token_list(body: &Value) -> Vec<TokenListEntry> {
+    let tokens = body.as_array().or_else(|| body.get("tokens").and_then(|t| t.as_array()));
+    tokens.into_iter().flatten().filter_map(parse_token).collect()
+}
+
+fn parse_token(token: &Value) -> Option<TokenListEntry> {
+    let text = |value: &Value| value.as_str().filter(|s| !s.is_empty()).map(|s| s.to_string());
+    Some(TokenListEntry {
+        address: text(&token["address"])?,
+        symbol: text(&token["symbol"]),
+        name: text(&token["name"]),
+        decimals: token["decimals"].as_u64().map(|d| d as u8),
+        logo_uri: text(&token["logoURI"]),
+        coingecko_id: text(&token["extensions"]["coingeckoId"]),
+    })
+}
@@ -2,9 +2,11 @@ use sqlx::PgPool;
 
 use crate::services::{
     cache::CacheService,
-    ethereum_client::EthereumClient,
+    chain_client::ChainRegistry,
+    fx_service::FxService,
+    name_service::NameService,
+    price_history::PriceHistory,
     price_service::PriceService,
-    solana_client::SolanaClient,
 };
 
 #[derive(Clone)]
@@ -12,7 +14,8 @@ pub struct AppState {
     pub pool: PgPool,
     pub cache: CacheService,
     pub price_service: PriceService,
-    pub solana_client: SolanaClient,
-    pub ethereum_client: EthereumClient,
+    pub price_history: PriceHistory,
+    pub chains: ChainRegistry,
+    pub names: NameService,
+    pub fx: FxService,
 }
-
@@ -2,4 +2,6 @@ pub mod portfolio;
 pub mod token;
 pub mod user;
 pub mod transaction;
-
+pub mod staking;
+pub mod nft;
+pub mod price;
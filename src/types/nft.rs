@@ -0,0 +1,65 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct NftPortfolio {
+    pub chain: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>, // EVM chains only
+    pub address: String,
+    pub collections: Vec<NftCollection>,
+    pub total_nfts_count: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub total_value_usd: Option<Decimal>, // Only when floor prices are available
+    pub last_updated: String,
+    // Values converted to the requested currency, filled in by the routes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub fx_rate: Option<Decimal>, // Units of `currency` per USD
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub total_value: Option<Decimal>,
+}
+
+// NFTs held from one contract (EVM) or verified collection (Solana)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NftCollection {
+    pub address: String, // Contract address, or the collection mint; a Solana NFT outside any collection uses its own mint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub floor_price_usd: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub value_usd: Option<Decimal>, // Floor price times the number of items held
+    // floor_price_usd and value_usd in the requested currency
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub floor_price: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub value: Option<Decimal>,
+    pub nfts: Vec<Nft>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Nft {
+    pub token_id: String, // Mint or asset id on Solana
+    pub standard: String, // "erc721", "erc1155", "spl" or "compressed"
+    pub amount: String, // Always "1" for ERC-721
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>, // Gateway URL for ipfs:// and ar:// images
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_uri: Option<String>,
+}
+
+// An NFT a wallet has received, as stored in wallet_nfts
+#[derive(Debug, Clone, FromRow)]
+pub struct DiscoveredNft {
+    pub contract_address: String,
+    pub token_id: String,
+    pub standard: String,
+    pub first_seen_block: i64,
+}
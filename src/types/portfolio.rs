@@ -1,17 +1,68 @@
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use crate::types::nft::NftCollection;
 use crate::types::token::Token;
+use crate::types::staking::StakePosition;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct PortfolioResponse {
     pub chain: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>, // EVM chains only
     pub address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>, // ENS/SNS primary name, filled in by the routes
     pub native_balance: f64,
-    pub native_price_usd: f64,
-    pub native_value_usd: f64,
+    #[serde(default)]
+    pub native_raw_balance: String,
+    #[serde(default)]
+    pub native_decimals: u8,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub native_price_usd: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub native_value_usd: Decimal,
     pub tokens: Vec<Token>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub staking: Vec<StakePosition>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nfts: Vec<NftCollection>, // Solana only; EVM NFTs are served by the nfts route
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_tokens_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub nft_value_usd: Option<Decimal>, // NFTs valued at floor price, included in total_value_usd
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub total_value_usd: Option<Decimal>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_updated: Option<String>,
+    // Figures converted to the requested currency, filled in by the routes; the *_usd figures are always USD
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub fx_rate: Option<Decimal>, // Units of `currency` per USD
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub native_price: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub native_value: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub nft_value: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub total_value: Option<Decimal>,
 }
 
+
+// One EVM address resolved on every configured EVM chain
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MultiChainPortfolio {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub chains: Vec<PortfolioResponse>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total_value_usd: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub fx_rate: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub total_value: Option<Decimal>,
+}
@@ -0,0 +1,178 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::fmt;
+use std::str::FromStr;
+
+// Bucket widths kept in price_history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    FiveMinutes,
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 3] = [Resolution::FiveMinutes, Resolution::Hour, Resolution::Day];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::FiveMinutes => "5m",
+            Resolution::Hour => "1h",
+            Resolution::Day => "1d",
+        }
+    }
+
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Resolution::FiveMinutes => 300,
+            Resolution::Hour => 3600,
+            Resolution::Day => 86400,
+        }
+    }
+
+    // How long buckets are kept; longer than any range served at this resolution
+    pub fn retention_seconds(&self) -> Option<i64> {
+        match self {
+            Resolution::FiveMinutes => Some(2 * 86400),
+            Resolution::Hour => Some(35 * 86400),
+            Resolution::Day => None,
+        }
+    }
+
+    pub fn bucket_start(&self, timestamp: i64) -> i64 {
+        timestamp - timestamp.rem_euclid(self.seconds())
+    }
+}
+
+// Chart range requested as ?range=1d|7d|30d|90d|1y|max
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceRange {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+    Max,
+}
+
+impl PriceRange {
+    pub fn days(&self) -> Option<u32> {
+        match self {
+            PriceRange::Day => Some(1),
+            PriceRange::Week => Some(7),
+            PriceRange::Month => Some(30),
+            PriceRange::Quarter => Some(90),
+            PriceRange::Year => Some(365),
+            PriceRange::Max => None,
+        }
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        match self {
+            PriceRange::Day => Resolution::FiveMinutes,
+            PriceRange::Week | PriceRange::Month => Resolution::Hour,
+            PriceRange::Quarter | PriceRange::Year | PriceRange::Max => Resolution::Day,
+        }
+    }
+}
+
+impl FromStr for PriceRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1d" => Ok(PriceRange::Day),
+            "7d" => Ok(PriceRange::Week),
+            "30d" => Ok(PriceRange::Month),
+            "90d" => Ok(PriceRange::Quarter),
+            "1y" => Ok(PriceRange::Year),
+            "max" => Ok(PriceRange::Max),
+            _ => Err(format!("Invalid range: {} (expected 1d, 7d, 30d, 90d, 1y or max)", s)),
+        }
+    }
+}
+
+impl fmt::Display for PriceRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = match self {
+            PriceRange::Day => "1d",
+            PriceRange::Week => "7d",
+            PriceRange::Month => "30d",
+            PriceRange::Quarter => "90d",
+            PriceRange::Year => "1y",
+            PriceRange::Max => "max",
+        };
+        write!(f, "{}", range)
+    }
+}
+
+// OHLC prices in USD for the bucket starting at `timestamp` (unix seconds)
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct Candle {
+    pub timestamp: i64,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub open: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub high: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub low: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub close: Decimal,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PriceHistoryResponse {
+    pub chain: String,
+    pub token: String,
+    pub range: String,
+    pub resolution: String, // "5m", "1h" or "1d"
+    pub candles: Vec<Candle>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_start_rounds_down_to_the_resolution() {
+        assert_eq!(Resolution::FiveMinutes.bucket_start(1_700_000_123), 1_700_000_100);
+        assert_eq!(Resolution::FiveMinutes.bucket_start(1_700_000_100), 1_700_000_100);
+        assert_eq!(Resolution::Hour.bucket_start(1_700_003_599), 1_700_002_800);
+        assert_eq!(Resolution::Day.bucket_start(1_700_006_399), 1_699_920_000);
+        assert_eq!(Resolution::Day.bucket_start(1_700_006_400), 1_700_006_400);
+    }
+
+    #[test]
+    fn bucket_start_handles_pre_epoch_timestamps() {
+        assert_eq!(Resolution::Hour.bucket_start(-1), -3600);
+    }
+
+    #[test]
+    fn ranges_round_trip_through_strings() {
+        for range in ["1d", "7d", "30d", "90d", "1y", "max"] {
+            assert_eq!(range.parse::<PriceRange>().unwrap().to_string(), range);
+        }
+        assert!("2d".parse::<PriceRange>().is_err());
+        assert!("".parse::<PriceRange>().is_err());
+    }
+
+    #[test]
+    fn ranges_map_to_resolutions() {
+        assert_eq!(PriceRange::Day.resolution(), Resolution::FiveMinutes);
+        assert_eq!(PriceRange::Week.resolution(), Resolution::Hour);
+        assert_eq!(PriceRange::Month.resolution(), Resolution::Hour);
+        assert_eq!(PriceRange::Quarter.resolution(), Resolution::Day);
+        assert_eq!(PriceRange::Max.resolution(), Resolution::Day);
+        assert_eq!(PriceRange::Max.days(), None);
+    }
+
+    #[test]
+    fn retention_outlasts_the_ranges_served() {
+        for range in [PriceRange::Day, PriceRange::Week, PriceRange::Month, PriceRange::Quarter, PriceRange::Year] {
+            if let Some(retention) = range.resolution().retention_seconds() {
+                assert!(retention > range.days().unwrap() as i64 * 86400);
+            }
+        }
+    }
+}
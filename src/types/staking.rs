@@ -0,0 +1,17 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct StakePosition {
+    pub stake_account: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validator_vote_account: Option<String>,
+    pub activation_state: String, // "activating", "active", "deactivating" or "inactive"
+    pub delegated_amount: f64,
+    pub rent_reserve: f64,
+    pub total_balance: f64,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub value_usd: Decimal,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub value: Option<Decimal>, // value_usd in the portfolio's requested currency
+}
@@ -1,18 +1,82 @@
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Token {
     pub symbol: String,
     pub mint_or_address: String,
     pub amount: f64,
+    #[serde(default)]
+    pub raw_amount: String,
     pub decimals: u8,
-    pub price_usd: f64,
-    pub value_usd: f64,
+    #[serde(default, with = "rust_decimal::serde::float_option")]
+    pub price_usd: Option<Decimal>, // null for tokens no price source covers; value_usd is then 0
+    #[serde(with = "rust_decimal::serde::float")]
+    pub value_usd: Decimal,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_confidence: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub logo_uri: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub price_change_24h: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_program: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<TokenExtensions>,
+    // price_usd and value_usd in the portfolio's requested currency
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub price: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "rust_decimal::serde::float_option")]
+    pub value: Option<Decimal>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TokenExtensions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfer_fee_basis_points: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfer_fee_maximum: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interest_rate_bps: Option<i16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_uri: Option<String>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn token(price_usd: Option<Decimal>, value_usd: Decimal) -> Token {
+        Token {
+            symbol: "USDC".to_string(),
+            mint_or_address: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            amount: 2.5,
+            raw_amount: "2500000".to_string(),
+            decimals: 6,
+            price_usd,
+            value_usd,
+            ..Default::default()
+        }
+    }
+
+    // USD figures are computed as decimals but stay JSON numbers for existing clients
+    #[test]
+    fn usd_figures_serialize_as_numbers() {
+        let priced = serde_json::to_value(token(Some(Decimal::new(9998, 4)), Decimal::new(24995, 4))).unwrap();
+        assert_eq!(priced["price_usd"], json!(0.9998));
+        assert_eq!(priced["value_usd"], json!(2.4995));
+
+        let unpriced = serde_json::to_value(token(None, Decimal::ZERO)).unwrap();
+        assert_eq!(unpriced["price_usd"], json!(null));
+        assert_eq!(unpriced["value_usd"], json!(0.0));
+
+        let parsed: Token = serde_json::from_value(priced).unwrap();
+        assert_eq!((parsed.price_usd, parsed.value_usd), (Some(Decimal::new(9998, 4)), Decimal::new(24995, 4)));
+    }
+}
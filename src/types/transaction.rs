@@ -1,19 +1,114 @@
 use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Transaction {
     pub hash: String,
     pub timestamp: i64,
     #[serde(rename = "type")]
-    pub transaction_type: String, // "send" or "receive"
+    pub transaction_type: String, // "send", "receive", "self" or "other"
     pub amount: f64,
+    #[serde(default)]
+    pub raw_amount: String,
+    #[serde(default)]
+    pub decimals: u8,
     pub token_symbol: String,
     pub chain: String,
-    pub status: String, // "success" or "failed"
+    pub status: String, // "success", "failed" or "pending"
     pub from: String,
     pub to: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_mint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counterparty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee: Option<f64>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionPage {
+    pub transactions: Vec<Transaction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+// A transfer indexed from an EVM node, as stored in ethereum_transfers
+#[derive(Debug, Clone, FromRow)]
+pub struct EthereumTransfer {
+    pub tx_hash: String,
+    pub block_number: i64,
+    pub log_index: i64,
+    pub timestamp: i64,
+    pub from_address: String,
+    pub to_address: String,
+    pub token_address: Option<String>,
+    pub token_symbol: String,
+    pub decimals: i16,
+    pub raw_amount: String,
+    pub status: String,
+    pub fee_wei: Option<String>,
+}
+
+// Ethereum history position, serialized as "<block>:<log_index>"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EthereumCursor {
+    pub block: i64,
+    pub log_index: i64,
+}
+
+impl FromStr for EthereumCursor {
+    type Err = String;
 
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (block, log_index) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid cursor: {}", s))?;
+        Ok(EthereumCursor {
+            block: block.parse().map_err(|_| format!("Invalid cursor block: {}", s))?,
+            log_index: log_index.parse().map_err(|_| format!("Invalid cursor log index: {}", s))?,
+        })
+    }
+}
+
+impl fmt::Display for EthereumCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.block, self.log_index)
+    }
+}
+
+// Bitcoin history position, serialized as "<block height>:<txid>" or "mempool:<txid>"
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BitcoinCursor {
+    pub height: u64, // u64::MAX for unconfirmed transactions, which sort newest
+    pub txid: String,
+}
+
+impl FromStr for BitcoinCursor {
+    type Err = String;
 
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (height, txid) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid cursor: {}", s))?;
+        if txid.len() != 64 || !txid.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid cursor txid: {}", s));
+        }
+        let height = match height {
+            "mempool" => u64::MAX,
+            height => height.parse().map_err(|_| format!("Invalid cursor height: {}", s))?,
+        };
+        Ok(BitcoinCursor { height, txid: txid.to_lowercase() })
+    }
+}
 
+impl fmt::Display for BitcoinCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.height == u64::MAX {
+            write!(f, "mempool:{}", self.txid)
+        } else {
+            write!(f, "{}:{}", self.height, self.txid)
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
 use chrono::{DateTime, Utc};
 
@@ -7,6 +7,7 @@ pub struct User {
     pub id: i32,
     pub email: String,
     pub username: Option<String>,
+    pub default_currency: Option<String>, // Currency balances are converted to by default; None for USD only
     #[sqlx(default)]
     pub created_at: DateTime<Utc>,
     #[sqlx(default)]
@@ -17,6 +18,24 @@ pub struct User {
 pub struct CreateUserRequest {
     pub email: String,
     pub username: Option<String>,
+    pub default_currency: Option<String>,
+}
+
+// Only the fields given are changed; a null default_currency clears it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateUserRequest {
+    pub username: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    pub default_currency: Option<Option<String>>,
+}
+
+// Tells a field sent as null (Some(None)) apart from one left out (None)
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
@@ -39,3 +58,20 @@ pub struct AddWalletRequest {
     pub is_primary: Option<bool>,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_request_tells_null_from_missing() {
+        let missing: UpdateUserRequest = serde_json::from_str(r#"{"username":"a"}"#).unwrap();
+        assert_eq!(missing.default_currency, None);
+
+        let cleared: UpdateUserRequest = serde_json::from_str(r#"{"default_currency":null}"#).unwrap();
+        assert_eq!(cleared.default_currency, Some(None));
+
+        let set: UpdateUserRequest = serde_json::from_str(r#"{"default_currency":"eur"}"#).unwrap();
+        assert_eq!(set.default_currency, Some(Some("eur".to_string())));
+    }
+}
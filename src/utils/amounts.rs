@@ -0,0 +1,145 @@
+// Fixed-point helpers shared by the chain clients. Raw amounts are base-10 integer strings in the
+// token's smallest unit, so they stay exact regardless of size (U256 balances don't fit u128).
+
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::str::FromStr;
+
+// rust_decimal holds at most 28 fractional digits
+const MAX_DECIMAL_SCALE: usize = 28;
+
+// Formats a raw integer amount with `decimals` implied places, e.g. ("1500000", 6) -> "1.5"
+pub fn format_units(raw_amount: &str, decimals: u8) -> String {
+    let digits = raw_amount.trim_start_matches('0');
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return "0".to_string();
+    }
+
+    let decimals = decimals as usize;
+    let (whole, fraction) = if digits.len() > decimals {
+        digits.split_at(digits.len() - decimals)
+    } else {
+        ("0", digits)
+    };
+    let fraction = format!("{:0>width$}", fraction, width = decimals);
+    let fraction = fraction.trim_end_matches('0');
+
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+pub fn to_f64(raw_amount: &str, decimals: u8) -> f64 {
+    format_units(raw_amount, decimals).parse().unwrap_or(0.0)
+}
+
+// USD value of a raw amount, multiplied in decimal so small balances of cheap tokens don't round away
+pub fn usd_value(raw_amount: &str, decimals: u8, price_usd: Decimal) -> Decimal {
+    value_of(&format_units(raw_amount, decimals), price_usd)
+}
+
+// Same as `usd_value` for an already formatted amount such as "12.5". Amounts beyond Decimal's
+// range are worth more than any real market could pay out, so they are left unvalued.
+pub fn value_of(amount: &str, price_usd: Decimal) -> Decimal {
+    to_decimal(amount)
+        .and_then(|amount| amount.checked_mul(price_usd))
+        .map(|value| value.normalize())
+        .unwrap_or_default()
+}
+
+// Totals and conversions saturate instead of panicking on overflow like Decimal's operators
+pub fn sum(values: impl IntoIterator<Item = Decimal>) -> Decimal {
+    values.into_iter().fold(Decimal::ZERO, |total, value| total.saturating_add(value))
+}
+
+pub fn convert(value: Decimal, rate: Decimal) -> Decimal {
+    value.saturating_mul(rate).normalize()
+}
+
+// Amounts with too many fractional digits lose the least significant ones; None when the whole
+// part alone doesn't fit
+pub fn to_decimal(amount: &str) -> Option<Decimal> {
+    let truncated = match amount.split_once('.') {
+        Some((whole, fraction)) => {
+            let keep = MAX_DECIMAL_SCALE.saturating_sub(whole.len()).min(fraction.len());
+            if keep == 0 {
+                whole.to_string()
+            } else {
+                format!("{}.{}", whole, &fraction[..keep])
+            }
+        }
+        None => amount.to_string(),
+    };
+    Decimal::from_str(&truncated).ok()
+}
+
+// Prices arrive as JSON numbers from most providers and as decimal strings from some; small
+// numbers are rendered in exponent form, e.g. 1.5e-9
+pub fn parse_decimal(value: &Value) -> Option<Decimal> {
+    let text = match value {
+        Value::String(text) => text.clone(),
+        Value::Number(number) => number.to_string(),
+        _ => return None,
+    };
+    Decimal::from_str(&text)
+        .or_else(|_| Decimal::from_scientific(&text))
+        .ok()
+        .map(|value| value.normalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U256;
+    use serde_json::json;
+
+    #[test]
+    fn formats_balances_above_u128() {
+        let raw = U256::MAX.to_string();
+        assert_eq!(
+            format_units(&raw, 18),
+            "115792089237316195423570985008687907853269984665640564039457.584007913129639935"
+        );
+        assert_eq!(format_units(&(U256::from(u128::MAX) + 1).to_string(), 0), "340282366920938463463374607431768211456");
+    }
+
+    #[test]
+    fn formats_zero_decimal_tokens_as_whole_units() {
+        assert_eq!(format_units("1500", 0), "1500");
+        assert_eq!(format_units("0", 0), "0");
+        assert_eq!(usd_value("1500", 0, Decimal::new(2, 1)), Decimal::from(300));
+    }
+
+    #[test]
+    fn trims_trailing_and_leading_zeros() {
+        assert_eq!(format_units("1500000", 6), "1.5");
+        assert_eq!(format_units("1000000", 6), "1");
+        assert_eq!(format_units("100", 6), "0.0001");
+        assert_eq!(format_units("000120", 2), "1.2");
+        assert_eq!(format_units("", 6), "0");
+        assert_eq!(usd_value("2500000", 6, Decimal::from(2)).to_string(), "5");
+    }
+
+    #[test]
+    fn values_dust_exactly_and_leaves_unrepresentable_amounts_unvalued() {
+        // One wei of a token priced at a millionth of a cent
+        assert_eq!(usd_value("1", 18, Decimal::new(1, 8)).to_string(), "0.00000000000000000000000001");
+        assert_eq!(usd_value(&U256::MAX.to_string(), 0, Decimal::ONE), Decimal::ZERO);
+    }
+
+    #[test]
+    fn parses_prices_from_numbers_exponents_and_strings() {
+        assert_eq!(parse_decimal(&json!(0.1)).unwrap().to_string(), "0.1");
+        assert_eq!(parse_decimal(&json!(1.5e-9)).unwrap().to_string(), "0.0000000015");
+        assert_eq!(parse_decimal(&json!("2500.50")).unwrap().to_string(), "2500.5");
+        assert_eq!(parse_decimal(&json!(null)), None);
+    }
+
+    #[test]
+    fn totals_saturate_instead_of_overflowing() {
+        assert_eq!(sum([Decimal::MAX, Decimal::ONE]), Decimal::MAX);
+        assert_eq!(convert(Decimal::MAX, Decimal::TWO), Decimal::MAX);
+    }
+}
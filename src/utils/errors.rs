@@ -11,6 +11,12 @@ pub enum AppError {
     #[error("Invalid address: {0}")]
     InvalidAddress(String),
     
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    
+    #[error("Not found: {0}")]
+    NotFound(String),
+    
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
     
@@ -34,6 +40,8 @@ impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, error_message) = match self {
             AppError::InvalidAddress(msg) => (StatusCode::BAD_REQUEST, msg),
+            AppError::InvalidRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             AppError::Database(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)),
             AppError::Serialization(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Serialization error: {}", e)),
             AppError::Http(e) => (StatusCode::BAD_GATEWAY, format!("HTTP error: {}", e)),
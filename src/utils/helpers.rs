@@ -1,19 +1,62 @@
-// Helper functions for address validation and formatting
+use ethers::types::Address as EthAddress;
+use ethers::utils::to_checksum;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
 
+// Helper functions for address validation and formatting.
+// EVM addresses are stored lowercase and returned EIP-55 checksummed.
+
+// 0x-prefixed 40-digit hex; mixed-case input must carry a valid EIP-55 checksum
+pub fn is_valid_evm_address(address: &str) -> bool {
+    let Some(hex) = address.strip_prefix("0x") else {
+        return false;
+    };
+    if hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return false;
+    }
+
+    // All-lowercase and all-uppercase addresses carry no checksum
+    let is_mixed_case = hex.chars().any(|c| c.is_ascii_lowercase()) && hex.chars().any(|c| c.is_ascii_uppercase());
+    !is_mixed_case || checksum_evm_address(address) == address
+}
+
+// Canonical storage form; only called on valid addresses
+pub fn normalize_evm_address(address: &str) -> String {
+    address.to_lowercase()
+}
+
+// Canonical storage form of a parsed address
+pub fn evm_address_key(address: &EthAddress) -> String {
+    normalize_evm_address(&format!("{:?}", address))
+}
+
+// EIP-55 form for responses; anything that isn't an address is returned unchanged
+pub fn checksum_evm_address(address: &str) -> String {
+    match EthAddress::from_str(address) {
+        Ok(addr) => to_checksum(&addr, None),
+        Err(_) => address.to_string(),
+    }
+}
+
+pub fn is_valid_solana_address(address: &str) -> bool {
+    Pubkey::from_str(address).is_ok()
+}
+
+// Display form of a stored address for the chain it belongs to
 pub fn format_address(address: &str, chain: &str) -> String {
     match chain {
-        "ethereum" => {
-            if address.starts_with("0x") {
-                format!("0x{}", &address[2..].to_lowercase())
-            } else {
-                address.to_lowercase()
-            }
-        }
-        "solana" => address.to_string(),
-        _ => address.to_string(),
+        "solana" | "bitcoin" => address.to_string(),
+        // Every other chain, including "evm" wallets, uses EVM addresses
+        _ => checksum_evm_address(address),
     }
 }
 
+// Uppercase ISO 4217 code, or None for anything that isn't three letters
+pub fn normalize_currency(currency: &str) -> Option<String> {
+    let currency = currency.trim();
+    (currency.len() == 3 && currency.chars().all(|c| c.is_ascii_alphabetic())).then(|| currency.to_uppercase())
+}
+
 pub fn truncate_address(address: &str, start: usize, end: usize) -> String {
     if address.len() <= start + end {
         return address.to_string();
@@ -21,3 +64,57 @@ pub fn truncate_address(address: &str, start: usize, end: usize) -> String {
     format!("{}...{}", &address[..start], &address[address.len() - end..])
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from EIP-55
+    const EIP55_VECTORS: [&str; 4] = [
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    #[test]
+    fn checksums_match_eip55_vectors() {
+        for expected in EIP55_VECTORS {
+            assert_eq!(checksum_evm_address(&expected.to_lowercase()), expected);
+            assert_eq!(checksum_evm_address(&format!("0x{}", expected[2..].to_uppercase())), expected);
+        }
+    }
+
+    #[test]
+    fn accepts_checksummed_and_single_case_addresses() {
+        for address in EIP55_VECTORS {
+            assert!(is_valid_evm_address(address));
+            assert!(is_valid_evm_address(&address.to_lowercase()));
+            assert!(is_valid_evm_address(&format!("0x{}", address[2..].to_uppercase())));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        // First vector with the case of one letter flipped
+        assert!(!is_valid_evm_address("0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"));
+    }
+
+    #[test]
+    fn rejects_malformed_addresses() {
+        assert!(!is_valid_evm_address("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"));
+        assert!(!is_valid_evm_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1bea"));
+        assert!(!is_valid_evm_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beagg"));
+    }
+
+    #[test]
+    fn normalizes_to_lowercase() {
+        assert_eq!(normalize_evm_address(EIP55_VECTORS[0]), "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed");
+    }
+
+    #[test]
+    fn parsed_addresses_use_the_same_key() {
+        for address in EIP55_VECTORS {
+            assert_eq!(evm_address_key(&address.parse().unwrap()), normalize_evm_address(address));
+        }
+    }
+}
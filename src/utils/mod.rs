@@ -1,3 +1,4 @@
+pub mod amounts;
 pub mod errors;
 pub mod helpers;
 